@@ -0,0 +1,166 @@
+// The AST engine (`--engine ast`) finds the tables of a statement in its
+// syntax tree rather than with regexes, which tells the target of a statement
+// apart from the tables it reads however deeply they're nested. Statements
+// that the parser doesn't understand (e.g. T-SQL it doesn't support) fall back
+// to the regex engine.
+use clap::ValueEnum;
+use sqlparser::ast::{ FromTable, ObjectName, Query, SetExpr, Statement, TableFactor, Visit, Visitor };
+use sqlparser::dialect::{
+    DatabricksDialect, GenericDialect, MsSqlDialect, MySqlDialect, PostgreSqlDialect, SQLiteDialect, SnowflakeDialect,
+};
+use sqlparser::parser::Parser;
+use std::collections::{ HashMap, HashSet };
+use std::ops::ControlFlow;
+
+use crate::dialect::Dialect;
+use crate::StatementType;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Engine {
+    /// find tables with regexes, statement by statement
+    #[default]
+    Regex,
+    /// find tables in the syntax tree of each statement
+    Ast,
+}
+
+/// the tables that a statement writes (or reads, for a SELECT) and the tables
+/// it reads besides, or None if the statement can't be parsed as one of the
+/// given type
+pub fn find_tables(text: &str, statement_type: StatementType, dialect: Dialect) -> Option<(Vec<String>, Vec<String>)> {
+    let parser_dialect: Box<dyn sqlparser::dialect::Dialect> = match dialect {
+        Dialect::Tsql       => Box::new(MsSqlDialect {}),
+        Dialect::Snowflake  => Box::new(SnowflakeDialect {}),
+        Dialect::Databricks => Box::new(DatabricksDialect {}),
+        Dialect::Sqlite     => Box::new(SQLiteDialect {}),
+        Dialect::Postgres   => Box::new(PostgreSqlDialect {}),
+        Dialect::Mysql      => Box::new(MySqlDialect {}),
+        // sqlparser has no Oracle dialect
+        Dialect::Oracle     => Box::new(GenericDialect {}),
+        Dialect::Auto       => Box::new(GenericDialect {}),
+    };
+    let statements = Parser::parse_sql(parser_dialect.as_ref(), text).ok()?;
+    let statement = statements.first()?;
+    let mut relations = Relations::default();
+    let _ = statement.visit(&mut relations);
+
+    // an INSERT or UPDATE after a WITH clause is the body of a query
+    let statement = match statement {
+        Statement::Query(query) => match query.body.as_ref() {
+            SetExpr::Insert(statement) | SetExpr::Update(statement) => statement,
+            _ => statement,
+        },
+        _ => statement,
+    };
+    let targets: Vec<String> = match (statement_type, statement) {
+        (StatementType::Select, Statement::Query(_)) => relations.tables(),
+        (StatementType::Insert, Statement::Insert(insert)) => vec![relations.resolve(&insert.table_name)],
+        (StatementType::Update, Statement::Update { table, .. }) => {
+            table_name(&table.relation).map(|name| relations.resolve(name)).into_iter().collect()
+        },
+        (StatementType::Delete, Statement::Delete(delete)) => match &delete.from {
+            // `DELETE o FROM t_order o` deletes from the tables before FROM
+            _ if !delete.tables.is_empty() => delete.tables.iter().map(|name| relations.resolve(name)).collect(),
+            FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from) => from.first()
+                .and_then(|table| table_name(&table.relation))
+                .map(|name| relations.resolve(name))
+                .into_iter()
+                .collect(),
+        },
+        (StatementType::Merge, Statement::Merge { table, .. }) => {
+            table_name(table).map(|name| relations.resolve(name)).into_iter().collect()
+        },
+        // the statement isn't the one that was detected on its first line
+        _ => return None,
+    };
+    let reads: Vec<String> = relations.tables().into_iter().filter(|table| !targets.contains(table)).collect();
+    Some((targets, reads))
+}
+
+fn table_name(table_factor: &TableFactor) -> Option<&ObjectName> {
+    match table_factor {
+        TableFactor::Table { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// the name of a table as `schema.table` (without its database), or `table`
+/// if it has no schema
+fn qualified_name(name: &ObjectName) -> String {
+    let parts: Vec<&str> = name.0.iter().map(|ident| ident.value.as_str()).collect();
+    match parts.as_slice() {
+        [.., schema, table] if !schema.is_empty() => format!("{}.{}", schema, table),
+        [.., table] => table.to_string(),
+        [] => String::new(),
+    }
+}
+
+/// the relations of a statement: the tables and the names that only stand in
+/// for a table (aliases and common table expressions)
+#[derive(Default)]
+struct Relations {
+    names:   Vec<String>,
+    // lowercase alias => table
+    aliases: HashMap<String, String>,
+    ctes:    HashSet<String>,
+}
+
+impl Relations {
+    /// the table of a name, which may be an alias
+    fn resolve(&self, name: &ObjectName) -> String {
+        let name = qualified_name(name);
+        self.aliases.get(&name.to_lowercase()).cloned().unwrap_or(name)
+    }
+
+    /// the tables, in order of appearance
+    fn tables(&self) -> Vec<String> {
+        self.names.iter()
+            .filter(|name| !self.aliases.contains_key(&name.to_lowercase()) && !self.ctes.contains(&name.to_lowercase()))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Visitor for Relations {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        for cte in query.with.iter().flat_map(|with| &with.cte_tables) {
+            self.ctes.insert(cte.alias.name.value.to_lowercase());
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<()> {
+        if let TableFactor::Table { name, alias: Some(alias), .. } = table_factor {
+            self.aliases.insert(alias.name.value.to_lowercase(), qualified_name(name));
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<()> {
+        let name = qualified_name(relation);
+        if !self.names.contains(&name) { self.names.push(name); }
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_ast_tables() {
+        let tables = |text: &str, statement_type: StatementType| find_tables(text, statement_type, Dialect::Tsql);
+        assert_eq!(tables("UPDATE o SET status = 1 FROM t_order o JOIN t_wave w ON w.id = o.wave_id", StatementType::Update),
+            Some((vec![String::from("t_order")], vec![String::from("t_wave")])));
+        assert_eq!(tables("INSERT INTO t_order_log (id) SELECT id FROM (SELECT id FROM t_order) x", StatementType::Insert),
+            Some((vec![String::from("t_order_log")], vec![String::from("t_order")])));
+        assert_eq!(tables("DELETE FROM t_pick WHERE order_id IN (SELECT id FROM dbo.t_order WHERE status = 9)", StatementType::Delete),
+            Some((vec![String::from("t_pick")], vec![String::from("dbo.t_order")])));
+        assert_eq!(tables("WITH recent AS (SELECT id FROM t_order) SELECT * FROM t_pick p JOIN recent r ON r.id = p.order_id", StatementType::Select),
+            Some((vec![String::from("t_order"), String::from("t_pick")], Vec::new())));
+        assert_eq!(tables("UPDATE t_order SET status = (1", StatementType::Update), None);
+        assert_eq!(tables("SELECT 1", StatementType::Delete), None);
+    }
+}
@@ -0,0 +1,86 @@
+// The catalog is the tables declared (CREATE TABLE) in the scanned files. Table
+// names are case-insensitive in most databases, so the same table can be
+// written as `T_ORDER` in one procedure and `t_order` in another; the catalog
+// gives every table the name it was declared with. The declarations are found
+// in the files read for matches (those that pass the prefilter), and are kept
+// with a checkpoint and JSON results so that resumed scans and merged shards
+// name the tables like a single scan.
+use regex::Regex;
+use std::collections::{ BTreeMap, HashMap };
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::unbracket;
+use crate::Statement;
+
+/// the tables declared in each file, in the order of the paths
+pub type Declarations = BTreeMap<PathBuf, Vec<String>>;
+
+#[derive(Debug, Default)]
+pub struct Catalog {
+    // lowercase name => declared name
+    names: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// the catalog of the declarations of files, the first declaration of a
+    /// table (in the order of the paths) winning, e.g. over a later
+    /// re-creation
+    pub fn new(declarations: &Declarations) -> Catalog {
+        let mut catalog = Catalog::default();
+        for names in declarations.values() {
+            catalog.declare(names);
+        }
+        catalog
+    }
+
+    /// add the tables declared in a file, unless they already were
+    pub fn declare(&mut self, names: &[String]) {
+        for name in names {
+            self.names.entry(name.to_lowercase()).or_insert_with(|| name.clone());
+        }
+    }
+
+    /// the declared name of a table, or the name as given if the table isn't
+    /// declared in the scanned files
+    pub fn canonical(&self, table: &str) -> String {
+        self.names.get(&table.to_lowercase()).cloned().unwrap_or_else(|| table.to_string())
+    }
+
+    /// give the tables of the statements the names they're declared with
+    pub fn name_tables(&self, statements: &mut [Statement]) {
+        for statement in statements {
+            statement.table = self.canonical(&statement.table);
+            for table in &mut statement.tables {
+                table.name = self.canonical(&table.name);
+            }
+        }
+    }
+}
+
+/// the tables declared in a text, by their name without their schema
+pub fn declarations(text: &str) -> Vec<String> {
+    static CREATE_RE: OnceLock<Regex> = OnceLock::new();
+    let create_re = CREATE_RE.get_or_init(|| {
+        Regex::new(r"(?im)^\s*create\s+((global\s+)?(temp|temporary)\s+)?table\s+(if\s+not\s+exists\s+)?((\[[^\]]+\]|\w+)\.)*(\[[^\]]+\]|[#\w]+)")
+            .expect("regex didn't compile")
+    });
+    create_re.captures_iter(text).map(|captures| unbracket(&captures[7])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_table_names() {
+        let catalog = Catalog::new(&Declarations::from([
+            (PathBuf::from("b.sql"), declarations("CREATE TABLE t_order (id INT)")),
+            (PathBuf::from("a.sql"), declarations("CREATE TABLE dbo.[T_Order] (id INT)\nGO\n  create table #Picks (id INT)\n")),
+        ]));
+        assert_eq!(catalog.canonical("T_ORDER"), "T_Order");
+        assert_eq!(catalog.canonical("t_order"), "T_Order");
+        assert_eq!(catalog.canonical("#picks"), "#Picks");
+        assert_eq!(catalog.canonical("t_wave"), "t_wave");
+    }
+}
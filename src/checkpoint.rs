@@ -0,0 +1,123 @@
+// A checkpoint records which files have been completely scanned (and the
+// statements found in them, and the tables they declare) so that a long-running scan can be resumed after
+// an interruption without re-reading the files that were already finished.
+use serde::{ Deserialize, Serialize };
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{ BufReader, BufWriter, Error, ErrorKind };
+use std::path::{ Path, PathBuf };
+use std::time::{ Duration, Instant };
+
+use crate::catalog::Declarations;
+use crate::Statement;
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    queries:      Vec<String>,
+    completed:    HashSet<PathBuf>,
+    statements:   Vec<Statement>,
+    #[serde(default)]
+    declarations: Declarations,
+    #[serde(skip)]
+    path:         PathBuf,
+    #[serde(skip)]
+    interval:     Duration,
+    #[serde(skip, default = "Instant::now")]
+    last_saved:   Instant,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path, queries: &[String], interval: Duration) -> Checkpoint {
+        Checkpoint {
+            queries:      queries.to_vec(),
+            completed:    HashSet::new(),
+            statements:   Vec::new(),
+            declarations: Declarations::new(),
+            path:         path.to_path_buf(),
+            interval,
+            last_saved:   Instant::now(),
+        }
+    }
+
+    /// load a checkpoint written by a previous run.
+    /// The checkpoint is only valid for the exact same set of search queries,
+    /// otherwise the recorded statements would not match the current search.
+    pub fn load(path: &Path, queries: &[String], interval: Duration) -> Result<Checkpoint, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut checkpoint: Checkpoint = serde_json::from_reader(reader)?;
+        if checkpoint.queries != queries {
+            return Err(Error::new(ErrorKind::InvalidData, format!(
+                "checkpoint was written for different search queries: {}",
+                checkpoint.queries.join(" "))));
+        }
+        checkpoint.path     = path.to_path_buf();
+        checkpoint.interval = interval;
+        Ok(checkpoint)
+    }
+
+    /// save future progress to a different file than the one loaded from
+    pub fn with_path(mut self, path: &Path) -> Checkpoint {
+        self.path = path.to_path_buf();
+        self
+    }
+
+    pub fn is_complete(&self, file_path: &Path) -> bool {
+        self.completed.contains(file_path)
+    }
+
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    pub fn declarations(&self) -> &Declarations {
+        &self.declarations
+    }
+
+    /// mark a file as completely scanned, writing the checkpoint to disk if
+    /// the checkpoint interval has elapsed since it was last saved
+    pub fn record(&mut self, file_path: &Path, statements: &[Statement], declared: &[String]) -> Result<(), Error> {
+        self.completed.insert(file_path.to_path_buf());
+        self.statements.extend_from_slice(statements);
+        if !declared.is_empty() {
+            self.declarations.insert(file_path.to_path_buf(), declared.to_vec());
+        }
+        if self.last_saved.elapsed() >= self.interval {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        // write to a temporary file first so that an interruption while
+        // saving never leaves a truncated checkpoint behind
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(writer, self)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn resume_requires_same_queries() {
+        let dir = TempDir::new("checkpoint");
+        let path = dir.path().join("checkpoint.json");
+        let queries = vec![String::from("u:t_order")];
+        let mut checkpoint = Checkpoint::new(&path, &queries, Duration::ZERO);
+        checkpoint.record(Path::new("a.sql"), &[], &[]).unwrap();
+
+        let resumed = Checkpoint::load(&path, &queries, Duration::ZERO).unwrap();
+        assert!(resumed.is_complete(Path::new("a.sql")));
+        assert!(!resumed.is_complete(Path::new("b.sql")));
+
+        let other_queries = vec![String::from("d:t_order")];
+        assert!(Checkpoint::load(&path, &other_queries, Duration::ZERO).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+// `--clipboard` scans the SQL on the system clipboard, e.g. a statement copied
+// out of SSMS, without saving it to a file first. The text is scanned as a file
+// in the temporary directory, which is removed once the scan is done, and its
+// statements are reported as those of CLIPBOARD_PATH.
+use std::path::{ Path, PathBuf };
+
+use crate::error::{ Context, Result };
+
+/// the path of the statements found on the clipboard
+pub const CLIPBOARD_PATH: &str = "<clipboard>";
+
+/// the SQL of the clipboard, as a file for the duration of the scan
+pub struct ClipboardFile {
+    path: PathBuf,
+}
+
+impl ClipboardFile {
+    pub fn read() -> Result<ClipboardFile> {
+        let text = arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .context(|| String::from("Unable to read the clipboard"))?;
+        let path = std::env::temp_dir().join(format!("gresql-clipboard-{}.sql", std::process::id()));
+        std::fs::write(&path, text).context(|| format!("Unable to write {}", path.display()))?;
+        Ok(ClipboardFile { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ClipboardFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,88 @@
+// Colored output (`--color`), like ripgrep's: the path and line numbers of each
+// match are colored, and the keyword of the statement and the mentions of its
+// table are highlighted in its text. Colors are only written to a terminal,
+// unless they're asked for, and NO_COLOR turns them off.
+use clap::ValueEnum;
+use std::io::IsTerminal;
+
+use gresql::ByteSpan;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    /// color the output if stdout is a terminal and NO_COLOR isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+const PATH:    &str = "\x1b[35m";
+const LINE:    &str = "\x1b[32m";
+const KEYWORD: &str = "\x1b[1;34m";
+const TABLE:   &str = "\x1b[1;31m";
+const RESET:   &str = "\x1b[0m";
+
+impl ColorChoice {
+    /// whether the output to stdout is colored
+    pub fn enabled(&self) -> bool {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        self.is_enabled(std::io::stdout().is_terminal(), no_color)
+    }
+
+    fn is_enabled(&self, is_terminal: bool, no_color: bool) -> bool {
+        match self {
+            ColorChoice::Auto   => is_terminal && !no_color,
+            ColorChoice::Always => true,
+            ColorChoice::Never  => false,
+        }
+    }
+}
+
+fn paint(color: &str, text: &str) -> String {
+    format!("{}{}{}", color, text, RESET)
+}
+
+pub fn path(text: &str) -> String {
+    paint(PATH, text)
+}
+
+pub fn line(text: &str) -> String {
+    paint(LINE, text)
+}
+
+/// the text of a statement with its keyword and the mentions of its table
+/// highlighted, the spans being bytes of the text
+pub fn highlight(text: &str, keyword_span: Option<ByteSpan>, table_spans: &[ByteSpan]) -> String {
+    let mut spans: Vec<(ByteSpan, &str)> = keyword_span.into_iter().map(|span| (span, KEYWORD))
+        .chain(table_spans.iter().map(|span| (*span, TABLE)))
+        .filter(|(span, _)| span.begin < span.end && text.get(span.begin..span.end).is_some())
+        .collect();
+    spans.sort_by_key(|(span, _)| span.begin);
+    let mut highlighted = String::new();
+    let mut end = 0;
+    for (span, color) in spans {
+        // spans never overlap, but a broken one mustn't break the text
+        if span.begin < end { continue; }
+        highlighted.push_str(&text[end..span.begin]);
+        highlighted.push_str(&paint(color, &text[span.begin..span.end]));
+        end = span.end;
+    }
+    highlighted.push_str(&text[end..]);
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_matches() {
+        let text = "UPDATE o SET x = 1 FROM t_order o";
+        let highlighted = highlight(text, Some(ByteSpan { begin: 0, end: 6 }), &[ByteSpan { begin: 24, end: 31 }]);
+        assert_eq!(highlighted, "\x1b[1;34mUPDATE\x1b[0m o SET x = 1 FROM \x1b[1;31mt_order\x1b[0m o");
+        assert_eq!(highlight(text, None, &[]), text);
+        assert!(!ColorChoice::Auto.is_enabled(false, false));
+        assert!(!ColorChoice::Auto.is_enabled(true, true));
+        assert!(ColorChoice::Always.is_enabled(false, true));
+    }
+}
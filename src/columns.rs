@@ -0,0 +1,173 @@
+// The columns that a statement writes, for the queries that only match the
+// statements touching some columns of their tables (`-s u:t_order@order_status`):
+// the columns assigned by the SET clause of an UPDATE (or of a MERGE or an
+// upsert) and the columns listed by an INSERT. An INSERT without a column list
+// writes every column of its table.
+use regex::Regex;
+
+use crate::{ normalize_name, split_schema, unbracket, StatementType };
+
+/// whether a statement (its cleaned text) writes a column, whose name is
+/// compared ignoring case and whatever it's qualified with
+pub fn writes_column(statement_type: StatementType, text: &str, column: &str) -> bool {
+    let column = column_name(column);
+    let is_column = |name: &String| name.eq_ignore_ascii_case(&column);
+    match statement_type {
+        StatementType::Update => set_columns(text).iter().any(is_column),
+        StatementType::Insert => insert_columns(text).is_none_or(|columns| columns.iter().any(is_column)),
+        StatementType::Merge  => set_columns(text).iter().any(is_column)
+            || (has_insert(text) && insert_columns(text).is_none_or(|columns| columns.iter().any(is_column))),
+        _ => false,
+    }
+}
+
+/// a column without its table (or alias) and brackets
+fn column_name(name: &str) -> String {
+    let name = normalize_name(name.trim());
+    unbracket(split_schema(&name).1).trim_matches('"').to_string()
+}
+
+/// the columns assigned by the SET clauses of a statement (a MERGE has one
+/// for each of its `WHEN MATCHED THEN UPDATE`s), which end at the first
+/// clause that follows them
+fn set_columns(text: &str) -> Vec<String> {
+    let set_re = Regex::new(r"(?i)\bset\s").expect("regex didn't compile");
+    let target_re = Regex::new(r#"^(\[[^\]]*\]|"[^"]*"|[\w#$]+)(\.(\[[^\]]*\]|"[^"]*"|[\w#$]+))*$"#)
+        .expect("regex didn't compile");
+    let mut columns: Vec<String> = Vec::new();
+    for m in set_re.find_iter(text) {
+        if !is_top_level(&text[..m.start()]) { continue; }
+        // `SET @total = qty = qty - 1` assigns both a variable and a column,
+        // and `qty += 1` is an assignment as well
+        for assignment in assignments(&text[m.end()..]) {
+            let targets: Vec<&str> = assignment.split('=').collect();
+            for target in &targets[..targets.len() - 1] {
+                let target = target.trim().trim_end_matches(['+', '-', '*', '/', '%', '&', '|', '^']).trim();
+                if target.starts_with('@') { continue; }
+                if !target_re.is_match(target) { break; }
+                columns.push(column_name(target));
+            }
+        }
+    }
+    columns
+}
+
+/// the assignments of a SET clause, split at the commas outside of
+/// parentheses, strings and CASE expressions
+fn assignments(text: &str) -> Vec<&str> {
+    let end_keywords = ["from", "where", "output", "option", "when", "returning"];
+    let mut assignments: Vec<&str> = Vec::new();
+    let mut depth = 0;
+    let mut cases = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    let mut word_start: Option<usize> = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if let Some(closing) = quote {
+            if c == closing { quote = None; }
+            continue;
+        }
+        let is_word = c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '$');
+        if is_word {
+            word_start.get_or_insert(i);
+            continue;
+        }
+        if let Some(begin) = word_start.take() {
+            match text[begin..i].to_lowercase().as_str() {
+                "case" => cases += 1,
+                "end" if cases > 0 => cases -= 1,
+                word if depth == 0 && cases == 0 && end_keywords.contains(&word) => {
+                    assignments.push(&text[start..begin]);
+                    return assignments;
+                },
+                _ => {},
+            }
+        }
+        match c {
+            '\'' => quote = Some('\''),
+            '"'  => quote = Some('"'),
+            '['  => quote = Some(']'),
+            '('  => depth += 1,
+            ')' if depth == 0 => {
+                assignments.push(&text[start..i]);
+                return assignments;
+            },
+            ')'  => depth -= 1,
+            ',' | ';' if depth == 0 && cases == 0 => {
+                assignments.push(&text[start..i]);
+                if c == ';' { return assignments; }
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    assignments.push(&text[start..]);
+    assignments
+}
+
+/// whether a position of a statement is outside of any parentheses or string
+fn is_top_level(before: &str) -> bool {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    for c in before.chars() {
+        match (quote, c) {
+            (Some(closing), c) if c == closing => quote = None,
+            (Some(_), _) => {},
+            (None, '\'') => quote = Some('\''),
+            (None, '[')  => quote = Some(']'),
+            (None, '(')  => depth += 1,
+            (None, ')')  => depth -= 1,
+            _ => {},
+        }
+    }
+    depth == 0 && quote.is_none()
+}
+
+fn has_insert(text: &str) -> bool {
+    Regex::new(r"(?i)\binsert\b").expect("regex didn't compile").is_match(text)
+}
+
+/// the column list of an INSERT, or None if it has none (e.g.
+/// `INSERT INTO t_order VALUES (...)`, or the `INSERT VALUES (...)` of a MERGE)
+fn insert_columns(text: &str) -> Option<Vec<String>> {
+    let insert_re = Regex::new(
+        r#"(?i)\binsert\b(?:\s+or\s+\w+)?(?:\s+into)?\s*((?:\[[^\]]*\]|"[^"]*"|[\w.#@$])*)\s*(?:with\s*\([^)]*\)\s*)?\(([^)]*)\)"#)
+        .expect("regex didn't compile");
+    let captures = insert_re.captures(text)?;
+    if matches!(captures[1].to_lowercase().as_str(), "values" | "select" | "default") {
+        return None;
+    }
+    let list = captures[2].trim();
+    if list.split_whitespace().next().is_some_and(|word| word.eq_ignore_ascii_case("select")) {
+        return None;
+    }
+    Some(list.split(',').map(column_name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_written_columns() {
+        let update = "UPDATE o SET o.order_status = 'S', [ship_date] = CASE WHEN x = 1 THEN GETDATE() END, @n = qty += 1 FROM t_order o WHERE o.order_total = 0";
+        assert!(writes_column(StatementType::Update, update, "order_status"));
+        assert!(writes_column(StatementType::Update, update, "SHIP_DATE"));
+        assert!(writes_column(StatementType::Update, update, "qty"));
+        assert!(!writes_column(StatementType::Update, update, "order_total"));
+        assert!(!writes_column(StatementType::Update, update, "x"));
+
+        let insert = "INSERT INTO dbo.t_order WITH (TABLOCK) (order_id, [order_status]) SELECT order_id, 'N' FROM t_import";
+        assert!(writes_column(StatementType::Insert, insert, "order_status"));
+        assert!(!writes_column(StatementType::Insert, insert, "ship_date"));
+        assert!(writes_column(StatementType::Insert, "INSERT INTO t_order VALUES (1, 'N')", "ship_date"));
+
+        let merge = "MERGE t_order AS t USING t_import AS s ON t.order_id = s.order_id \
+            WHEN MATCHED THEN UPDATE SET t.order_total = s.order_total \
+            WHEN NOT MATCHED THEN INSERT (order_id, order_status) VALUES (s.order_id, 'N');";
+        assert!(writes_column(StatementType::Merge, merge, "order_total"));
+        assert!(writes_column(StatementType::Merge, merge, "order_status"));
+        assert!(!writes_column(StatementType::Merge, merge, "ship_date"));
+        assert!(!writes_column(StatementType::Delete, "DELETE FROM t_order", "order_status"));
+    }
+}
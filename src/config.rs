@@ -0,0 +1,263 @@
+// The config file (`--config`, or `.gresql.toml` in the current directory)
+// holds settings that are shared by a team rather than given on every run.
+// Config files (and policies) can refer to environment variables as
+// `${NAME}`, so that a shared file works across machines and CI.
+use glob::{ MatchOptions, Pattern };
+use regex::Regex;
+use serde::{ Deserialize, Deserializer, Serialize, Serializer };
+use std::io::{ Error, ErrorKind };
+use std::path::Path;
+
+use crate::dialect::Dialect;
+use crate::{ ScanOpts, StatementType };
+
+pub const DEFAULT_CONFIG_FILE: &str = ".gresql.toml";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// regexes whose matches in a file are emitted as extra result columns
+    #[serde(rename = "capture", skip_serializing_if = "Vec::is_empty")]
+    pub captures: Vec<Capture>,
+    /// the modules that files belong to, by the patterns of their paths
+    #[serde(rename = "module", skip_serializing_if = "Vec::is_empty")]
+    pub modules:  Vec<Module>,
+    /// how the files whose path matches a glob are scanned, e.g. with another
+    /// dialect than the rest of a monorepo
+    #[serde(rename = "path", skip_serializing_if = "Vec::is_empty")]
+    pub paths:    Vec<PathRule>,
+    /// the words that begin statements of a type besides its own keywords
+    #[serde(rename = "keyword", skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<Keyword>,
+}
+
+/// a module of the codebase, e.g. the procedures of wave management, made of
+/// the files whose path matches its pattern
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Module {
+    pub name:    String,
+    #[serde(deserialize_with = "deserialize_regex", serialize_with = "serialize_regex")]
+    pub pattern: Regex,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Capture {
+    pub name:    String,
+    #[serde(deserialize_with = "deserialize_regex", serialize_with = "serialize_regex")]
+    pub pattern: Regex,
+}
+
+/// settings overriding those of the scan for the files matching a glob, each
+/// setting that is given replacing that of the scan (or of an earlier rule)
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathRule {
+    #[serde(deserialize_with = "deserialize_pattern", serialize_with = "serialize_pattern")]
+    pub glob:        Pattern,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialect:     Option<Dialect>,
+    /// whether statements built as strings for dynamic SQL are found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_sql: Option<bool>,
+}
+
+/// a word that begins statements of one of the types, e.g. `UPSERT` or a macro
+/// of the codebase such as `AUDIT_INSERT`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyword {
+    pub word:           String,
+    /// the type of the statements, by its name or its letter (`insert` or `i`)
+    #[serde(rename = "type", deserialize_with = "deserialize_statement_type")]
+    pub statement_type: StatementType,
+    /// what the word is read as to find the tables of its statements, e.g.
+    /// `INSERT INTO` for `AUDIT_INSERT t_order (...)`, or the keyword of the
+    /// type if it isn't given (`INSERT` for `UPSERT INTO t_order (...)`)
+    #[serde(rename = "as", default, skip_serializing_if = "Option::is_none")]
+    pub read_as:        Option<String>,
+}
+
+fn deserialize_statement_type<'de, D: Deserializer<'de>>(deserializer: D) -> Result<StatementType, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    let mut letters = name.chars();
+    let statement_type = match (letters.next(), letters.next()) {
+        (Some(letter), None) => StatementType::try_from(letter.to_ascii_lowercase()).ok(),
+        _ => StatementType::all().into_iter().find(|t| t.to_string().eq_ignore_ascii_case(&name)),
+    };
+    statement_type.ok_or_else(|| serde::de::Error::custom(format!("unknown statement type {}", name)))
+}
+
+fn deserialize_pattern<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+    Pattern::new(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+// patterns and regexes are written as they were given, so that a config
+// written out (`--print-config`) reads back the same
+fn serialize_pattern<S: Serializer>(pattern: &Pattern, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(pattern.as_str())
+}
+
+fn serialize_regex<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(regex.as_str())
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        let text = interpolate(&std::fs::read_to_string(path)?)?;
+        toml::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// the module of a file: the first module whose pattern matches its path
+    /// (with `/` separators, whatever the platform)
+    pub fn module(&self, path: &Path) -> Option<&str> {
+        let path = path.to_string_lossy().replace('\\', "/");
+        self.modules.iter()
+            .find(|module| module.pattern.is_match(&path))
+            .map(|module| module.name.as_str())
+    }
+
+    /// the options that a file is scanned with: those of the scan, with the
+    /// settings of every path rule whose glob matches the file (ignoring
+    /// case), in the order of the rules
+    pub fn scan_opts(&self, path: &Path, opts: &ScanOpts) -> ScanOpts {
+        let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+        let path = path.strip_prefix(".").unwrap_or(path);
+        let mut opts = opts.clone();
+        for rule in self.paths.iter().filter(|rule| rule.glob.matches_path_with(path, options)) {
+            if let Some(dialect) = rule.dialect { opts.dialect = dialect; }
+            if let Some(dynamic_sql) = rule.dynamic_sql { opts.dynamic_sql = dynamic_sql; }
+        }
+        opts
+    }
+}
+
+/// the text of a config file with each `${NAME}` replaced by the value of the
+/// environment variable, and each `$${` by a literal `${`
+pub fn interpolate(text: &str) -> Result<String, Error> {
+    let re = Regex::new(r"\$(\$)?\{([A-Za-z_][A-Za-z0-9_]*)\}").expect("regex didn't compile");
+    let mut interpolated = String::with_capacity(text.len());
+    let mut i = 0;
+    for captures in re.captures_iter(text) {
+        let m = captures.get(0).unwrap();
+        interpolated.push_str(&text[i..m.start()]);
+        let name = &captures[2];
+        match captures.get(1) {
+            Some(_) => interpolated.push_str(&m.as_str()[1..]),
+            None => match std::env::var(name) {
+                Ok(value) => interpolated.push_str(&value),
+                Err(_) => return Err(Error::new(ErrorKind::InvalidData,
+                    format!("environment variable {} isn't set", name))),
+            },
+        }
+        i = m.end();
+    }
+    interpolated.push_str(&text[i..]);
+    Ok(interpolated)
+}
+
+impl Capture {
+    /// the distinct values captured from a file's text: the first capture
+    /// group of each match, or the whole match if the pattern has no groups
+    pub fn values(&self, text: &str) -> Vec<String> {
+        let mut values: Vec<String> = Vec::new();
+        for captures in self.pattern.captures_iter(text) {
+            let value = captures.get(1).or_else(|| captures.get(0)).unwrap().as_str();
+            if !values.iter().any(|v| v == value) {
+                values.push(value.to_string());
+            }
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_from_config() {
+        let config: Config = toml::from_str(r#"
+            [[capture]]
+            name    = "ticket"
+            pattern = 'CHG-(\d+)'
+        "#).unwrap();
+        let text = "-- CHG-101: initial version\n-- CHG-205: fix\n-- CHG-101: again";
+        assert_eq!(config.captures[0].values(text), vec!["101", "205"]);
+
+        assert!(toml::from_str::<Config>("[[capture]]\nname = 'x'\npattern = '('").is_err());
+    }
+
+    #[test]
+    fn modules_from_config() {
+        let config: Config = toml::from_str(r#"
+            [[module]]
+            name    = "WaveMgmt"
+            pattern = '(^|/)usp_wave_\w*\.sql$'
+            [[module]]
+            name    = "Orders"
+            pattern = '^src/orders/'
+        "#).unwrap();
+        assert_eq!(config.module(Path::new("src/waves/usp_wave_release.sql")), Some("WaveMgmt"));
+        assert_eq!(config.module(Path::new("src/orders/usp_wave_x.sql")), Some("WaveMgmt"));
+        assert_eq!(config.module(Path::new("src/orders/usp_order_cancel.sql")), Some("Orders"));
+        assert_eq!(config.module(Path::new("src/usp_pick.sql")), None);
+    }
+
+    #[test]
+    fn path_rules_from_config() {
+        let config: Config = toml::from_str(r#"
+            [[path]]
+            glob    = "etl/**"
+            dialect = "postgres"
+            [[path]]
+            glob        = "**/legacy/**"
+            dynamic_sql = false
+        "#).unwrap();
+        let opts = ScanOpts::default();
+        let etl = config.scan_opts(Path::new("./ETL/legacy/load.sql"), &opts);
+        assert_eq!((etl.dialect, etl.dynamic_sql), (Dialect::Postgres, false));
+        let src = config.scan_opts(Path::new("src/usp_order.sql"), &opts);
+        assert_eq!((src.dialect, src.dynamic_sql), (Dialect::Tsql, true));
+    }
+
+    #[test]
+    fn keywords_from_config() {
+        let config: Config = toml::from_str(r#"
+            [[keyword]]
+            word = "UPSERT"
+            type = "insert"
+            [[keyword]]
+            word = "AUDIT_DELETE"
+            type = "d"
+            as   = "DELETE FROM"
+        "#).unwrap();
+        let keywords: Vec<(&str, StatementType, Option<&str>)> = config.keywords.iter()
+            .map(|k| (k.word.as_str(), k.statement_type, k.read_as.as_deref()))
+            .collect();
+        assert_eq!(keywords, vec![
+            ("UPSERT", StatementType::Insert, None),
+            ("AUDIT_DELETE", StatementType::Delete, Some("DELETE FROM")),
+        ]);
+        assert!(toml::from_str::<Config>("[[keyword]]\nword = 'UPSERT'\ntype = 'upsert'").is_err());
+
+        // a config written out reads back the same
+        let written: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(written.keywords.iter().map(|k| (k.word.as_str(), k.statement_type, k.read_as.as_deref())).collect::<Vec<_>>(),
+            keywords);
+    }
+
+    #[test]
+    fn interpolate_environment_variables() {
+        std::env::set_var("GRESQL_TEST_SCHEMA", "dbo");
+        assert_eq!(interpolate("name = '${GRESQL_TEST_SCHEMA}.t_order' # $${GRESQL_TEST_SCHEMA}").unwrap(),
+            "name = 'dbo.t_order' # ${GRESQL_TEST_SCHEMA}");
+        assert!(interpolate("name = '${GRESQL_TEST_UNSET}'").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+// The dialect of the scanned files decides which kinds of statements are
+// recognized, e.g. Service Broker only exists in T-SQL while COPY INTO and
+// table clones are Snowflake/Databricks statements, and how the files are
+// read: how identifiers are quoted, how comments begin and what ends a
+// statement.
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{ Deserialize, Serialize };
+use std::path::Path;
+
+use crate::handler;
+use crate::StatementType;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    /// Microsoft SQL Server / Azure SQL
+    #[default]
+    Tsql,
+    Snowflake,
+    Databricks,
+    Sqlite,
+    /// PostgreSQL, whose statements end with a `;`
+    Postgres,
+    /// MySQL/MariaDB, whose statements end with a `;` (or the DELIMITER of
+    /// the script) and whose comments may also begin with `#`
+    Mysql,
+    /// Oracle, whose statements end with a `;` (or a `/` line, which runs a
+    /// PL/SQL block in SQL*Plus)
+    Oracle,
+    /// detect the dialect of each file
+    Auto,
+}
+
+impl Dialect {
+    /// the statement types that exist in the dialect
+    pub fn statement_types(&self) -> Vec<StatementType> {
+        let mut statement_types = vec![
+            StatementType::Select,
+            StatementType::Insert,
+            StatementType::Update,
+            StatementType::Delete,
+            StatementType::Merge,
+            StatementType::Truncate,
+            StatementType::Drop,
+            StatementType::Alter,
+        ];
+        match self {
+            Dialect::Tsql => statement_types.extend([
+                StatementType::Send,
+                StatementType::Receive,
+                StatementType::Queue,
+                StatementType::Switch,
+                StatementType::Exec,
+            ]),
+            Dialect::Snowflake | Dialect::Databricks => statement_types.extend([
+                StatementType::Copy,
+                StatementType::Clone,
+            ]),
+            Dialect::Sqlite => statement_types.retain(|t| !matches!(t, StatementType::Merge | StatementType::Truncate)),
+            Dialect::Mysql => statement_types.retain(|t| *t != StatementType::Merge),
+            Dialect::Postgres | Dialect::Oracle => {},
+            Dialect::Auto => return StatementType::all(),
+        }
+        statement_types
+    }
+
+    pub fn supports(&self, statement_type: StatementType) -> bool {
+        self.statement_types().contains(&statement_type)
+    }
+
+    /// the dialect of a file, which is only detected from its text if the
+    /// dialect is auto
+    pub fn resolve(&self, path: &Path) -> Dialect {
+        match self {
+            Dialect::Auto => handler::read_to_string(path)
+                .map(|text| Dialect::detect(&text))
+                .unwrap_or_default(),
+            dialect => *dialect,
+        }
+    }
+
+    /// sniff the dialect of a file from the constructs that only exist in one
+    /// of the dialects. Files without any of them are treated as T-SQL.
+    pub fn detect(text: &str) -> Dialect {
+        let patterns = [
+            (Dialect::Tsql,       r"(?im)^\s*go\s*$"),
+            (Dialect::Sqlite,     r"(?im)^\s*(attach\s+database|pragma)\b|\b(?i:insert\s+or\s+replace)\b"),
+            (Dialect::Mysql,      r"(?im)^\s*delimiter\s+\S+\s*$"),
+            (Dialect::Postgres,   r"(?i)\blanguage\s+'?plpgsql\b"),
+            (Dialect::Oracle,     r"(?im)^\s*/\s*$|\b(?i:varchar2)\b"),
+            (Dialect::Snowflake,  r"\$\$"),
+            (Dialect::Databricks, r"`[\w.]+`"),
+        ];
+        for (dialect, pattern) in patterns {
+            if Regex::new(pattern).expect("regex didn't compile").is_match(text) {
+                return dialect;
+            }
+        }
+        Dialect::default()
+    }
+
+    /// the statement type of a first word that is specific to the dialect,
+    /// e.g. `REPLACE INTO` is SQLite's INSERT OR REPLACE
+    pub fn statement_keyword(&self, word: &str) -> Option<StatementType> {
+        match (self, word) {
+            (Dialect::Sqlite | Dialect::Mysql, "replace") => Some(StatementType::Insert),
+            _ => None,
+        }
+    }
+
+    /// whether an INSERT can update the existing rows of its table, e.g.
+    /// `INSERT ... ON CONFLICT (id) DO UPDATE SET ...` or MySQL's
+    /// `INSERT ... ON DUPLICATE KEY UPDATE ...`
+    pub fn has_upsert(&self) -> bool {
+        matches!(self, Dialect::Sqlite | Dialect::Postgres | Dialect::Mysql)
+    }
+
+    /// whether a statement only ends with the delimiter (`;`) at the end of
+    /// one of its lines, so that it may span empty lines, rather than at an
+    /// empty line or a line beginning with a `;`
+    pub fn ends_at_delimiter(&self) -> bool {
+        matches!(self, Dialect::Postgres | Dialect::Mysql | Dialect::Oracle)
+    }
+
+    /// the lines that separate the batches of a script: T-SQL's GO (which
+    /// may be given a count, `GO 5`), or the `/` that runs what precedes it
+    /// in Oracle's SQL*Plus
+    pub fn batch_separator(&self) -> Regex {
+        match self {
+            Dialect::Oracle => Regex::new(r"^/\s*$"),
+            _ => Regex::new(r"^(?i)go(\s+\d+)?\s*(--.*)?$"),
+        }.expect("regex didn't compile")
+    }
+
+    /// the delimiter set by a line of a MySQL script (`DELIMITER //`), which
+    /// ends the statements that follow instead of `;`
+    pub fn delimiter_change(&self, line: &str) -> Option<String> {
+        if *self != Dialect::Mysql { return None; }
+        let (keyword, delimiter) = line.split_once(char::is_whitespace)?;
+        match keyword.eq_ignore_ascii_case("delimiter") && !delimiter.trim().is_empty() {
+            true  => Some(delimiter.trim().to_string()),
+            false => None,
+        }
+    }
+
+    /// whether a line is a comment, which begins with `--` (or with `#` in
+    /// MySQL, where it doesn't name a temporary table)
+    pub fn is_comment(&self, line: &str) -> bool {
+        line.starts_with("--") || (*self == Dialect::Mysql && line.starts_with('#'))
+    }
+
+    /// a line without its trailing comment, if any
+    pub fn trim_comment<'a>(&self, line: &'a str) -> &'a str {
+        let mut end = line.find("--").unwrap_or(line.len());
+        if *self == Dialect::Mysql {
+            let hash = line.char_indices()
+                .find(|(i, c)| *c == '#' && (*i == 0 || line[..*i].ends_with(char::is_whitespace)))
+                .map(|(i, _)| i);
+            end = end.min(hash.unwrap_or(end));
+        }
+        &line[..end]
+    }
+
+    /// the character that quotes identifiers in the dialect, besides the
+    /// brackets of T-SQL
+    fn identifier_quote(&self) -> Option<char> {
+        match self {
+            Dialect::Tsql | Dialect::Auto => None,
+            Dialect::Databricks | Dialect::Mysql => Some('`'),
+            Dialect::Snowflake | Dialect::Sqlite | Dialect::Postgres | Dialect::Oracle => Some('"'),
+        }
+    }
+
+    /// the quoted identifiers of a line written as bracketed identifiers, as
+    /// tables are matched, e.g. `"Sales"."Order Details"` (or
+    /// `` `Sales`.`Order Details` ``) is `[Sales].[Order Details]`. Strings
+    /// (which are also double-quoted where identifiers are quoted with
+    /// backticks) are left as they are.
+    pub fn bracket_identifiers(&self, line: &str) -> String {
+        let Some(quote) = self.identifier_quote() else { return line.to_string(); };
+        let mut bracketed = String::with_capacity(line.len());
+        let mut string_quote: Option<char> = None;
+        let mut in_identifier = false;
+        for c in line.chars() {
+            match (string_quote, c) {
+                (Some(closing), c) => {
+                    if c == closing { string_quote = None; }
+                    bracketed.push(c);
+                },
+                (None, c) if c == quote => {
+                    bracketed.push(if in_identifier { ']' } else { '[' });
+                    in_identifier = !in_identifier;
+                },
+                (None, '\'' | '"') if !in_identifier => {
+                    string_quote = Some(c);
+                    bracketed.push(c);
+                },
+                (None, c) => bracketed.push(c),
+            }
+        }
+        bracketed
+    }
+}
+
+/// the databases of a SQLite connection, whose names can prefix the tables in
+/// a statement, e.g. `aux.t_order` after `ATTACH DATABASE 'aux.db' AS aux`
+pub struct AttachedDatabases {
+    names:     Vec<String>,
+    attach_re: Regex,
+    prefix_re: Regex,
+}
+
+impl Default for AttachedDatabases {
+    fn default() -> AttachedDatabases {
+        AttachedDatabases::new()
+    }
+}
+
+impl AttachedDatabases {
+    pub fn new() -> AttachedDatabases {
+        let names = vec![String::from("main"), String::from("temp")];
+        AttachedDatabases {
+            prefix_re: prefix_regex(&names),
+            names,
+            attach_re: Regex::new(r"^(?i)attach\s+(database\s+)?.+\s+as\s+(\w+)\s*;?$")
+                .expect("regex didn't compile"),
+        }
+    }
+
+    /// record the database attached by a line (if any)
+    pub fn attach(&mut self, line: &str) {
+        let Some(captures) = self.attach_re.captures(line) else { return; };
+        let name = captures[2].to_lowercase();
+        if self.names.contains(&name) { return; }
+        self.names.push(name);
+        self.prefix_re = prefix_regex(&self.names);
+    }
+
+    /// remove the database names prefixing tables, and the conflict clauses
+    /// (e.g. `INSERT OR REPLACE`) that would be mistaken for table names
+    pub fn normalize(&self, line: &str) -> String {
+        let conflict_re = Regex::new(r"\b(?i:or\s+(replace|ignore|abort|fail|rollback))\s+")
+            .expect("regex didn't compile");
+        let line = self.prefix_re.replace_all(line, "");
+        conflict_re.replace_all(&line, "").to_string()
+    }
+}
+
+fn prefix_regex(names: &[String]) -> Regex {
+    Regex::new(&format!(r"\b(?i:{})\.", names.join("|"))).expect("regex didn't compile")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_dialects() {
+        assert_eq!(Dialect::detect("UPDATE t_order SET x = 1\nGO\n"), Dialect::Tsql);
+        assert_eq!(Dialect::detect("PRAGMA foreign_keys = ON;\nDELETE FROM t_order;"), Dialect::Sqlite);
+        assert_eq!(Dialect::detect("CREATE PROCEDURE p() RETURNS INT LANGUAGE SQL AS $$ SELECT 1 $$;"), Dialect::Snowflake);
+        assert_eq!(Dialect::detect("MERGE INTO `sales`.`t_order` USING s ON 1 = 1"), Dialect::Databricks);
+        assert_eq!(Dialect::detect("DELIMITER //\nSELECT 1 //"), Dialect::Mysql);
+        assert_eq!(Dialect::detect("CREATE FUNCTION f() RETURNS INT AS $$ SELECT 1 $$ LANGUAGE plpgsql;"), Dialect::Postgres);
+        assert_eq!(Dialect::detect("BEGIN\n  UPDATE t_order SET x = 1;\nEND;\n/\n"), Dialect::Oracle);
+        assert_eq!(Dialect::detect("UPDATE t_order SET x = 1"), Dialect::Tsql);
+    }
+
+    #[test]
+    fn read_dialect_syntax() {
+        assert_eq!(Dialect::Postgres.bracket_identifiers(r#"UPDATE "Sales"."t_order" SET note = 'say "hi"'"#),
+            r#"UPDATE [Sales].[t_order] SET note = 'say "hi"'"#);
+        assert_eq!(Dialect::Mysql.bracket_identifiers("DELETE FROM `t_order` WHERE x = \"`\""), "DELETE FROM [t_order] WHERE x = \"`\"");
+        assert_eq!(Dialect::Tsql.bracket_identifiers(r#"UPDATE "t_order""#), r#"UPDATE "t_order""#);
+        assert_eq!(Dialect::Mysql.trim_comment("UPDATE t_order SET x = 1; # done"), "UPDATE t_order SET x = 1; ");
+        assert_eq!(Dialect::Tsql.trim_comment("UPDATE #orders SET x = 1 -- done"), "UPDATE #orders SET x = 1 ");
+        assert!(Dialect::Mysql.is_comment("# done") && !Dialect::Tsql.is_comment("#orders"));
+        assert_eq!(Dialect::Mysql.delimiter_change("DELIMITER //"), Some(String::from("//")));
+        assert!(Dialect::Oracle.batch_separator().is_match("/"));
+    }
+
+    #[test]
+    fn normalize_sqlite_statements() {
+        let mut databases = AttachedDatabases::new();
+        databases.attach("ATTACH DATABASE 'archive.db' AS archive;");
+        assert_eq!(
+            databases.normalize("INSERT OR REPLACE INTO archive.t_order SELECT * FROM main.t_order"),
+            "INSERT INTO t_order SELECT * FROM t_order");
+        assert_eq!(StatementType::Update.find_table(&databases.normalize("UPDATE OR IGNORE t_order SET x = 1 ")),
+            Some(String::from("t_order")));
+        assert_eq!(databases.normalize("SELECT o.id FROM t_order o"), "SELECT o.id FROM t_order o");
+    }
+}
@@ -0,0 +1,69 @@
+// The JSON documents that gresql writes, and their JSON Schemas (`gresql
+// schema`), which are generated from the same types that the documents are
+// serialized from, so that integrators can validate results and generate code
+// for them without the schema drifting from the output.
+use clap::ValueEnum;
+use schemars::{ schema_for, Schema };
+
+use crate::manifest::Manifest;
+use crate::report::{ Census, CoOccurrence, Crud, DeployOrder, Groups, Profile };
+use crate::results::{ Comparison, Results };
+use crate::Statement;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Document {
+    /// the results of a scan (`--format json`)
+    #[default]
+    Results,
+    /// a line of `--format ndjson`
+    Statement,
+    /// `--report profile --format json`
+    Profile,
+    /// `--report deploy-order --format json`
+    DeployOrder,
+    /// `--report crud --format json` (and `crud-by-file`)
+    Crud,
+    /// `--report co-occurrence --format json`
+    CoOccurrence,
+    /// `--group-by ... --format json`
+    Groups,
+    /// what a signed document was produced from (`--sign`)
+    Manifest,
+    /// `gresql compare --format json`
+    Comparison,
+    /// `gresql census --format json`
+    Census,
+}
+
+impl Document {
+    pub fn json_schema(&self) -> Schema {
+        match self {
+            Document::Results      => schema_for!(Results),
+            Document::Statement    => schema_for!(Statement),
+            Document::Profile      => schema_for!(Profile),
+            Document::DeployOrder  => schema_for!(DeployOrder),
+            Document::Crud         => schema_for!(Crud),
+            Document::CoOccurrence => schema_for!(CoOccurrence),
+            Document::Groups       => schema_for!(Groups),
+            Document::Manifest     => schema_for!(Manifest),
+            Document::Comparison   => schema_for!(Comparison),
+            Document::Census       => schema_for!(Census),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_schema() {
+        let schema = serde_json::to_value(Document::Results.json_schema()).unwrap();
+        assert_eq!(schema["title"], "Results");
+        assert_eq!(schema["required"], serde_json::json!(["version", "queries", "statements"]));
+        let statement = &schema["$defs"]["Statement"];
+        assert!(statement["required"].as_array().unwrap().contains(&serde_json::json!("table")));
+        assert!(!statement["required"].as_array().unwrap().contains(&serde_json::json!("object")));
+        assert!(schema["$defs"]["StatementType"]["enum"].as_array().unwrap().contains(&serde_json::json!("UPDATE")));
+    }
+}
@@ -0,0 +1,246 @@
+// Dynamic SQL builds statements (and object names) out of string literals and
+// variables at run-time, e.g.
+//
+//   SET @table = 't_order_' + @suffix
+//   SET @sql   = 'DELETE FROM ' + @table + ' WHERE id = 1'
+//   EXEC(@sql)
+//
+// The helpers here recover as much of the literal text of such statements as
+// can be known without running them.
+use regex::{ Captures, Regex };
+use std::collections::HashMap;
+
+pub fn join_literal_fragments(line: &str) -> String {
+    // partially-dynamic statements build object names out of literals and
+    // variables, e.g. `'UPDATE ' + @schema + '.t_order SET ...'` or
+    // `UPDATE $(schema).t_order SET ...` (sqlcmd). Remove the variables (and
+    // the schema separator following them) so that the literal parts of the
+    // statement are joined back together and can still be matched.
+    let concat_re = Regex::new(r"'\s*\+\s*(@\w+|\$\(\w+\))\s*\+\s*N?'\.?")
+        .expect("regex didn't compile");
+    let sqlcmd_re = Regex::new(r"\[?\$\(\w+\)\]?\.")
+        .expect("regex didn't compile");
+    let line = concat_re.replace_all(line, "");
+    sqlcmd_re.replace_all(&line, "").to_string()
+}
+
+/// the value of a string variable; partial values are only the known prefix
+/// of the variable's actual value
+#[derive(Debug, Clone, PartialEq)]
+struct Value {
+    text:    String,
+    partial: bool,
+}
+
+/// string variables whose (prefix) values are known from simple assignments
+/// of literals and other known variables within a procedure
+pub struct Variables {
+    values:        HashMap<String, Value>,
+    assignment_re: Regex,
+    reference_re:  Regex,
+}
+
+impl Default for Variables {
+    fn default() -> Variables {
+        Variables::new()
+    }
+}
+
+impl Variables {
+    pub fn new() -> Variables {
+        Variables {
+            values:        HashMap::new(),
+            assignment_re: Regex::new(r"^(?i:set|select|declare)\s+(@\w+)\b[^=]*?=\s*(.+?);?$")
+                .expect("regex didn't compile"),
+            reference_re:  Regex::new(r"'\s*\+\s*(@\w+)(\s*\+\s*N?'|\s*\)|\s*;?\s*$)")
+                .expect("regex didn't compile"),
+        }
+    }
+
+    /// forget every variable, e.g. at the start of a new procedure
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// whether a line assigns a variable
+    pub fn is_assignment(&self, line: &str) -> bool {
+        self.assignment_re.is_match(line)
+    }
+
+    /// record the value of the variable assigned by a line (if any)
+    pub fn assign(&mut self, line: &str) {
+        let Some(captures) = self.assignment_re.captures(line) else { return; };
+        let name = captures[1].to_lowercase();
+        match self.evaluate(&captures[2]) {
+            Some(value) => { self.values.insert(name, value); },
+            None => { self.values.remove(&name); },
+        }
+    }
+
+    /// evaluate a concatenation of literals and variables, e.g.
+    /// `'t_order_' + @suffix`. Evaluation stops at the first part that isn't
+    /// known, in which case the value is partial.
+    fn evaluate(&self, expression: &str) -> Option<Value> {
+        let mut value = Value { text: String::new(), partial: false };
+        for part in split_concatenation(expression) {
+            let part = part.trim();
+            let literal = part.strip_prefix('N').unwrap_or(part);
+            if literal.len() >= 2 && literal.starts_with('\'') && literal.ends_with('\'') {
+                value.text.push_str(&literal[1..literal.len() - 1].replace("''", "'"));
+                continue;
+            }
+            match self.values.get(&part.to_lowercase()) {
+                Some(known) => {
+                    value.text.push_str(&known.text);
+                    if known.partial {
+                        value.partial = true;
+                        break;
+                    }
+                },
+                None => {
+                    value.partial = true;
+                    break;
+                },
+            }
+        }
+        // an empty string is a known value (e.g. `DECLARE @sql NVARCHAR(MAX) = ''`
+        // before the statement is appended to it), nothing known isn't
+        match value.text.is_empty() && value.partial {
+            true  => None,
+            false => Some(value),
+        }
+    }
+
+    /// the SQL that a line runs as dynamic SQL, from the variable (or the
+    /// concatenation) given to `EXEC (...)` or `sp_executesql`, and whether
+    /// only a prefix of it is known
+    pub fn executed_sql(&self, line: &str) -> Option<(String, bool)> {
+        let exec_re = Regex::new(r"^(?i:exec(?:ute)?)\s*\((.+)\)\s*;?$").expect("regex didn't compile");
+        let executesql_re = Regex::new(
+            r"^(?i:exec(?:ute)?)\s+(?:@\w+\s*=\s*)?(?:\[?\w+\]?\.)*\[?(?i:sp_executesql)\]?\s+(?:@(?i:stmt)\s*=\s*)?(N?'(?:[^']|'')*'|@\w+)")
+            .expect("regex didn't compile");
+        let expression = exec_re.captures(line).or_else(|| executesql_re.captures(line))?;
+        let value = self.evaluate(&expression[1])?;
+        Some((value.text, value.partial))
+    }
+
+    /// replace the known variables concatenated into the literals of a line
+    /// with their values, e.g. `'DELETE FROM ' + @table + ' WHERE ...'`
+    /// becomes `'DELETE FROM t_order_ WHERE ...'`. Returns whether any of the
+    /// substituted values was partial.
+    pub fn substitute(&self, line: &str) -> (String, bool) {
+        let mut partial = false;
+        let line = self.reference_re.replace_all(line, |captures: &Captures| {
+            let Some(value) = self.values.get(&captures[1].to_lowercase()) else {
+                return captures[0].to_string();
+            };
+            partial |= value.partial;
+            // the literal is closed again if the variable was the end of it
+            let rest = &captures[2];
+            match rest.contains('\'') {
+                true  => value.text.clone(),
+                false => format!("{}'{}", value.text, rest),
+            }
+        });
+        (line.to_string(), partial)
+    }
+}
+
+/// whether a line leaves a string literal open, to be continued on the next
+/// line (an escaped quote, `''`, counting twice)
+pub fn has_open_literal(line: &str) -> bool {
+    line.matches('\'').count() % 2 == 1
+}
+
+/// the SQL run by an EXEC with each statement on its own line, a blank line
+/// following each `;` between statements (outside of literals), so that it's
+/// split like the statements of a file
+pub fn split_executed_sql(sql: &str) -> String {
+    let mut split = String::with_capacity(sql.len());
+    let mut in_string = false;
+    for c in sql.chars() {
+        if c == '\'' { in_string = !in_string; }
+        split.push(c);
+        if c == ';' && !in_string {
+            split.push_str("\n\n");
+        }
+    }
+    split
+}
+
+/// split an expression on the `+` operators that aren't inside literals
+fn split_concatenation(expression: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = Vec::new();
+    let mut in_string = false;
+    let mut begin = 0;
+    for (i, c) in expression.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            '+' if !in_string => {
+                parts.push(&expression[begin..i]);
+                begin = i + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&expression[begin..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StatementType;
+
+    #[test]
+    fn join_dynamic_sql_fragments() {
+        assert_eq!(
+            join_literal_fragments("SET @sql = N'UPDATE ' + @schema + '.t_order SET status = 1'"),
+            "SET @sql = N'UPDATE t_order SET status = 1'");
+        assert_eq!(
+            join_literal_fragments("DELETE FROM [$(db)].$(schema).t_order WHERE id = 1"),
+            "DELETE FROM t_order WHERE id = 1");
+        assert_eq!(
+            StatementType::Update.find_table(&join_literal_fragments("'UPDATE ' + @schema + '.t_order SET x = 1'")),
+            Some(String::from("t_order")));
+    }
+
+    #[test]
+    fn propagate_variable_values() {
+        let mut variables = Variables::new();
+        variables.assign("DECLARE @prefix VARCHAR(20) = 't_order'");
+        variables.assign("SET @table = @prefix + '_' + @suffix");
+        variables.assign("SET @archive = N't_order_archive';");
+
+        assert_eq!(
+            variables.substitute("SET @sql = 'DELETE FROM ' + @table + ' WHERE id = 1'"),
+            (String::from("SET @sql = 'DELETE FROM t_order_ WHERE id = 1'"), true));
+        assert_eq!(
+            variables.substitute("SET @sql = 'SELECT * FROM ' + @archive"),
+            (String::from("SET @sql = 'SELECT * FROM t_order_archive'"), false));
+        assert_eq!(
+            variables.substitute("SET @sql = 'SELECT * FROM ' + @unknown"),
+            (String::from("SET @sql = 'SELECT * FROM ' + @unknown"), false));
+
+        variables.clear();
+        assert_eq!(variables.substitute("'x' + @archive"), (String::from("'x' + @archive"), false));
+    }
+
+    #[test]
+    fn rebuild_executed_sql() {
+        let mut variables = Variables::new();
+        variables.assign("DECLARE @sql NVARCHAR(MAX) = N''");
+        variables.assign("SET @table = 't_order'");
+        variables.assign("SET @sql = @sql + N'UPDATE ' + @table");
+        variables.assign("SET @sql = @sql + N' SET status = ''S''; DELETE FROM t_pick'");
+        assert_eq!(variables.executed_sql("EXEC sp_executesql @sql, N'@id INT', @id"),
+            Some((String::from("UPDATE t_order SET status = 'S'; DELETE FROM t_pick"), false)));
+        assert_eq!(variables.executed_sql("EXEC (@sql + ' WHERE id = ' + @id)"),
+            Some((String::from("UPDATE t_order SET status = 'S'; DELETE FROM t_pick WHERE id = "), true)));
+        assert_eq!(variables.executed_sql("EXEC @rc = dbo.sp_executesql N'TRUNCATE TABLE t_wave'"),
+            Some((String::from("TRUNCATE TABLE t_wave"), false)));
+        assert_eq!(variables.executed_sql("EXEC usp_ship @sql"), None);
+        assert_eq!(split_executed_sql("UPDATE t SET x = ';'; DELETE FROM t"), "UPDATE t SET x = ';';\n\n DELETE FROM t");
+        assert!(has_open_literal("SET @sql = N'UPDATE t_order") && !has_open_literal("SET x = 'it''s'"));
+    }
+}
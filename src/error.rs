@@ -0,0 +1,56 @@
+// An error ends the program with exit code 2, like grep's, because a scan
+// exits with 0 when it matched any statements and 1 when it didn't, for shell
+// conditionals and CI jobs (`if gresql -s d:t_order src; then ...`).
+use std::fmt;
+
+/// a scan matched at least one statement
+pub const MATCHED: i32 = 0;
+/// a scan didn't match any statement
+pub const NO_MATCH: i32 = 1;
+/// the runs compared by `gresql compare` have different matches, like the
+/// exit code of diff
+pub const DIFFERENT: i32 = 1;
+/// the program couldn't do what it was asked (its exit code)
+pub const ERROR: i32 = 2;
+
+#[derive(Debug)]
+pub struct Error {
+    message: String,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn new(message: impl Into<String>) -> Error {
+        Error { message: message.into() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// the context of an error, e.g. `Unable to read usp_x.sql: Permission denied`
+pub trait Context<T> {
+    fn context(self, context: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T, E: fmt::Display> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|e| Error::new(format!("{}: {}", context(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_context_to_errors() {
+        let result: std::result::Result<(), std::io::Error> = Err(std::io::Error::other("Permission denied"));
+        let error = result.context(|| String::from("Unable to read usp_x.sql")).unwrap_err();
+        assert_eq!(error.to_string(), "Unable to read usp_x.sql: Permission denied");
+    }
+}
@@ -0,0 +1,178 @@
+// `--git-range FROM..TO` scans the files that changed in a range of commits as
+// they were at its end, e.g. to review the statements of a release. The files
+// are written to a temporary directory for the duration of the scan (like the
+// clipboard's SQL), and each match is given the commits of the range that last
+// changed its lines, from `git blame`.
+use ignore::overrides::Override;
+use std::collections::{ HashMap, HashSet };
+use std::ffi::OsString;
+use std::path::{ Path, PathBuf };
+use std::process::Command;
+
+use gresql::{ handler, is_excluded, Statement };
+
+use crate::error::{ Context, Error, Result };
+
+/// the length of the abbreviated commits given to the matches, like those of
+/// `git log --oneline`
+const ABBREV: usize = 7;
+
+/// the files changed in a range of commits, as they were at its end
+pub struct GitRange {
+    range: String,
+    dir:   PathBuf,
+    /// the path of each file in the temporary directory, and its path (from
+    /// the current directory) in the repository
+    files: HashMap<PathBuf, PathBuf>,
+}
+
+impl GitRange {
+    /// write the files changed in the range, within the paths and except the
+    /// excluded ones, as they were at its end
+    pub fn checkout(range: &str, paths: &[OsString], excludes: &Override) -> Result<GitRange> {
+        let (from, to) = parse_range(range)?;
+        let range = format!("{}..{}", from, to);
+        let mut args: Vec<OsString> = ["diff", "--name-only", "--relative", "--no-renames", "--diff-filter=d", "-z", &range, "--"]
+            .map(OsString::from).to_vec();
+        args.extend(paths.iter().cloned());
+        let changed = git(&args).context(|| format!("Unable to list the files changed in {}", range))?;
+
+        let dir = std::env::temp_dir().join(format!("gresql-git-{}", std::process::id()));
+        let mut git_range = GitRange { range, dir, files: HashMap::new() };
+        let changed = String::from_utf8_lossy(&changed);
+        for path in changed.split('\0').filter(|path| !path.is_empty()).map(PathBuf::from) {
+            if !handler::is_handled(&path) || is_excluded(excludes, &path) { continue; }
+            let mut object = OsString::from(format!("{}:./", to));
+            object.push(&path);
+            let text = git(&[OsString::from("show"), object]).context(|| format!("Unable to read {} at {}", path.display(), to))?;
+            let file = git_range.dir.join(&path);
+            if let Some(parent) = file.parent() {
+                std::fs::create_dir_all(parent).context(|| format!("Unable to create {}", parent.display()))?;
+            }
+            std::fs::write(&file, text).context(|| format!("Unable to write {}", file.display()))?;
+            git_range.files.insert(file, path);
+        }
+        Ok(git_range)
+    }
+
+    /// the files to scan, in the temporary directory
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.files.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+
+    /// the path in the repository of a file to scan
+    pub fn source(&self, path: &Path) -> Option<&Path> {
+        self.files.get(path).map(PathBuf::as_path)
+    }
+
+    /// give the statements the paths of their files in the repository, and
+    /// the commits of the range that last changed their lines
+    pub fn restore(&self, statements: &mut [Statement]) {
+        let mut blames: HashMap<PathBuf, Vec<Option<String>>> = HashMap::new();
+        for statement in statements {
+            let Some(source) = self.source(&statement.file_path) else { continue; };
+            let lines = blames.entry(source.to_path_buf()).or_insert_with(|| {
+                self.blame(source).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    Vec::new()
+                })
+            });
+            let mut commits: Vec<String> = Vec::new();
+            for commit in lines.iter().skip(statement.begin).take(statement.end + 1 - statement.begin).flatten() {
+                if !commits.contains(commit) { commits.push(commit.clone()); }
+            }
+            statement.file_path = source.to_path_buf();
+            statement.commits = commits;
+        }
+    }
+
+    /// the commit of the range that last changed each line of a file, or
+    /// None for the lines that were last changed before it
+    fn blame(&self, path: &Path) -> Result<Vec<Option<String>>> {
+        let args = [OsString::from("blame"), OsString::from("--porcelain"), OsString::from(&self.range), OsString::from("--"),
+            path.as_os_str().to_os_string()];
+        let output = git(&args).context(|| format!("Unable to blame {} in {}", path.display(), self.range))?;
+        Ok(parse_blame(&String::from_utf8_lossy(&output)))
+    }
+}
+
+impl Drop for GitRange {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// the commits at the beginning and the end of a range, `FROM..TO`, the end
+/// being HEAD if it isn't given
+fn parse_range(range: &str) -> Result<(&str, &str)> {
+    match range.split_once("..") {
+        Some((from, to)) if !from.is_empty() && !to.starts_with('.') => Ok((from, if to.is_empty() { "HEAD" } else { to })),
+        _ => Err(Error::new(format!("Invalid --git-range {}: expected a range of commits of the form FROM..TO", range))),
+    }
+}
+
+/// run git, returning its output or its error message
+fn git(args: &[OsString]) -> Result<Vec<u8>> {
+    let output = Command::new("git").args(args).output().context(|| String::from("Unable to run git"))?;
+    match output.status.success() {
+        true  => Ok(output.stdout),
+        false => Err(Error::new(String::from_utf8_lossy(&output.stderr).trim().to_string())),
+    }
+}
+
+/// the commit of each line of the porcelain output of `git blame FROM..TO`,
+/// the boundary commits (of the lines changed before the range) and those of
+/// blank lines being None
+fn parse_blame(output: &str) -> Vec<Option<String>> {
+    let mut lines: Vec<Option<String>> = Vec::new();
+    let mut boundaries: HashSet<&str> = HashSet::new();
+    let mut current: Option<(&str, usize)> = None;
+    for line in output.lines() {
+        // the text of a line, which follows its header and the commit's
+        // details (only given for the first line of each commit). A blank
+        // line (e.g. the one ending a statement) changes no statement.
+        if let Some(text) = line.strip_prefix('\t') {
+            if let Some((commit, number)) = current.take() {
+                if lines.len() < number { lines.resize(number, None); }
+                lines[number - 1] = (!text.trim().is_empty()).then(|| commit.to_string());
+            }
+            continue;
+        }
+        if line == "boundary" {
+            if let Some((commit, _)) = current { boundaries.insert(commit); }
+            continue;
+        }
+        // `<commit> <original line> <final line> [<lines of the group>]`
+        let parts: Vec<&str> = line.split(' ').collect();
+        let is_header = (3..=4).contains(&parts.len()) && parts[0].len() == 40 && parts[0].chars().all(|c| c.is_ascii_hexdigit());
+        if let (true, Some(number)) = (is_header, parts.get(2).and_then(|n| n.parse::<usize>().ok())) {
+            current = Some((parts[0], number));
+        }
+    }
+    lines.into_iter()
+        .map(|commit| commit.filter(|commit| !boundaries.contains(commit.as_str())).map(|commit| commit[..ABBREV].to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_blame_of_range() {
+        let old = "1111111111111111111111111111111111111111";
+        let new = "2222222222222222222222222222222222222222";
+        let output = format!(
+            "{old} 1 1 1\nauthor a\nboundary\nfilename x.sql\n\tUPDATE t_order\n\
+             {new} 2 2 2\nauthor b\nsummary Set the status\nfilename x.sql\n\tSET status = 1\n\
+             {new} 3 3\n\tWHERE id = 1\n\
+             {new} 4 4\n\t\n");
+        assert_eq!(parse_blame(&output), vec![None, Some(String::from("2222222")), Some(String::from("2222222")), None]);
+        assert_eq!(parse_range("v1.0..v2.0").unwrap(), ("v1.0", "v2.0"));
+        assert_eq!(parse_range("v1.0..").unwrap(), ("v1.0", "HEAD"));
+        assert!(parse_range("v1.0...v2.0").is_err());
+        assert!(parse_range("v1.0").is_err());
+    }
+}
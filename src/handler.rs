@@ -0,0 +1,193 @@
+// A handler reads the SQL of one kind of file, e.g. a DACPAC (the package a
+// SQL Server database project is built into) holds the code of its objects in
+// an XML model rather than in scripts. Every file is scanned as the text that
+// its handler reads, so a new input format only needs a handler registered
+// for its extensions in HANDLERS.
+//
+// Whatever its format, the text of a file is decoded to UTF-8 before it's
+// scanned: a BOM gives its encoding (SSMS saves scripts as UTF-16LE with a
+// BOM), otherwise it's the one given with --encoding, or UTF-8.
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use regex::Regex;
+use std::fs::File;
+use std::io::{ BufRead, BufReader, Cursor, Error, ErrorKind, Read };
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub struct Handler {
+    pub name:       &'static str,
+    /// the extensions (lowercase, without the dot) of the files it reads
+    pub extensions: &'static [&'static str],
+    read:           fn(&Path) -> std::io::Result<Box<dyn BufRead>>,
+}
+
+pub const HANDLERS: [Handler; 2] = [
+    Handler { name: "sql",    extensions: &["sql"],    read: read_sql },
+    Handler { name: "dacpac", extensions: &["dacpac"], read: read_dacpac },
+];
+
+/// the encoding of the files without a BOM, set once from --encoding
+static ENCODING: OnceLock<&'static Encoding> = OnceLock::new();
+
+/// read the files without a BOM in an encoding other than UTF-8
+pub fn set_encoding(encoding: &'static Encoding) {
+    let _ = ENCODING.set(encoding);
+}
+
+/// the handler of a file. Files named on the command line are read as SQL
+/// whatever their extension.
+pub fn handler(path: &Path) -> &'static Handler {
+    registered_handler(path).unwrap_or(&HANDLERS[0])
+}
+
+/// whether the files in a directory with the extension of this one are read
+pub fn is_handled(path: &Path) -> bool {
+    registered_handler(path).is_some()
+}
+
+fn registered_handler(path: &Path) -> Option<&'static Handler> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    HANDLERS.iter().find(|handler| handler.extensions.contains(&extension.as_str()))
+}
+
+/// a reader of the SQL text of a file
+pub fn open(path: &Path) -> std::io::Result<Box<dyn BufRead>> {
+    (handler(path).read)(path)
+}
+
+pub fn read_to_string(path: &Path) -> std::io::Result<String> {
+    let mut text = String::new();
+    open(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// a reader of text as UTF-8, decoded from the encoding of its BOM or the one
+/// set by set_encoding. Without either, its bytes are read as they are.
+fn decode<R: Read + 'static>(reader: R) -> Box<dyn BufRead> {
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(ENCODING.get().copied())
+        .bom_override(true)
+        .build(reader);
+    Box::new(BufReader::new(decoder))
+}
+
+fn read_sql(path: &Path) -> std::io::Result<Box<dyn BufRead>> {
+    Ok(decode(File::open(path)?))
+}
+
+/// the objects of a DACPAC as the script that would create them (each in its
+/// own batch), followed by its pre- and post-deployment scripts. Line numbers
+/// are those of that script.
+fn read_dacpac(path: &Path) -> std::io::Result<Box<dyn BufRead>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let mut read_entry = |name: &str| -> std::io::Result<Option<String>> {
+        let mut entry = match archive.by_name(name) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, e)),
+        };
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        let mut text = String::new();
+        decode(Cursor::new(bytes)).read_to_string(&mut text)?;
+        Ok(Some(text))
+    };
+    let mut text = String::new();
+    if let Some(model) = read_entry("model.xml")? {
+        text.push_str(&model_script(&model));
+    }
+    for script in ["predeploy.sql", "postdeploy.sql"] {
+        if let Some(script) = read_entry(script)? {
+            text.push_str(&script);
+            text.push_str("\nGO\n\n");
+        }
+    }
+    Ok(Box::new(Cursor::new(text)))
+}
+
+/// the CREATE statements of the objects whose code is in a DACPAC model. The
+/// model only has the body of each object, after its AS.
+fn model_script(model: &str) -> String {
+    let element_re = Regex::new(r#"<Element Type="Sql(Procedure|View|\w*Function|DmlTrigger)" Name="([^"]+)""#)
+        .expect("regex didn't compile");
+    let body_re = Regex::new(r#"(?s)<Property Name="(?:BodyScript|QueryScript)">\s*<Value>\s*<!\[CDATA\[(.*?)\]\]>"#)
+        .expect("regex didn't compile");
+    let elements: Vec<(usize, &str, &str)> = element_re.captures_iter(model)
+        .map(|captures| (captures.get(0).unwrap().start(), captures.get(1).unwrap().as_str(), captures.get(2).unwrap().as_str()))
+        .collect();
+    let mut script = String::new();
+    for captures in body_re.captures_iter(model) {
+        let start = captures.get(0).unwrap().start();
+        // the body belongs to the last object before it (its parameters are
+        // elements too, but not objects)
+        let Some((_, kind, name)) = elements.iter().rev().find(|(position, _, _)| *position < start) else { continue; };
+        let kind = match *kind {
+            "Procedure"  => "PROCEDURE",
+            "View"       => "VIEW",
+            "DmlTrigger" => "TRIGGER",
+            _            => "FUNCTION",
+        };
+        script.push_str(&format!("CREATE {} {} AS\n{}\nGO\n\n", kind, name, captures[1].trim()));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::io::Write;
+
+    #[test]
+    fn read_dacpac_objects() {
+        let model = r#"<DataSchemaModel><Model>
+<Element Type="SqlTable" Name="[dbo].[t_order]"></Element>
+<Element Type="SqlProcedure" Name="[dbo].[usp_order_cancel]">
+  <Relationship Name="Parameters"><Entry><Element Type="SqlSubroutineParameter" Name="[dbo].[usp_order_cancel].[@id]"/></Entry></Relationship>
+  <Property Name="BodyScript"><Value><![CDATA[
+UPDATE t_order SET status = 9 WHERE id = @id]]></Value></Property>
+</Element>
+</Model></DataSchemaModel>"#;
+        let dir = TempDir::new("dacpac");
+        let path = dir.path().join("model.dacpac");
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("model.xml", options).unwrap();
+        writer.write_all(model.as_bytes()).unwrap();
+        writer.start_file("postdeploy.sql", options).unwrap();
+        writer.write_all(b"DELETE FROM t_order_log").unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(handler(&path).name, "dacpac");
+        assert_eq!(read_to_string(&path).unwrap(), "\
+CREATE PROCEDURE [dbo].[usp_order_cancel] AS
+UPDATE t_order SET status = 9 WHERE id = @id
+GO
+
+DELETE FROM t_order_log
+GO
+
+");
+        assert_eq!(handler(Path::new("usp_x.txt")).name, "sql");
+        assert!(!is_handled(Path::new("usp_x.txt")));
+    }
+
+    #[test]
+    fn decode_files_with_a_bom() {
+        let text = "UPDATE t_order SET note = 'déjà vu'\nGO\n";
+        for (bom, bytes) in [
+            (&[0xFF, 0xFE][..], text.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>()),
+            (&[0xFE, 0xFF][..], text.encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<u8>>()),
+            (&[0xEF, 0xBB, 0xBF][..], text.as_bytes().to_vec()),
+            (&[][..], text.as_bytes().to_vec()),
+        ] {
+            let decoded = || decode(std::io::Cursor::new([bom, &bytes].concat()));
+            let mut decoded_text = String::new();
+            decoded().read_to_string(&mut decoded_text).unwrap();
+            assert_eq!(decoded_text, text);
+            assert_eq!(decoded().lines().count(), 2);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+// Header blocks are the conventional comments at the top of a procedure, e.g.
+//
+//   /*
+//    * Author:      jsmith
+//    * Description: allocates the picks of a wave
+//    * Modification History:
+//    *   2021-03-04  bwayne  skip cancelled orders
+//    */
+//   CREATE PROCEDURE dbo.usp_allocate_wave
+//
+// which is how a lot of T-SQL shops track who owns each object.
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{ Deserialize, Serialize };
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Header {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author:      Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history:     Vec<String>,
+}
+
+enum Section {
+    None,
+    Description,
+    History,
+}
+
+impl Header {
+    fn is_empty(&self) -> bool {
+        self.author.is_none() && self.description.is_none() && self.history.is_empty()
+    }
+
+    fn parse(comment_lines: &[&str]) -> Header {
+        let field_re = Regex::new(r"^(?i)(author|created by|description|purpose|modification history|change history|history|modifications|revisions)\b\s*:?\s*(.*)$")
+            .expect("regex didn't compile");
+        let mut header = Header::default();
+        let mut section = Section::None;
+        for line in comment_lines {
+            let line = strip_comment_markers(line);
+            if let Some(captures) = field_re.captures(line) {
+                let value = captures[2].trim();
+                section = match captures[1].to_lowercase().as_str() {
+                    "author" | "created by" => {
+                        if !value.is_empty() { header.author = Some(value.to_string()); }
+                        Section::None
+                    },
+                    "description" | "purpose" => {
+                        if !value.is_empty() { header.description = Some(value.to_string()); }
+                        Section::Description
+                    },
+                    _ => {
+                        if !value.is_empty() { header.history.push(value.to_string()); }
+                        Section::History
+                    },
+                };
+                continue;
+            }
+            if line.is_empty() || line.chars().all(|c| !c.is_alphanumeric()) {
+                // blank lines and lines of -----/***** end a section
+                section = Section::None;
+                continue;
+            }
+            match section {
+                Section::Description => {
+                    let description = header.description.get_or_insert_with(String::new);
+                    if !description.is_empty() { description.push(' '); }
+                    description.push_str(line);
+                },
+                Section::History => header.history.push(line.to_string()),
+                // any other "key: value" line (e.g. "Create date:") ends the
+                // current section
+                Section::None => {},
+            }
+        }
+        header
+    }
+}
+
+fn strip_comment_markers(line: &str) -> &str {
+    line.trim()
+        .trim_start_matches("--")
+        .trim_start_matches("/*")
+        .trim_end_matches("*/")
+        .trim_start_matches('*')
+        .trim()
+}
+
+/// parse the header of every object in a file, returning the (0-based) line
+/// at which each object begins with its header.
+/// The top of the file is treated as the beginning of an object so that plain
+/// scripts can have headers too.
+pub fn parse_headers(text: &str) -> Vec<(usize, Header)> {
+    let create_re = Regex::new(r"^(?i)create\s+(or\s+alter\s+)?(proc|procedure|function|trigger|view)\b")
+        .expect("regex didn't compile");
+    let lines: Vec<&str> = text.lines().collect();
+    let mut headers: Vec<(usize, Header)> = Vec::new();
+
+    let leading = Header::parse(&comment_block_after(&lines, 0));
+    if !leading.is_empty() {
+        headers.push((0, leading));
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        if !create_re.is_match(line.trim()) { continue; }
+        // the header is either right above the CREATE statement (possibly
+        // separated by SET options and GO) or at the top of the object body
+        let mut comment_lines = comment_block_before(&lines, i);
+        comment_lines.extend(comment_block_after(&lines, i + 1));
+        headers.push((i, Header::parse(&comment_lines)));
+    }
+    headers
+}
+
+/// the header of the object that contains the given (0-based) line
+pub fn header_at(headers: &[(usize, Header)], line: usize) -> Option<&Header> {
+    headers.iter()
+        .rev()
+        .find(|(begin, _)| *begin <= line)
+        .map(|(_, header)| header)
+        .filter(|header| !header.is_empty())
+}
+
+fn is_comment_line(line: &str, in_block: bool) -> bool {
+    let line = line.trim();
+    in_block || line.starts_with("--") || line.starts_with("/*")
+}
+
+fn comment_block_before<'a>(lines: &[&'a str], end: usize) -> Vec<&'a str> {
+    let mut block: Vec<&str> = Vec::new();
+    let mut in_block = false;
+    for line in lines[..end].iter().rev() {
+        let trimmed = line.trim();
+        if trimmed.ends_with("*/") { in_block = true; }
+        let is_comment = is_comment_line(line, in_block);
+        if trimmed.starts_with("/*") { in_block = false; }
+        if is_comment {
+            block.push(line);
+            continue;
+        }
+        let lower = trimmed.to_lowercase();
+        if trimmed.is_empty() || lower == "go" || lower.starts_with("set ") { continue; }
+        break;
+    }
+    block.reverse();
+    block
+}
+
+fn comment_block_after<'a>(lines: &[&'a str], begin: usize) -> Vec<&'a str> {
+    let mut block: Vec<&str> = Vec::new();
+    let mut in_block = false;
+    for line in lines.iter().skip(begin) {
+        let trimmed = line.trim();
+        if trimmed.starts_with("/*") { in_block = true; }
+        let is_comment = is_comment_line(line, in_block);
+        if trimmed.ends_with("*/") { in_block = false; }
+        if is_comment {
+            block.push(line);
+            continue;
+        }
+        // skip over the rest of the object declaration (parameters, AS, BEGIN)
+        // until the first statement of the body
+        let lower = trimmed.to_lowercase();
+        if trimmed.is_empty() || lower == "as" || lower == "begin" || lower.starts_with('@') || lower.starts_with('(') || lower.starts_with(')') {
+            continue;
+        }
+        if !block.is_empty() || begin == 0 { break; }
+        if lower.ends_with(" as") || lower.starts_with("with ") || lower.starts_with("returns") { continue; }
+        break;
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_procedure_headers() {
+        let text = "\
+SET ANSI_NULLS ON
+GO
+/*
+ * Author:      jsmith
+ * Description: allocates the picks
+ *              of a wave
+ * Modification History:
+ *   2021-03-04  bwayne  skip cancelled orders
+ *   2022-01-10  dprince add priority
+ */
+CREATE PROCEDURE dbo.usp_allocate_wave
+    @wave_id INT
+AS
+BEGIN
+    UPDATE t_pick_detail SET status = 1 WHERE wave_id = @wave_id;
+END
+GO
+CREATE PROCEDURE dbo.usp_release_wave AS
+-- Author: bwayne
+UPDATE t_wave SET status = 2;
+";
+        let headers = parse_headers(text);
+        assert_eq!(headers.len(), 2);
+
+        let header = header_at(&headers, 14).unwrap();
+        assert_eq!(header.author.as_deref(), Some("jsmith"));
+        assert_eq!(header.description.as_deref(), Some("allocates the picks of a wave"));
+        assert_eq!(header.history, vec![
+            "2021-03-04  bwayne  skip cancelled orders",
+            "2022-01-10  dprince add priority",
+        ]);
+
+        let header = header_at(&headers, 20).unwrap();
+        assert_eq!(header.author.as_deref(), Some("bwayne"));
+        assert!(header_at(&headers, 0).is_none());
+    }
+}
@@ -0,0 +1,373 @@
+// An index of every statement of a codebase (`gresql index`), so that searches
+// (`gresql query`) are answered without reading every file again. Each file is
+// recorded with its modification time and size; a search first re-parses the
+// files that changed since (and drops those that were removed), then matches
+// the queries against the recorded statements.
+//
+// Several processes may use an index at once, e.g. a query while `gresql index`
+// rebuilds it: readers share a lock of its lock file (`<index>.lock`) and a
+// writer holds it alone, as the index itself is replaced when it's saved. Every
+// save counts a new generation of the index, which tells a process whether
+// another one saved it since it was loaded.
+use rayon::prelude::*;
+use serde::{ Deserialize, Serialize };
+use std::collections::{ BTreeMap, HashSet };
+use std::ffi::OsString;
+use std::fs::{ File, OpenOptions };
+use std::io::{ BufReader, BufWriter, Error, ErrorKind };
+use std::path::{ Path, PathBuf };
+use std::time::{ Duration, Instant, UNIX_EPOCH };
+
+use crate::dialect::Dialect;
+use crate::{
+    exclude_globs, find_file_statements, get_file_paths, is_temp_object, QueryMatch, ScanOpts, SearchQuery, Statement,
+    StatementType,
+};
+
+/// the file that the index is written to, unless another is given
+pub const DEFAULT_INDEX_FILE: &str = ".gresql-index.json";
+
+/// the version of the index format, an index of another version is rebuilt
+const INDEX_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Index {
+    version:    u32,
+    /// the number of times the index was saved
+    #[serde(default)]
+    generation: u64,
+    /// the paths that were indexed, and the globs excluded from them, which
+    /// are walked again to find the files that were added or removed
+    paths:      Vec<OsString>,
+    excludes:   Vec<String>,
+    dialect:    Dialect,
+    files:      BTreeMap<PathBuf, IndexedFile>,
+    /// whether the index was loaded (rather than built), in which case it
+    /// isn't saved over a later generation
+    #[serde(skip)]
+    loaded:     bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    /// the modification time (in nanoseconds since the epoch) and the size
+    /// of the file when it was parsed
+    modified:   u128,
+    len:        u64,
+    statements: Vec<Statement>,
+    /// how long parsing the file took (in nanoseconds), which every search
+    /// saves until it changes (0 for files indexed before it was recorded)
+    #[serde(default)]
+    parse_time: u64,
+}
+
+/// the number of files that an update of the index parsed, dropped or kept
+/// (`--cache-stats`), and the time it took to parse them or was saved by
+/// keeping them
+#[derive(Debug, Default, PartialEq)]
+pub struct IndexUpdate {
+    pub added:     usize,
+    pub changed:   usize,
+    pub removed:   usize,
+    pub unchanged: usize,
+    pub parsed_in: Duration,
+    pub saved:     Duration,
+}
+
+impl IndexUpdate {
+    /// whether the index has to be saved again
+    pub fn is_changed(&self) -> bool {
+        self.added + self.changed + self.removed > 0
+    }
+}
+
+impl Index {
+    pub fn new(paths: &[OsString], excludes: &[String], dialect: Dialect) -> Index {
+        Index {
+            version:    INDEX_VERSION,
+            generation: 0,
+            paths:      paths.to_vec(),
+            excludes:   excludes.to_vec(),
+            dialect,
+            files:      BTreeMap::new(),
+            loaded:     false,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Index, Error> {
+        // an index that can't be locked (e.g. on a read-only share) is read
+        // anyway, as nothing can write it there either
+        let _lock = IndexLock::shared(path).ok();
+        let reader = BufReader::new(File::open(path)?);
+        let mut index: Index = serde_json::from_reader(reader)?;
+        if index.version != INDEX_VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!(
+                "the index was written by another version of gresql (format {}), run gresql index again", index.version)));
+        }
+        index.loaded = true;
+        Ok(index)
+    }
+
+    /// write the index as its next generation, returning whether it was
+    /// written: an index that was loaded isn't if another process saved it
+    /// since (e.g. a query while the index was rebuilt), keeping the other
+    /// process's index
+    pub fn save(&mut self, path: &Path) -> Result<bool, Error> {
+        let _lock = IndexLock::exclusive(path)?;
+        let saved = saved_generation(path);
+        if self.loaded && saved != Some(self.generation) {
+            return Ok(false);
+        }
+        self.generation = saved.unwrap_or(0).max(self.generation) + 1;
+        self.loaded = true;
+        // like a checkpoint, the index is written to a temporary file first
+        // so that an interruption never leaves a truncated index behind
+        let mut tmp_path = path.to_path_buf().into_os_string();
+        tmp_path.push(".tmp");
+        let writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(writer, self)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(true)
+    }
+
+    pub fn files(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn statements(&self) -> usize {
+        self.files.values().map(|file| file.statements.len()).sum()
+    }
+
+    /// every statement of the index, temporary objects included
+    pub fn indexed_statements(&self) -> impl Iterator<Item = &Statement> {
+        self.files.values().flat_map(|file| &file.statements)
+    }
+
+    /// walk the indexed paths again, parsing the files that are new or whose
+    /// modification time or size changed, and dropping the files that are
+    /// gone. Files that can't be read are dropped too, and parsed again once
+    /// they can.
+    pub fn update(&mut self) -> Result<IndexUpdate, ignore::Error> {
+        let excludes = exclude_globs(&self.excludes)?;
+        let file_paths = get_file_paths(&self.paths, &excludes);
+        let mut update = IndexUpdate::default();
+        let before = self.files.len();
+        self.files.retain(|path, _| file_paths.contains(path));
+        update.removed = before - self.files.len();
+
+        let stale: Vec<(&PathBuf, (u128, u64))> = file_paths.iter()
+            .filter_map(|path| file_stamp(path).map(|stamp| (path, stamp)))
+            .filter(|(path, (modified, len))| {
+                self.files.get(*path).is_none_or(|file| file.modified != *modified || file.len != *len)
+            })
+            .collect();
+        // every statement of every type is recorded, temporary objects
+        // included, and the queries choose among them
+        let query = SearchQuery::all();
+        let opts = ScanOpts { dialect: self.dialect, include_temp: true, ..ScanOpts::default() };
+        let started = Instant::now();
+        let parsed: Vec<(PathBuf, IndexedFile)> = stale.par_iter()
+            .map(|(path, (modified, len))| {
+                let started = Instant::now();
+                let mut statements = find_file_statements(path, std::slice::from_ref(&query), &opts).unwrap_or_default();
+                for statement in &mut statements {
+                    statement.queries.clear();
+                }
+                let parse_time = started.elapsed().as_nanos().try_into().unwrap_or(u64::MAX);
+                (path.to_path_buf(), IndexedFile { modified: *modified, len: *len, statements, parse_time })
+            })
+            .collect();
+        update.parsed_in = started.elapsed();
+        // the files that aren't parsed again are the hits of the cache
+        let stale_paths: HashSet<&PathBuf> = stale.iter().map(|(path, _)| *path).collect();
+        let kept: Vec<&IndexedFile> = self.files.iter()
+            .filter(|(path, _)| !stale_paths.contains(path))
+            .map(|(_, file)| file)
+            .collect();
+        update.unchanged = kept.len();
+        update.saved = Duration::from_nanos(kept.iter().map(|file| file.parse_time).sum());
+        for (path, file) in parsed {
+            match self.files.insert(path, file) {
+                Some(_) => update.changed += 1,
+                None    => update.added += 1,
+            }
+        }
+        Ok(update)
+    }
+
+    /// the statements matching the queries, file by file, as a scan of the
+    /// indexed files would find them
+    pub fn search(&self, search_queries: &[SearchQuery], query_match: QueryMatch, include_temp: bool) -> Vec<Statement> {
+        let mut statements: Vec<Statement> = Vec::new();
+        for file in self.files.values() {
+            let mut file_statements: Vec<Statement> = Vec::new();
+            let mut matched_queries = 0;
+            for query in search_queries {
+                let found: Vec<&Statement> = file.statements.iter()
+                    .filter(|statement| matches_indexed(query, statement, include_temp))
+                    .collect();
+                if !found.is_empty() { matched_queries += 1; }
+                for statement in found {
+                    match file_statements.iter_mut().find(|s| s.is_same_match(statement)) {
+                        Some(s) => s.queries.push(query.name.clone()),
+                        None => {
+                            let mut statement = statement.clone();
+                            statement.queries.push(query.name.clone());
+                            file_statements.push(statement);
+                        },
+                    }
+                }
+            }
+            if query_match == QueryMatch::All && matched_queries < search_queries.len() { continue; }
+            statements.extend(file_statements);
+        }
+        statements
+    }
+
+    /// search the index for a query `runs` times, returning the number of
+    /// matches and how long each search took, the shortest first
+    pub fn time_search(&self, query: &SearchQuery, include_temp: bool, runs: usize) -> (usize, Vec<Duration>) {
+        let mut matches = 0;
+        let mut times: Vec<Duration> = (0..runs).map(|_| {
+            let started = Instant::now();
+            matches = self.search(std::slice::from_ref(query), QueryMatch::All, include_temp).len();
+            started.elapsed()
+        }).collect();
+        times.sort();
+        (matches, times)
+    }
+}
+
+/// the p-th percentile (nearest rank) of durations sorted the shortest first
+pub fn percentile(times: &[Duration], p: usize) -> Duration {
+    match times.len() {
+        0 => Duration::ZERO,
+        n => times[(n * p).div_ceil(100).max(1) - 1],
+    }
+}
+
+/// a lock of an index, held until it's dropped: shared by the processes
+/// reading the index and exclusive to the one writing it
+struct IndexLock {
+    /// the lock file, which is unlocked when it's closed
+    _file: File,
+}
+
+impl IndexLock {
+    fn shared(path: &Path) -> Result<IndexLock, Error> {
+        let file = IndexLock::open(path)?;
+        file.lock_shared()?;
+        Ok(IndexLock { _file: file })
+    }
+
+    fn exclusive(path: &Path) -> Result<IndexLock, Error> {
+        let file = IndexLock::open(path)?;
+        file.lock()?;
+        Ok(IndexLock { _file: file })
+    }
+
+    fn open(path: &Path) -> Result<File, Error> {
+        let mut lock_path = path.to_path_buf().into_os_string();
+        lock_path.push(".lock");
+        OpenOptions::new().create(true).truncate(false).write(true).open(lock_path)
+    }
+}
+
+/// whether a statement of the index matches a query. The table of a partial
+/// statement is only a prefix of its name, and temporary objects are only
+/// matched if they're asked for (the "table" of a SEND being its message type).
+fn matches_indexed(query: &SearchQuery, statement: &Statement, include_temp: bool) -> bool {
+    let is_match = match statement.partial {
+        true  => query.statement_types.contains(&statement.statement_type) && query.matches_table_prefix(&statement.table),
+        false => query.matches(statement),
+    };
+    is_match
+        && (include_temp
+            || statement.statement_type == StatementType::Send
+            || !is_temp_object(&statement.table)
+            || query.names_table(&statement.table))
+}
+
+/// the generation of the index saved at a path (skipping the rest of it), or
+/// None if there's none
+fn saved_generation(path: &Path) -> Option<u64> {
+    #[derive(Deserialize)]
+    struct Saved {
+        #[serde(default)]
+        generation: u64,
+    }
+    let reader = BufReader::new(File::open(path).ok()?);
+    serde_json::from_reader::<_, Saved>(reader).ok().map(|saved| saved.generation)
+}
+
+/// the modification time and size of a file, or None if it can't be read
+fn file_stamp(path: &Path) -> Option<(u128, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some((modified, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_search_queries;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn reparse_changed_files() {
+        let dir = TempDir::new("index");
+        let a = dir.write("a.sql", "UPDATE t_order SET x = 1\n\nDELETE FROM #orders\n");
+        dir.write("b.sql", "SELECT * FROM t_order\n");
+        let mut index = Index::new(&[dir.path().as_os_str().to_os_string()], &[], Dialect::Tsql);
+        let counts = |update: IndexUpdate| (update.added, update.changed, update.removed, update.unchanged);
+        assert_eq!(counts(index.update().unwrap()), (2, 0, 0, 0));
+        let update = index.update().unwrap();
+        assert!(!update.is_changed() && update.saved > Duration::ZERO);
+        assert_eq!(counts(update), (0, 0, 0, 2));
+
+        let queries = parse_search_queries(&[String::from("u:t_order"), String::from("s:t_order")]);
+        assert_eq!(index.search(&queries, QueryMatch::Any, false).len(), 2);
+        assert!(index.search(&queries, QueryMatch::All, false).is_empty());
+        let queries = parse_search_queries(&[String::from("d:")]);
+        assert!(index.search(&queries, QueryMatch::All, false).is_empty());
+        assert_eq!(index.search(&queries, QueryMatch::All, true).len(), 1);
+
+        let b = dir.write("b.sql", "UPDATE t_order SET y = 2\n");
+        std::fs::remove_file(a).unwrap();
+        assert_eq!(counts(index.update().unwrap()), (0, 1, 1, 0));
+        let queries = parse_search_queries(&[String::from("u:t_order")]);
+        assert_eq!(index.search(&queries, QueryMatch::All, false)[0].file_path, b);
+    }
+
+    #[test]
+    fn percentiles_of_search_times() {
+        let times: Vec<Duration> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&times, 50), Duration::from_millis(10));
+        assert_eq!(percentile(&times, 95), Duration::from_millis(19));
+        assert_eq!(percentile(&times[..1], 95), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn keep_index_saved_by_another_process() {
+        let dir = TempDir::new("index-lock");
+        let path = dir.path().join("index.json");
+        assert!(Index::new(&[dir.path().as_os_str().to_os_string()], &[], Dialect::Tsql).save(&path).unwrap());
+        let mut loaded = Index::load(&path).unwrap();
+
+        // a reader doesn't keep a writer out, but the writer keeps everyone out
+        let reader = IndexLock::shared(&path).unwrap();
+        assert!(IndexLock::open(&path).unwrap().try_lock_shared().is_ok());
+        assert!(IndexLock::open(&path).unwrap().try_lock().is_err());
+        drop(reader);
+        let writer = IndexLock::exclusive(&path).unwrap();
+        assert!(IndexLock::open(&path).unwrap().try_lock_shared().is_err());
+        drop(writer);
+
+        assert!(Index::load(&path).unwrap().save(&path).unwrap());
+        assert!(!loaded.save(&path).unwrap());
+        // a rebuilt index is saved over any generation
+        assert!(Index::new(&[dir.path().as_os_str().to_os_string()], &[], Dialect::Tsql).save(&path).unwrap());
+        assert_eq!(saved_generation(&path), Some(3));
+    }
+}
@@ -0,0 +1,2383 @@
+// gresql as a library: search queries, the statements that they match and the
+// scan of files for them, e.g. for build tooling that would rather not shell
+// out to the command line interface (which is built on this crate).
+//
+//   let queries = gresql::parse_search_queries(&[String::from("ud:t_order")]);
+//   for statement in gresql::search_files(&file_paths, &queries) { ... }
+pub mod ast;
+pub mod catalog;
+pub mod checkpoint;
+pub mod columns;
+pub mod config;
+pub mod dialect;
+pub mod document;
+pub mod dynamic;
+pub mod handler;
+pub mod header;
+pub mod index;
+pub mod lint;
+pub mod manifest;
+pub mod objects;
+pub mod policy;
+pub mod pragma;
+pub mod query;
+pub mod report;
+pub mod results;
+pub mod schema;
+#[cfg(test)]
+mod testing;
+
+use ast::Engine;
+use clap::ValueEnum;
+use config::{ Config, Keyword };
+use dialect::{ AttachedDatabases, Dialect };
+use dynamic::{ has_open_literal, join_literal_fragments, split_executed_sql, Variables };
+use glob::{ glob, Pattern };
+use ignore::overrides::{ Override, OverrideBuilder };
+use ignore::WalkBuilder;
+use header::Header;
+use pragma::{ is_ignored, parse_pragmas };
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{ Deserialize, Serialize };
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{ BTreeMap, HashMap, HashSet };
+use std::ffi::{ OsStr, OsString };
+use std::io::BufRead;
+use std::path::{ Path, PathBuf };
+use grep_regex::RegexMatcher;
+use grep_searcher::Searcher;
+use grep_searcher::sinks::Bytes;
+
+/// how the files are scanned
+#[derive(Clone)]
+pub struct ScanOpts {
+    pub first_match:    bool,
+    pub dialect:        Dialect,
+    pub ignore_pragmas: bool,
+    pub engine:         Engine,
+    /// print why each statement of the file did or didn't match (`--why`)
+    pub why:            bool,
+    pub query_match:    QueryMatch,
+    /// find temporary tables and table variables (`--include-temp`)
+    pub include_temp:   bool,
+    /// find the statements built as strings for dynamic SQL
+    pub dynamic_sql:    bool,
+    /// rebuild the SQL that `EXEC (@sql)` and `sp_executesql @sql` run from
+    /// the assignments of the variable, and find its statements there rather
+    /// than in the literals (`--dynamic-sql`)
+    pub resolve_exec:   bool,
+}
+
+impl Default for ScanOpts {
+    fn default() -> ScanOpts {
+        ScanOpts {
+            first_match: false, dialect: Dialect::Tsql, ignore_pragmas: true, engine: Engine::Regex, why: false,
+            query_match: QueryMatch::All, include_temp: false, dynamic_sql: true, resolve_exec: false,
+        }
+    }
+}
+
+/// which of several search queries a file has to match (`--match`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum QueryMatch {
+    /// every query
+    #[default]
+    All,
+    /// at least one of the queries
+    Any,
+}
+
+// statement types ============================================================
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StatementType {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Merge,
+    // DDL that destroys (or changes) a table
+    Truncate,
+    Drop,
+    Alter,
+    // service broker
+    Send,
+    Receive,
+    Queue,
+    // partition switch
+    Switch,
+    // snowflake/databricks
+    Copy,
+    Clone,
+    // procedure calls, whose "table" is the procedure
+    Exec,
+}
+
+impl StatementType {
+    pub fn all() -> Vec<StatementType> {
+        vec![
+            StatementType::Select,
+            StatementType::Insert,
+            StatementType::Update,
+            StatementType::Delete,
+            StatementType::Merge,
+            StatementType::Truncate,
+            StatementType::Drop,
+            StatementType::Alter,
+            StatementType::Send,
+            StatementType::Receive,
+            StatementType::Queue,
+            StatementType::Switch,
+            StatementType::Copy,
+            StatementType::Clone,
+            StatementType::Exec,
+        ]
+    }
+
+    /// whether statements of the type read tables other than their target,
+    /// see `find_nested_tables`
+    fn has_nested_reads(&self) -> bool {
+        matches!(self, StatementType::Update | StatementType::Delete | StatementType::Merge | StatementType::Copy | StatementType::Clone)
+    }
+
+    /// every statement type that modifies the object it targets
+    pub fn writes() -> Vec<StatementType> {
+        StatementType::all().into_iter()
+            .filter(|t| !matches!(t, StatementType::Select | StatementType::Exec))
+            .collect()
+    }
+
+    /// the tables targeted by a statement. This is a single table except for
+    /// SELECT statements, which read the table of each of their branches
+    /// (combined with UNION, EXCEPT or INTERSECT) and every joined table.
+    fn find_tables(&self, s: &str) -> Vec<String> {
+        let mut tables: Vec<String> = Vec::new();
+        let mut push_table = |table: String| {
+            if !tables.contains(&table) { tables.push(table); }
+        };
+        match self {
+            StatementType::Select => {
+                // subqueries (including derived tables, e.g. the source of a
+                // PIVOT) are read by the statement too, but their tables
+                // mustn't be mistaken for the tables of the outer statement
+                let subqueries = find_subqueries(s);
+                let outer = remove_subqueries(s, &subqueries);
+                let re = Regex::new(r"\b(?i:union\s+all|union|except|intersect)\b").expect("regex didn't compile");
+                for branch in re.split(&outer) {
+                    if let Some(table) = self.find_table(branch) { push_table(table); }
+                    find_join_tables(branch).into_iter().for_each(&mut push_table);
+                }
+                for (begin, end) in subqueries {
+                    self.find_tables(&s[begin..end]).into_iter().for_each(&mut push_table);
+                }
+            },
+            StatementType::Switch => {
+                // the rows of a partition are moved out of the source table
+                // and into the target table, so both are written
+                let re = Regex::new(&format!(r"\b(?i:alter\s+table)\s+{QUALIFIED_NAME}\s+(?i:switch)\b.*?\b(?i:to)\s+{QUALIFIED_NAME}")).expect("regex didn't compile");
+                if let Some(captures) = re.captures(s) {
+                    push_table(normalize_name(&captures[1]));
+                    push_table(normalize_name(&captures[2]));
+                }
+            },
+            StatementType::Exec => {
+                // consecutive calls aren't separated by a blank line or a
+                // semicolon, so a statement can have several of them
+                for callee in find_callees(s) { push_table(callee); }
+            },
+            StatementType::Drop => {
+                // `DROP TABLE a, b` drops every table of its list
+                let re = Regex::new(&format!(r"\b(?i:drop\s+table)\s+(?:(?i:if\s+exists)\s+)?({QUALIFIED_NAME}(\s*,\s*{QUALIFIED_NAME})*)"))
+                    .expect("regex didn't compile");
+                let name_re = Regex::new(QUALIFIED_NAME).expect("regex didn't compile");
+                if let Some(captures) = re.captures(s) {
+                    for name in name_re.find_iter(&captures[1]) { push_table(normalize_name(name.as_str())); }
+                }
+            },
+            _ => {
+                if let Some(table) = self.find_table(s) { push_table(table); }
+            },
+        }
+        tables
+    }
+
+    /// the tables read within an UPDATE or DELETE statement by its subqueries,
+    /// e.g. `b` in `DELETE FROM a WHERE id IN (SELECT id FROM b)`, and by its
+    /// FROM and joins (other than the target of the statement), or the source table of
+    /// a COPY INTO (unload) or CLONE
+    fn find_nested_tables(&self, s: &str) -> Vec<String> {
+        let source_re = match self {
+            // stages (`@stage`) and locations aren't tables
+            StatementType::Copy  => Some(r"\b(?i:from)\s+(\[[^\]]+\]|[#\w]+)".to_string()),
+            StatementType::Clone => Some(format!(r"\b(?i:clone)\s+{QUALIFIED_NAME}")),
+            _ => None,
+        };
+        if let Some(pattern) = source_re {
+            let re = Regex::new(&pattern).expect("regex didn't compile");
+            return re.captures(s).map(|c| normalize_name(&c[1])).into_iter().collect();
+        }
+        match self {
+            StatementType::Update | StatementType::Delete | StatementType::Merge => {
+                let target = self.find_table(s);
+                let subqueries = find_subqueries(s);
+                let outer = remove_subqueries(s, &subqueries);
+                let mut tables: Vec<String> = Vec::new();
+                let subquery_tables = subqueries.iter()
+                    .flat_map(|&(begin, end)| StatementType::Select.find_tables(&s[begin..end]));
+                // the aliases of a FROM (`DELETE FROM o FROM t_order o`) aren't tables
+                let aliases = find_aliases(s);
+                // a MERGE reads the table of its USING clause (or those of its
+                // subquery, `USING (SELECT ...) s`)
+                let from = match self {
+                    StatementType::Merge => "using",
+                    _                    => "from",
+                };
+                let from_re = Regex::new(&format!(r"\b(?i:{from})\s+{QUALIFIED_NAME}")).expect("regex didn't compile");
+                let from_tables = from_re.captures_iter(&outer)
+                    .map(|c| normalize_name(&c[1]))
+                    .filter(|table| !aliases.contains_key(&table.to_lowercase()));
+                for table in subquery_tables.chain(from_tables).chain(find_join_tables(&outer)) {
+                    // the target may be named with or without its schema
+                    let is_target = target.as_deref().is_some_and(|target| split_schema(target).1 == split_schema(&table).1);
+                    if !is_target && !tables.contains(&table) {
+                        tables.push(table);
+                    }
+                }
+                tables
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn find_table(&self, s: &str) -> Option<String> {
+        let has_from: bool = s.to_string().to_lowercase().contains("from");
+        let re = match self {
+            StatementType::Insert => {
+                Regex::new(&format!(r"\b(?i:into)\s+{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Merge => {
+                Regex::new(&format!(r"\b(?i:merge)\s+(?:(?i:into)\s+)?{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Copy => {
+                // the target is either a table (load) or a stage (unload)
+                Regex::new(&format!(r"\b(?i:copy\s+into)\s+{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Clone => {
+                Regex::new(&format!(r"\b(?i:table)\s+(?:(?i:if\s+not\s+exists)\s+)?{QUALIFIED_NAME}\s+(?i:(shallow\s+|deep\s+)?clone)\b")).expect("regex didn't compile")
+            },
+            StatementType::Delete if !has_from => {
+                Regex::new(&format!(r"\b(?i:delete)\s+{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Delete => {
+                // the target follows DELETE (and its FROM, if any), and may
+                // be the alias of a table of the FROM or a JOIN that follows,
+                // e.g. `DELETE o FROM t_order o` or `DELETE FROM x FROM t_order o
+                // JOIN t_order_detail x ON ...`
+                let re = Regex::new(&format!(r"\b(?i:delete)\s+(?:(?i:top)\s*\(\s*\d+\s*\)\s*(?i:percent\s+)?)?(?:(?i:from)\s+)?{QUALIFIED_NAME}"))
+                    .expect("regex didn't compile");
+                return Some(resolve_alias(s, &re.captures(s)?[1]));
+            },
+            StatementType::Select | StatementType::Receive => {
+                Regex::new(&format!(r"\b(?i:from)\s+{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Send => {
+                // a message is sent on a conversation (handle), so the closest
+                // thing to a target is the type of the message, if any
+                let re = Regex::new(r"\b(?i:message\s+type)\s+([@#\[\]/:.\w]+)").expect("regex didn't compile");
+                if let Some(captures) = re.captures(s) {
+                    return Some(captures[1].to_string());
+                }
+                Regex::new(&format!(r"\b(?i:on\s+conversation)\s+\(?\s*{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Queue => {
+                Regex::new(&format!(r"\b(?i:queue)\s+{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Switch => {
+                Regex::new(&format!(r"\b(?i:alter\s+table)\s+{QUALIFIED_NAME}\s+(?i:switch)\b")).expect("regex didn't compile")
+            },
+            StatementType::Truncate => {
+                Regex::new(&format!(r"\b(?i:truncate\s+table)\s+{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Drop => return self.find_tables(s).into_iter().next(),
+            StatementType::Alter => {
+                Regex::new(&format!(r"\b(?i:alter\s+table)\s+(?:(?i:if\s+exists)\s+)?{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Exec => return find_callees(s).into_iter().next(),
+            StatementType::Update if !has_from => {
+                Regex::new(&format!(r"\b(?i:update)\s+{QUALIFIED_NAME}")).expect("regex didn't compile")
+            },
+            StatementType::Update => {
+                // There are two possible forms here:
+                //   UPDATE <a> ... FROM <table> <a> WHERE ...
+                //   UPDATE <b> ... FROM <table> <a> INNER JOIN <table_2> <b> WHERE ...
+                // We need to find the target of the update statement based on
+                // the alias
+                let re = Regex::new(&format!(r"\b(?i:update)\s+{QUALIFIED_NAME}")).expect("regex didn't compile");
+                return Some(resolve_alias(s, &re.captures(s)?[1]));
+            }
+        };
+        Some(normalize_name(re.captures(s)?.get(1)?.as_str()))
+    }
+}
+
+/// the tables of the FROM and JOIN clauses of a statement (outside of its
+/// subqueries) by their lowercase aliases, e.g. `d` for `FROM t_pick_detail d`
+/// or `JOIN dbo.t_pick AS p`
+fn find_aliases(s: &str) -> HashMap<String, String> {
+    // the keywords that can follow a table without an alias
+    const KEYWORDS: [&str; 25] = [
+        "from", "on", "where", "inner", "left", "right", "full", "cross", "outer", "join", "with", "set", "group",
+        "order", "having", "union", "except", "intersect", "option", "output", "pivot", "unpivot", "for",
+        "select", "when",
+    ];
+    let outer = remove_subqueries(s, &find_subqueries(s));
+    let re = Regex::new(&format!(r"\b(?i:from|join)\s+{QUALIFIED_NAME}\s+(?:(?i:as)\s+)?(\[[^\]]+\]|\w+)"))
+        .expect("regex didn't compile");
+    let mut aliases: HashMap<String, String> = HashMap::new();
+    let mut start = 0;
+    // the "alias" may be the next FROM (`DELETE FROM o FROM t_order o`), so
+    // the search goes on from the table rather than the alias
+    while let Some(c) = re.captures_at(&outer, start) {
+        let alias = normalize_name(&c[2]).to_lowercase();
+        if !KEYWORDS.contains(&alias.as_str()) {
+            aliases.insert(alias, normalize_name(&c[1]));
+        }
+        start = c.get(1).map_or(outer.len(), |table| table.end());
+    }
+    aliases
+}
+
+/// the table of a name, which may be an alias of one of the tables of the
+/// statement (the FROM may only be in a subquery or a CTE, in which case the
+/// name isn't an alias)
+fn resolve_alias(s: &str, name: &str) -> String {
+    let name = normalize_name(name);
+    if name.contains('.') { return name; }
+    find_aliases(s).remove(&name.to_lowercase()).unwrap_or(name)
+}
+
+/// the text of a statement that begins with one of the keywords of the config
+/// with the keyword replaced by what it's read as, or the text itself
+fn read_as_keyword<'a>(keywords: &[Keyword], text: &'a str) -> Cow<'a, str> {
+    let word = text.split_whitespace().next().unwrap_or_default();
+    match keywords.iter().find(|keyword| keyword.word.eq_ignore_ascii_case(word)) {
+        Some(keyword) => {
+            let read_as = keyword.read_as.clone().unwrap_or_else(|| keyword.statement_type.to_string());
+            Cow::Owned(format!("{}{}", read_as, &text.trim_start()[word.len()..]))
+        },
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// the procedures called by EXEC (or EXECUTE) statements, e.g. `usp_foo` for
+/// `EXEC usp_foo`, `EXECUTE @rc = usp_foo` and `EXEC [usp_foo];1`, qualified
+/// by their schema if they are. Procedures named by a variable (`EXEC @proc`)
+/// or built in dynamic SQL (`EXEC (@sql)`) aren't known.
+fn find_callees(s: &str) -> Vec<String> {
+    let re = Regex::new(&format!(r"\b(?i:exec|execute)\s+(?:@\w+\s*=\s*)?{QUALIFIED_NAME}")).expect("regex didn't compile");
+    re.captures_iter(s)
+        .map(|captures| normalize_name(&captures[1]))
+        .filter(|callee| !callee.starts_with('@'))
+        .collect()
+}
+
+/// an object name, or a bracketed identifier (which can contain any character,
+/// including spaces), e.g. `t_order`, `@orders` or `[Aufträge 2024]`
+const NAME: &str = r"(\[[^\]]+\]|[@#\w]+)";
+
+/// an object name, possibly qualified by its schema (and database or server),
+/// e.g. `t_order`, `dbo.t_order` or `[Sales DB].[dbo].[t_order]`
+const QUALIFIED_NAME: &str = r"((?:(?:\[[^\]]+\]|[@#\w]+)?\.){0,3}(?:\[[^\]]+\]|[@#\w]+))";
+
+/// a (possibly qualified) name as `schema.table`, or `table` if it has no
+/// schema, without the brackets of its parts and without its database
+pub(crate) fn normalize_name(name: &str) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut part = String::new();
+    let mut in_brackets = false;
+    for c in name.chars() {
+        match c {
+            '[' if !in_brackets => in_brackets = true,
+            ']' if in_brackets  => in_brackets = false,
+            '.' if !in_brackets => parts.push(std::mem::take(&mut part)),
+            _ => part.push(c),
+        }
+    }
+    parts.push(part);
+    match parts.as_slice() {
+        [.., schema, table] if !schema.is_empty() => format!("{}.{}", schema, table),
+        [.., table] => table.clone(),
+        [] => String::new(),
+    }
+}
+
+/// whether a name is a temporary table (`#orders`) or a table variable
+/// (`@orders`) rather than a table of the database
+pub fn is_temp_object(name: &str) -> bool {
+    name.starts_with(['#', '@'])
+}
+
+/// the schema (if any) and the table of a normalized name
+pub fn split_schema(name: &str) -> (Option<&str>, &str) {
+    match name.rsplit_once('.') {
+        Some((schema, table)) => (Some(schema), table),
+        None => (None, name),
+    }
+}
+
+/// a name without the brackets of a bracketed identifier
+pub(crate) fn unbracket(name: &str) -> String {
+    name.strip_prefix('[')
+        .and_then(|name| name.strip_suffix(']'))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// the byte ranges of the outermost subqueries of a statement, i.e. the text
+/// between the parentheses of every `(SELECT ...)` that isn't inside another
+fn find_subqueries(s: &str) -> Vec<(usize, usize)> {
+    let re = Regex::new(r"^\(\s*(?i:select|with)\b").expect("regex didn't compile");
+    let mut subqueries: Vec<(usize, usize)> = Vec::new();
+    let mut depth: usize = 0;
+    let mut subquery_start: Option<(usize, usize)> = None; // (begin, depth)
+    let mut in_string = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            '(' if !in_string => {
+                if subquery_start.is_none() && re.is_match(&s[i..]) {
+                    subquery_start = Some((i + 1, depth));
+                }
+                depth += 1;
+            },
+            ')' if !in_string => {
+                depth = depth.saturating_sub(1);
+                if let Some((begin, subquery_depth)) = subquery_start {
+                    if depth == subquery_depth {
+                        subqueries.push((begin, i));
+                        subquery_start = None;
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    subqueries
+}
+
+/// the byte offset of the parenthesis that closes the one before a text
+fn closing_paren(s: &str) -> Option<usize> {
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string && depth == 0 => return Some(i),
+            ')' if !in_string => depth -= 1,
+            _ => {},
+        }
+    }
+    None
+}
+
+/// a common table expression: its name and the byte range of its query
+type Cte = (String, (usize, usize));
+
+/// the common table expressions of a statement that begins with a WITH
+/// clause, and the byte offset of the statement that follows them
+fn split_ctes(s: &str) -> Option<(Vec<Cte>, usize)> {
+    let with_re = Regex::new(r"^\s*(?i:with)(\s+(?i:recursive))?\s").expect("regex didn't compile");
+    let cte_re = Regex::new(&format!(r"^\s*{NAME}\s*(\([^)]*\))?\s*(?i:as)\s*\(")).expect("regex didn't compile");
+    let mut offset = with_re.find(s)?.end();
+    let mut ctes: Vec<Cte> = Vec::new();
+    loop {
+        let captures = cte_re.captures(&s[offset..])?;
+        let begin = offset + captures.get(0)?.end();
+        let end = begin + closing_paren(&s[begin..])?;
+        ctes.push((unbracket(&captures[1]), (begin, end)));
+        offset = end + 1;
+        match s[offset..].trim_start().strip_prefix(',') {
+            Some(rest) => offset = s.len() - rest.len(),
+            None => break,
+        }
+    }
+    Some((ctes, s.len() - s[offset..].trim_start().len()))
+}
+
+/// the tables of a statement that follows CTEs and the tables it reads
+/// besides. The tables read by the CTEs are read by the statement, the names
+/// of the CTEs aren't tables, and a statement that writes a CTE writes the
+/// table of its query (e.g. `WITH o AS (SELECT ... FROM t_order) UPDATE o`).
+fn find_cte_tables(statement_type: StatementType, s: &str, ctes: &[Cte], offset: usize) -> (Vec<String>, Vec<String>) {
+    let cte = |table: &str| ctes.iter().find(|(name, _)| name.eq_ignore_ascii_case(table));
+    let statement = &s[offset..];
+    let mut tables: Vec<String> = Vec::new();
+    for table in statement_type.find_tables(statement) {
+        let table = match cte(&table) {
+            Some((_, (begin, end))) if statement_type != StatementType::Select => {
+                let Some(table) = StatementType::Select.find_table(&s[*begin..*end]) else { continue; };
+                table
+            },
+            Some(_) => continue,
+            None => table,
+        };
+        if !tables.contains(&table) { tables.push(table); }
+    }
+    let mut nested_tables: Vec<String> = statement_type.find_nested_tables(statement).into_iter()
+        .filter(|table| cte(table).is_none())
+        .collect();
+    let reads = match statement_type {
+        StatementType::Select => &mut tables,
+        _ => &mut nested_tables,
+    };
+    for (_, (begin, end)) in ctes {
+        for table in StatementType::Select.find_tables(&s[*begin..*end]) {
+            if cte(&table).is_none() && !reads.contains(&table) { reads.push(table); }
+        }
+    }
+    if statement_type != StatementType::Select {
+        nested_tables.retain(|table| !tables.contains(table));
+    }
+    (tables, nested_tables)
+}
+
+/// the text of a statement with the text of its subqueries removed, leaving
+/// only the (empty) parentheses
+fn remove_subqueries(s: &str, subqueries: &[(usize, usize)]) -> String {
+    let mut outer = String::with_capacity(s.len());
+    let mut i = 0;
+    for &(begin, end) in subqueries {
+        outer.push_str(&s[i..begin]);
+        i = end;
+    }
+    outer.push_str(&s[i..]);
+    outer
+}
+
+/// the tables of every (INNER/LEFT/RIGHT/FULL/CROSS) JOIN in a statement
+fn find_join_tables(s: &str) -> Vec<String> {
+    let re = Regex::new(&format!(r"\b(?i:join)\s+{QUALIFIED_NAME}")).expect("regex didn't compile");
+    re.captures_iter(s).map(|c| normalize_name(&c[1])).collect()
+}
+
+// implement try_from &char for StatementType
+impl TryFrom<char> for StatementType {
+    type Error = ();
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            's' => Ok(StatementType::Select),
+            'i' => Ok(StatementType::Insert),
+            'u' => Ok(StatementType::Update),
+            'd' => Ok(StatementType::Delete),
+            'm' => Ok(StatementType::Merge),
+            'n' => Ok(StatementType::Send),
+            'r' => Ok(StatementType::Receive),
+            'q' => Ok(StatementType::Queue),
+            'w' => Ok(StatementType::Switch),
+            'c' => Ok(StatementType::Copy),
+            'l' => Ok(StatementType::Clone),
+            'e' => Ok(StatementType::Exec),
+            't' => Ok(StatementType::Truncate),
+            'x' => Ok(StatementType::Drop),
+            'a' => Ok(StatementType::Alter),
+            _ => Err(()),
+        }
+    }
+}
+
+/// the character of a statement type in a search query
+impl From<StatementType> for char {
+    fn from(statement_type: StatementType) -> char {
+        match statement_type {
+            StatementType::Select   => 's',
+            StatementType::Insert   => 'i',
+            StatementType::Update   => 'u',
+            StatementType::Delete   => 'd',
+            StatementType::Merge    => 'm',
+            StatementType::Send     => 'n',
+            StatementType::Receive  => 'r',
+            StatementType::Queue    => 'q',
+            StatementType::Switch   => 'w',
+            StatementType::Copy     => 'c',
+            StatementType::Clone    => 'l',
+            StatementType::Exec     => 'e',
+            StatementType::Truncate => 't',
+            StatementType::Drop     => 'x',
+            StatementType::Alter    => 'a',
+        }
+    }
+}
+
+// implement try_from String for StatementType
+impl TryFrom<String> for StatementType {
+    type Error = ();
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "select" => Ok(StatementType::Select),
+            "insert" => Ok(StatementType::Insert),
+            "update" => Ok(StatementType::Update),
+            "delete" => Ok(StatementType::Delete),
+            "merge"  => Ok(StatementType::Merge),
+            "send"    => Ok(StatementType::Send),
+            "receive" => Ok(StatementType::Receive),
+            "copy"    => Ok(StatementType::Copy),
+            "exec" | "execute" => Ok(StatementType::Exec),
+            "truncate" => Ok(StatementType::Truncate),
+            _ => Err(()),
+        }
+    }
+}
+
+// display for StatementType
+impl std::fmt::Display for StatementType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatementType::Select => write!(f, "SELECT"),
+            StatementType::Insert => write!(f, "INSERT"),
+            StatementType::Update => write!(f, "UPDATE"),
+            StatementType::Delete => write!(f, "DELETE"),
+            StatementType::Merge  => write!(f, "MERGE"),
+            StatementType::Send    => write!(f, "SEND"),
+            StatementType::Receive => write!(f, "RECEIVE"),
+            StatementType::Queue   => write!(f, "QUEUE"),
+            StatementType::Switch  => write!(f, "SWITCH"),
+            StatementType::Copy    => write!(f, "COPY"),
+            StatementType::Clone   => write!(f, "CLONE"),
+            StatementType::Exec    => write!(f, "EXEC"),
+            StatementType::Truncate => write!(f, "TRUNCATE"),
+            StatementType::Drop     => write!(f, "DROP"),
+            StatementType::Alter    => write!(f, "ALTER"),
+        }
+    }
+}
+
+fn parse_statement_types(statement_types: &str) -> Vec<StatementType> {
+    if statement_types.contains('*') {
+        // return all statement types except select (and procedure calls)
+        return StatementType::writes();
+    }
+    // the unique types, in the order they're given, so that a query always
+    // has the same types in the same order
+    let mut types: Vec<StatementType> = Vec::new();
+    for stmt_type in statement_types.chars().filter_map(|c| StatementType::try_from(c).ok()) {
+        if !types.contains(&stmt_type) {
+            types.push(stmt_type);
+        }
+    }
+    types
+}
+
+/// the role of the tables of a MERGE given after its `m`, `>` for its target
+/// and `<` for its source (`m>:t_order`), or None for either
+fn parse_merge_role(statement_types: &str) -> Option<TableRole> {
+    match (statement_types.contains("m>"), statement_types.contains("m<")) {
+        (true, false) => Some(TableRole::Target),
+        (false, true) => Some(TableRole::Source),
+        _             => None,
+    }
+}
+// ============================================================================
+
+// search queries =============================================================
+#[derive(Debug)]
+pub struct SearchQuery {
+    /// the name given to the query (`LABEL=QUERY`), e.g. to give it a budget
+    pub label: Option<String>,
+    /// the label of the query, or the query itself, by which its matches are
+    /// attributed to it
+    pub name: String,
+    pub statement_types: Vec<StatementType>,
+    pub tables: Vec<String>,
+    /// the tables given as a `/regex/` or a glob, e.g. `t_pick_*`
+    patterns: Vec<TablePattern>,
+    /// the tables that a statement mustn't be of to match, given after a `!`
+    /// (`u:t_order*,!t_order_audit`) or with `--not-table`
+    pub excluded: Vec<String>,
+    excluded_patterns: Vec<TablePattern>,
+    /// the columns of the tables that a statement has to write to match
+    /// (`u:t_order@order_status`), or none to match any statement
+    pub columns: Vec<String>,
+    /// the words of the config that begin statements besides the keywords
+    /// of their types, e.g. `UPSERT`
+    pub keywords: Vec<Keyword>,
+    /// the role that the table of a MERGE has to have to match, its target
+    /// (`m>:t_order`) or its USING source (`m<:t_order_stage`), or None for
+    /// either
+    pub merge_role: Option<TableRole>,
+}
+
+/// a table of a query that matches the names of several tables, given as a
+/// `/regex/` or a glob (`t_pick_*`, `t_pick_?`). It matches the whole name of
+/// a table (whatever its schema) ignoring case.
+#[derive(Debug)]
+struct TablePattern {
+    /// the pattern as it was given
+    source:  String,
+    /// the pattern as a regex, e.g. `t_pick_.*` for `t_pick_*`
+    regex:   String,
+    matcher: Regex,
+}
+
+impl SearchQuery {
+    /// a query for every statement of every type, used when a report is
+    /// requested without any search queries
+    pub fn all() -> SearchQuery {
+        SearchQuery::new("*", StatementType::all(), Vec::new())
+    }
+    /// a query for statements of the given types to any of the (literal)
+    /// tables, or to any table if there are none
+    pub fn new(name: &str, statement_types: Vec<StatementType>, tables: Vec<String>) -> SearchQuery {
+        SearchQuery {
+            label: None,
+            name: name.to_string(),
+            statement_types,
+            tables,
+            patterns: Vec::new(),
+            columns:  Vec::new(),
+            excluded: Vec::new(),
+            excluded_patterns: Vec::new(),
+            keywords: Vec::new(),
+            merge_role: None,
+        }
+    }
+    /// exclude a table (or a `/regex/` or a glob) from the matches of the query
+    pub fn exclude_table(&mut self, table: &str) -> Result<(), regex::Error> {
+        match parse_table_pattern(table) {
+            Some(pattern) => self.excluded_patterns.push(pattern?),
+            None          => self.excluded.push(normalize_name(table)),
+        }
+        Ok(())
+    }
+    /// whether a found statement is one of the matches of the query. The
+    /// table of a found statement may have been renamed to its declared case.
+    pub fn matches(&self, statement: &Statement) -> bool {
+        let table = statement.table.to_lowercase();
+        self.statement_types.contains(&statement.statement_type)
+            && (self.any_table()
+                || self.tables.iter().any(|t| table_matches(&t.to_lowercase(), statement.schema.as_deref(), &table))
+                || self.patterns.iter().any(|p| p.matcher.is_match(&table)))
+            && !self.excludes_table(statement.schema.as_deref(), &table)
+            && (self.columns.is_empty() || (!statement.nested && self.writes_columns(statement.statement_type, &statement.text)))
+            && (statement.statement_type != StatementType::Merge || self.matches_merge_role(statement.nested))
+    }
+    /// whether the table of a MERGE, its target or (if it's nested) its
+    /// source, has the role of the query
+    fn matches_merge_role(&self, nested: bool) -> bool {
+        match self.merge_role {
+            Some(TableRole::Target) => !nested,
+            Some(TableRole::Source) => nested,
+            _                       => true,
+        }
+    }
+    /// whether a statement writes any of the columns of the query. The
+    /// tables that a statement reads have no columns written.
+    fn writes_columns(&self, statement_type: StatementType, text: &str) -> bool {
+        self.columns.iter().any(|column| columns::writes_column(statement_type, text, column))
+    }
+    /// the tables of the query (lowercase), its patterns and its columns, for
+    /// comparing queries
+    fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.iter()
+            .map(|t| t.to_lowercase())
+            .chain(self.patterns.iter().map(|p| p.source.clone()))
+            .chain(self.columns.iter().map(|c| format!("@{}", c.to_lowercase())))
+            .chain(self.excluded.iter().map(|t| format!("!{}", t.to_lowercase())))
+            .chain(self.excluded_patterns.iter().map(|p| format!("!{}", p.source)))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+    /// whether the query is for every table (but the excluded ones)
+    fn any_table(&self) -> bool {
+        self.tables.is_empty() && self.patterns.is_empty()
+    }
+    /// whether a table is one of the excluded tables of the query
+    fn excludes_table(&self, schema: Option<&str>, table: &str) -> bool {
+        self.excluded.iter().any(|t| table_matches(t, schema, table))
+            || self.excluded_patterns.iter().any(|p| p.matcher.is_match(table))
+    }
+    /// whether the query lists a (normalized) table name itself, rather than
+    /// matching it with a pattern or matching every table
+    fn names_table(&self, name: &str) -> bool {
+        let (schema, table) = split_schema(name);
+        self.tables.iter().any(|t| table_matches(t, schema, table))
+    }
+    /// match a (normalized) table name, which may be qualified by its schema
+    fn matches_table(&self, name: &str) -> bool {
+        let (schema, table) = split_schema(name);
+        (self.any_table()
+            || self.tables.iter().any(|t| table_matches(t, schema, table))
+            || self.patterns.iter().any(|p| p.matcher.is_match(table)))
+            && !self.excludes_table(schema, table)
+    }
+    /// match a table name of which only a prefix is known. Patterns can't
+    /// tell whether they'd match the rest of the name, so they never do, and
+    /// neither are excluded tables told apart.
+    fn matches_table_prefix(&self, prefix: &str) -> bool {
+        let prefix = split_schema(prefix).1.to_lowercase();
+        self.any_table() || self.tables.iter().any(|t| split_schema(t).1.to_lowercase().starts_with(&prefix))
+    }
+    fn statement_pattern(&self) -> String {
+        let mut statement_types = self.statement_types.clone();
+        if statement_types.contains(&StatementType::Select) {
+            // tables are also read by the joins of other statements, and by
+            // the statements that copy or clone them
+            statement_types.extend(StatementType::all().into_iter().filter(|t| t.has_nested_reads()));
+        }
+        let mut pattern = String::new();
+        pattern.push_str(r"\b((?i)");
+        pattern.push_str(
+            &statement_types.iter()
+                .map(|st| match st {
+                    StatementType::Exec => String::from("EXEC(UTE)?"),
+                    st => st.to_string(),
+                })
+                .chain(self.keywords.iter()
+                    .filter(|keyword| statement_types.contains(&keyword.statement_type))
+                    .map(|keyword| regex::escape(&keyword.word)))
+                .collect::<Vec<String>>()
+                .join("|"));
+        pattern.push_str(r")\b");
+        pattern
+    }
+    fn table_pattern(&self) -> String {
+        let mut pattern = String::new();
+        pattern.push_str("((?i)");
+        // table names are literal, unless given as a `/regex/` or a glob
+        pattern.push_str(
+            &self.tables.iter()
+                .map(|t| regex::escape(split_schema(t).1))
+                .chain(self.patterns.iter().map(|p| format!("(?:{})", p.regex)))
+                .collect::<Vec<String>>()
+                .join("|"));
+        pattern.push_str(r")\b");
+        // table names can also be built by concatenating variables into
+        // dynamic SQL, in which case the name doesn't appear in the file
+        pattern.push_str(r"|'\s*\+\s*@");
+        pattern
+    }
+}
+
+/// whether a table of a query matches a table of a statement. A table of a
+/// query that is qualified by a schema (`dbo.t_order`) only matches the table
+/// of that schema; names are compared ignoring case.
+pub(crate) fn table_matches(query_table: &str, schema: Option<&str>, table: &str) -> bool {
+    let (query_schema, query_table) = split_schema(query_table);
+    query_table.to_lowercase() == table.to_lowercase()
+        && query_schema.is_none_or(|query_schema| schema.is_some_and(|schema| schema.eq_ignore_ascii_case(query_schema)))
+}
+
+pub fn parse_search_queries(strings: &[String]) -> Vec<SearchQuery> {
+    strings.iter()
+        .filter_map(|s| split_search_query(s).map(|parts| (s, parts)))
+        .filter_map(|(s, (label, statement_types, tables, columns))| {
+            let mut query = SearchQuery {
+                label:           label.map(String::from),
+                name:            label.unwrap_or(s).to_string(),
+                statement_types: parse_statement_types(statement_types),
+                tables:          Vec::new(),
+                patterns:        Vec::new(),
+                columns:         columns.into_iter()
+                    .filter(|column| !column.trim().is_empty())
+                    .map(|column| column.trim().to_string())
+                    .collect(),
+                excluded:          Vec::new(),
+                excluded_patterns: Vec::new(),
+                keywords:          Vec::new(),
+                merge_role:        parse_merge_role(statement_types),
+            };
+            // a query without tables (`d:` or `d:*`) is for every table
+            for table in tables.into_iter().filter(|table| !table.is_empty() && *table != "*") {
+                if let Some(excluded) = table.strip_prefix('!') {
+                    if query.exclude_table(excluded).is_err() { return None; }
+                    continue;
+                }
+                match parse_table_pattern(table) {
+                    Some(Ok(pattern)) => query.patterns.push(pattern),
+                    Some(Err(_))      => return None,
+                    None              => query.tables.push(normalize_name(table)),
+                }
+            }
+            Some(query)
+        })
+        .collect()
+}
+
+/// the label, statement types, tables and columns of a query
+type QueryParts<'a> = (Option<&'a str>, &'a str, Vec<&'a str>, Vec<&'a str>);
+
+/// split a query into its label, statement types, tables and columns, e.g.
+/// `orders=u,d:t_order,/t_order_\d+/@order_status`. Separators inside a
+/// `/regex/` are part of the regex.
+fn split_search_query(s: &str) -> Option<QueryParts<'_>> {
+    let (label, s) = match s.split_once('=') {
+        Some((label, rest)) if !label.contains('/') => (Some(label), rest),
+        _ => (None, s),
+    };
+    let ps = split_outside_patterns(s, ':');
+    let (statement_types, tables) = match ps.len() {
+        1 => ("*", ps[0]),
+        2 => (ps[0], ps[1]),
+        _ => return None,
+    };
+    let (tables, columns) = split_columns(tables);
+    Some((label, statement_types, split_outside_patterns(tables, ','), columns))
+}
+
+/// split the tables of a query from the columns that follow them
+/// (`t_order,t_order_log@order_status,ship_date`). The `@` of a table
+/// variable (`u:@orders`, `u:!@orders`) begins a table rather than the columns.
+fn split_columns(tables: &str) -> (&str, Vec<&str>) {
+    let mut separator = None;
+    let mut start = 0;
+    for part in split_outside_patterns(tables, ',') {
+        let at = match part.starts_with('/') {
+            true  => part.find("/@").filter(|i| *i > 0).map(|i| i + 1),
+            false => {
+                let start = part.len() - part.trim_start_matches('!').len();
+                part.char_indices().skip(start + 1).find(|(_, c)| *c == '@').map(|(i, _)| i)
+            },
+        };
+        if let Some(i) = at {
+            separator = Some(start + i);
+            break;
+        }
+        start += part.len() + 1;
+    }
+    match separator {
+        Some(i) => (&tables[..i], tables[i + 1..].split(',').collect()),
+        None    => (tables, Vec::new()),
+    }
+}
+
+fn split_outside_patterns(s: &str, separator: char) -> Vec<&str> {
+    // a regex begins with a `/` at the start of a part (or after the `!` of
+    // an excluded table) and ends with a `/` at the end of one
+    let is_boundary = |c: Option<char>| matches!(c, None | Some(',') | Some(':') | Some('@') | Some('!'));
+    let mut parts: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut in_pattern = false;
+    for (i, c) in s.char_indices() {
+        if c == '/' {
+            match in_pattern {
+                false => in_pattern = is_boundary(s[..i].chars().next_back()),
+                true  => in_pattern = !is_boundary(s[i + 1..].chars().next()),
+            }
+        }
+        if c == separator && !in_pattern {
+            parts.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// a table given as `/regex/` or as a glob, in which `*` matches any
+/// characters and `?` any one character. Any other table is a literal name.
+fn parse_table_pattern(table: &str) -> Option<Result<TablePattern, regex::Error>> {
+    let regex = match table.strip_prefix('/').and_then(|table| table.strip_suffix('/')) {
+        Some(source) => source.to_string(),
+        None if table.contains(['*', '?']) => table.chars()
+            .map(|c| match c {
+                '*' => String::from(".*"),
+                '?' => String::from("."),
+                c   => regex::escape(&c.to_string()),
+            })
+            .collect(),
+        None => return None,
+    };
+    // the regex is checked on its own first, so that errors point into it
+    Some(Regex::new(&regex)
+        .and_then(|_| Regex::new(&format!("^(?i:{})$", regex)))
+        .map(|matcher| TablePattern { source: table.to_string(), regex, matcher }))
+}
+
+/// the `/regex/` tables of the queries that aren't valid regexes
+pub fn invalid_table_patterns(strings: &[String]) -> Vec<(String, regex::Error)> {
+    strings.iter()
+        .filter_map(|s| split_search_query(s))
+        .flat_map(|(_, _, tables, _)| tables)
+        .map(|table| table.strip_prefix('!').unwrap_or(table))
+        .filter_map(|table| match parse_table_pattern(table) {
+            Some(Err(e)) => Some((table.to_string(), e)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// warnings about queries that overlap: the same query given twice, a query
+/// whose matches are all matches of another (so it adds nothing), or a
+/// label given to different queries
+pub fn overlapping_queries(strings: &[String], query_match: QueryMatch) -> Vec<String> {
+    let queries: Vec<(&String, SearchQuery)> = strings.iter()
+        .filter_map(|s| parse_search_queries(std::slice::from_ref(s)).pop().map(|query| (s, query)))
+        .collect();
+    let mut warnings: Vec<String> = Vec::new();
+    for (i, (a, query_a)) in queries.iter().enumerate() {
+        for (b, query_b) in &queries[i + 1..] {
+            if let (Some(label), Some(label_b)) = (&query_a.label, &query_b.label) {
+                if label == label_b && a != b {
+                    warnings.push(format!("the label {} is given to both '{}' and '{}', only the matches of '{}' count toward its budget",
+                        label, a, b, a));
+                }
+            }
+            if query_a.table_names() != query_b.table_names() || query_a.merge_role != query_b.merge_role { continue; }
+            let a_in_b = query_a.statement_types.iter().all(|t| query_b.statement_types.contains(t));
+            let b_in_a = query_b.statement_types.iter().all(|t| query_a.statement_types.contains(t));
+            let (narrow, wide, query) = match (a_in_b, b_in_a) {
+                (true, true) => {
+                    warnings.push(format!("'{}' and '{}' are the same query", a, b));
+                    continue;
+                },
+                (true, false)  => (a, b, query_a),
+                (false, true)  => (b, a, query_b),
+                (false, false) => continue,
+            };
+            let types: Vec<String> = StatementType::all().into_iter()
+                .filter(|t| query.statement_types.contains(t))
+                .map(|t| t.to_string())
+                .collect();
+            warnings.push(match query_match {
+                QueryMatch::All => format!(
+                    "'{}' overlaps '{}': the {} statements match both. Together they find the same files as '{}' alone, '{}' alone finds statements of any of its types",
+                    narrow, wide, types.join("/"), narrow, wide),
+                QueryMatch::Any => format!(
+                    "'{}' overlaps '{}': the {} statements match both, so '{}' finds nothing that '{}' doesn't",
+                    narrow, wide, types.join("/"), narrow, wide),
+            });
+        }
+    }
+    warnings
+}
+// ============================================================================
+
+// file paths =================================================================
+pub enum PathType {
+    File,
+    Directory,
+    Symlink,
+}
+
+fn get_path_type(path: &Path) -> Option<PathType> {
+    if !path.exists() { return None; }
+    match path.is_file() {
+        true  => Some(PathType::File),
+        false => match path.is_dir() {
+            true  => Some(PathType::Directory),
+            false => Some(PathType::Symlink),
+        },
+    }
+}
+
+/// the globs of `--exclude`, as the overrides of a directory walk. Globs are
+/// matched like the lines of a .gitignore, relative to the current directory.
+pub fn exclude_globs(globs: &[String]) -> Result<Override, ignore::Error> {
+    let mut builder = OverrideBuilder::new(".");
+    for glob in globs {
+        builder.add(&format!("!{}", glob))?;
+    }
+    builder.build()
+}
+
+/// whether a path, or any of the directories it is in, is excluded
+pub fn is_excluded(excludes: &Override, path: &Path) -> bool {
+    excludes.matched(path, false).is_ignore()
+        || path.ancestors().skip(1).any(|dir| !dir.as_os_str().is_empty() && excludes.matched(dir, true).is_ignore())
+}
+
+/// the files of a list of paths (see `walk_file_paths`), all of them at once
+pub fn get_file_paths(strings: &[OsString], excludes: &Override) -> HashSet<PathBuf> {
+    walk_file_paths(strings, excludes).collect()
+}
+
+/// the files of a list of paths, of which each could be a file, a symlink, a
+/// directory, or a glob pattern, found as the directories are walked rather
+/// than collected first (e.g. to start scanning a share of millions of files
+/// before it's all been walked). Only the files (and glob matches) that were
+/// given are held, so that a directory's walk skips them, and a directory
+/// within another that was given is walked with it.
+pub fn walk_file_paths<'a>(strings: &'a [OsString], excludes: &'a Override) -> impl Iterator<Item = PathBuf> + 'a {
+    let mut given: Vec<PathBuf> = Vec::new();
+    let mut dirs: Vec<&Path> = Vec::new();
+    for s in strings {
+        let path: &Path = Path::new(s);
+        if let Some(path_type) = get_path_type(path) { // valid path
+            match path_type {
+                PathType::File => given.push(PathBuf::from(s)),
+                PathType::Symlink => given.extend(path.read_link()),
+                PathType::Directory => dirs.push(path),
+            }
+        } else if is_glob(s) {
+            // an invalid glob is skipped, the others still being walked
+            match glob(&s.to_string_lossy()) {
+                Ok(entries) => given.extend(entries.flatten()),
+                Err(e) => eprintln!("Invalid glob {}: {}", s.to_string_lossy(), e),
+            }
+        } else {
+            eprintln!("File not found: {}", s.to_string_lossy());
+        }
+    }
+    let roots: Vec<&Path> = dirs.iter().enumerate()
+        .filter(|(i, dir)| !dirs.iter().enumerate().any(|(j, other)| {
+            // of two equal directories, the first one is walked
+            j != *i && is_within(dir, other) && (!is_within(other, dir) || j < *i)
+        }))
+        .map(|(_, dir)| *dir)
+        .collect();
+    given.sort();
+    given.dedup();
+    let given_set: HashSet<PathBuf> = given.iter().cloned().collect();
+    let walked = roots.into_iter().flat_map(move |root| {
+        // get all the files in the directory that a handler reads (e.g. .sql
+        // files), except those ignored by .gitignore (or .ignore) files and
+        // excluded
+        let walk = WalkBuilder::new(root)
+            .hidden(false)
+            .require_git(false)
+            .overrides(excludes.clone())
+            .build();
+        walk.flatten().filter_map(|entry| {
+            let is_file = entry.file_type().is_some_and(|file_type| file_type.is_file());
+            // paths are relative to the current directory as given, e.g.
+            // `src/a.sql` rather than `./src/a.sql`
+            (is_file && handler::is_handled(entry.path()))
+                .then(|| entry.path().strip_prefix(".").unwrap_or(entry.path()).to_path_buf())
+        })
+    });
+    given.into_iter()
+        .chain(walked.filter(move |path| !given_set.contains(path)))
+        .filter(move |path| !is_excluded(excludes, path))
+}
+
+/// whether a path that doesn't exist is a glob pattern
+fn is_glob(s: &OsStr) -> bool {
+    get_path_type(Path::new(s)).is_none() && s.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// the paths of a list (see `walk_file_paths`) that are invalid glob
+/// patterns, which aren't walked
+pub fn invalid_globs(strings: &[OsString]) -> Vec<&OsString> {
+    strings.iter().filter(|s| is_glob(s) && Pattern::new(&s.to_string_lossy()).is_err()).collect()
+}
+
+/// whether a path is (within) a directory, `.` being the current directory
+fn is_within(path: &Path, dir: &Path) -> bool {
+    let normalize = |path: &Path| -> PathBuf {
+        path.components().filter(|component| *component != std::path::Component::CurDir).collect()
+    };
+    normalize(path).starts_with(normalize(dir))
+}
+
+/// the paths of a list of files (e.g. the output of `git diff --name-only` or
+/// `fd -0`), one per line or separated by NUL characters
+pub fn read_file_list(mut reader: impl std::io::Read) -> std::io::Result<Vec<OsString>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let separator = if text.contains('\0') { '\0' } else { '\n' };
+    Ok(text.split(separator)
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(OsString::from)
+        .collect())
+}
+
+/// the number of lines of (the SQL text of) a file, counted in its bytes
+/// without reading it line by line (a last line without a newline counts too)
+pub fn count_lines(path: &Path) -> std::io::Result<usize> {
+    let mut reader = handler::open(path)?;
+    let mut lines = 0;
+    let mut last = b'\n';
+    loop {
+        let buffer = reader.fill_buf()?;
+        let n = buffer.len();
+        if n == 0 { break; }
+        lines += bytecount::count(buffer, b'\n');
+        last = buffer[n - 1];
+        reader.consume(n);
+    }
+    if last != b'\n' { lines += 1; }
+    Ok(lines)
+}
+// ============================================================================
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Statement {
+    pub file_path:      PathBuf,
+    pub statement_type: StatementType,
+    pub table:          String,
+    // the schema that the table is qualified with in the statement, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema:         Option<String>,
+    // the lines of the statement, from 0, `end` being the line that ends it
+    pub begin:          usize,
+    pub end:            usize,
+    // the bytes of the file that the lines of the statement are
+    #[serde(default)]
+    pub bytes:          ByteSpan,
+    // the batch of the script that the statement is in, from 0, batches
+    // being separated by GO
+    #[serde(default)]
+    pub batch:          usize,
+    pub text:           String,
+    // the bytes of `text` that are the keyword of the statement (unless the
+    // table is read by a subquery) and each mention of the table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyword_span:   Option<ByteSpan>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub table_spans:    Vec<ByteSpan>,
+    // whether the table is read by a subquery of the statement rather than
+    // being the target of the statement itself
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub nested:         bool,
+    // whether only a prefix of the table name is known, because it was built
+    // from variables in dynamic SQL
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial:        bool,
+    // whether the statement is dynamic SQL: it begins in a string literal, or
+    // it was rebuilt from the variable that an EXEC runs (`--dynamic-sql`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dynamic:        bool,
+    // values captured from the file by the config-defined capture patterns
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub captures:       BTreeMap<String, String>,
+    // the name of the object (procedure, view, ...) that contains the
+    // statement, if it is in one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object:         Option<String>,
+    // the module of the file, by the `[[module]]` patterns of the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module:         Option<String>,
+    // header comment of the object that contains the statement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header:         Option<Header>,
+    // the names of the search queries that matched the statement
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queries:        Vec<String>,
+    // every table referenced by the statement, of which `table` is one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tables:         Vec<TableRef>,
+    // the note given to the match by the triage of an earlier run
+    // (`--annotate`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note:           Option<String>,
+    // the commits of the `--git-range` that last changed the lines of the
+    // statement
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commits:        Vec<String>,
+}
+
+/// a range of the bytes of a file (or of a text), from `begin` up to (not
+/// including) `end`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ByteSpan {
+    pub begin: usize,
+    pub end:   usize,
+}
+
+/// how a statement accesses its table
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+    /// calls it, the "table" being a procedure
+    Execute,
+}
+
+/// a table referenced by a statement, and how
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TableRef {
+    pub name: String,
+    pub role: TableRole,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TableRole {
+    /// written by the statement
+    Target,
+    /// read by the statement, e.g. in its FROM clause or a subquery
+    Source,
+    /// read by a JOIN of the statement
+    Join,
+}
+
+/// the bytes of a text that name a table as it is written, bracketed or not,
+/// as a whole name (of which only the beginning is known if it is partial)
+pub fn find_table_mentions(text: &str, table: &str, partial: bool) -> Vec<ByteSpan> {
+    let table = regex::escape(table);
+    let table_re = Regex::new(&format!(r"(?i)\[{}\]|{}", table, table)).expect("regex didn't compile");
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '#' | '@' | '$');
+    table_re.find_iter(text)
+        .filter(|m| !text[..m.start()].ends_with(is_name_char))
+        .filter(|m| partial || !text[m.end()..].starts_with(is_name_char))
+        .map(|m| ByteSpan { begin: m.start(), end: m.end() })
+        .collect()
+}
+
+/// the tables referenced by a statement: its targets (or, for a SELECT, its
+/// sources) and the tables it reads besides
+fn table_refs(statement_type: StatementType, s: &str, tables: &[String], nested_tables: &[String]) -> Vec<TableRef> {
+    let joins = find_join_tables(s);
+    let read_role = |table: &String| match joins.contains(table) {
+        true  => TableRole::Join,
+        false => TableRole::Source,
+    };
+    let targets = tables.iter().map(|table| TableRef {
+        name: split_schema(table).1.to_string(),
+        role: match statement_type {
+            StatementType::Select => read_role(table),
+            _ => TableRole::Target,
+        },
+    });
+    let reads = nested_tables.iter().map(|table| TableRef { name: split_schema(table).1.to_string(), role: read_role(table) });
+    targets.chain(reads).collect()
+}
+
+impl Statement {
+    pub fn access(&self) -> Access {
+        match self.statement_type {
+            _ if self.nested => Access::Read,
+            StatementType::Select => Access::Read,
+            StatementType::Exec => Access::Execute,
+            _ => Access::Write,
+        }
+    }
+    /// find the keyword and the mentions of the table in the text, e.g. for a
+    /// UI to highlight them without parsing the statement again
+    fn highlight(&mut self) {
+        let keyword = match self.statement_type {
+            StatementType::Exec   => String::from("EXEC(UTE)?"),
+            StatementType::Insert => String::from("INSERT|REPLACE"),
+            statement_type        => statement_type.to_string(),
+        };
+        let keyword_re = Regex::new(&format!(r"\b(?i:{})\b", keyword)).expect("regex didn't compile");
+        self.keyword_span = match self.nested {
+            true  => None,
+            false => keyword_re.find(&self.text).map(|m| ByteSpan { begin: m.start(), end: m.end() }),
+        };
+        self.table_spans = find_table_mentions(&self.text, &self.table, self.partial);
+    }
+    /// whether two statements are the same match, whichever queries found them
+    fn is_same_match(&self, other: &Statement) -> bool {
+        (&self.file_path, self.begin, self.statement_type, &self.table, self.nested)
+            == (&other.file_path, other.begin, other.statement_type, &other.table, other.nested)
+    }
+    /// the statement type followed by the flags that qualify the match,
+    /// e.g. `SELECT nested=true`
+    pub fn type_with_flags(&self) -> String {
+        let mut s = self.statement_type.to_string();
+        if self.nested  { s.push_str(" nested=true"); }
+        if self.partial { s.push_str(" partial=true"); }
+        if self.dynamic { s.push_str(" dynamic=true"); }
+        s
+    }
+}
+
+pub fn find_statements(file_path: &Path, search_query: &SearchQuery, opts: &ScanOpts) -> Option<Vec<Statement>> {
+    // return a vector of all the statements from a file that match the search query
+    // a file that can't be opened (e.g. locked or permission-denied) has no
+    // statements to find
+    let reader = handler::open(file_path).ok()?;
+    let opts = ScanOpts { dialect: opts.dialect.resolve(file_path), ..opts.clone() };
+    find_reader_statements(reader, file_path, search_query, &opts)
+}
+
+/// whether there is anything left to read, like the (unstable)
+/// `BufRead::has_data_left`
+fn has_data_left(reader: &mut dyn BufRead) -> std::io::Result<bool> {
+    reader.fill_buf().map(|buffer| !buffer.is_empty())
+}
+
+/// the statements of a file (or of the SQL run by an EXEC of the file) that
+/// match the search query, read from a reader
+fn find_reader_statements(mut reader: Box<dyn BufRead + '_>, file_path: &Path, search_query: &SearchQuery, opts: &ScanOpts) -> Option<Vec<Statement>> {
+    let mut statements = Vec::<Statement>::new();
+    let dialect = opts.dialect;
+
+    // every line is read into the same buffer, and only its trimmed text
+    // (without the `;` that may end the previous statement) is copied out
+    let mut buffer = String::new();
+    // the bytes read so far, and where the line that was read last begins
+    let bytes_read = Cell::new(0);
+    let line_start = Cell::new(0);
+    let mut read_next_line = |reader: &mut dyn BufRead, trim_semicolons: bool| -> String {
+        buffer.clear();
+        let _ = reader.read_line(&mut buffer);
+        line_start.set(bytes_read.get());
+        bytes_read.set(bytes_read.get() + buffer.len());
+        match trim_semicolons {
+            true  => buffer.trim().trim_start_matches(';').to_string(),
+            false => buffer.trim().to_string(),
+        }
+    };
+
+    // dynamic statements begin inside a string literal,
+    // e.g. `SET @sql = N'UPDATE ' + @schema + '.t_order SET ...'`
+    let literal_re = Regex::new(r"N?'\s*([[:alpha:]]+)").expect("regex didn't compile");
+    let create_table_re = Regex::new(r"^(?i)create\s+(or\s+replace\s+)?([[:alpha:]]+\s+)?table\b").expect("regex didn't compile");
+    let keyword_statement_type = |line: &str| -> Option<StatementType> {
+        let mut words = line.split_whitespace();
+        let first_word: String = words.next().unwrap().to_lowercase();
+        // DDL is identified by the kind of object it creates/alters/drops.
+        // Whether an ALTER TABLE switches a partition isn't known until the
+        // whole statement has been read.
+        let second_word: String = words.next().unwrap_or_default().to_lowercase();
+        match (first_word.as_str(), second_word.as_str()) {
+            ("create" | "alter" | "drop", "queue") => return Some(StatementType::Queue),
+            ("alter", "table") => return Some(StatementType::Alter),
+            ("drop", "table") => return Some(StatementType::Drop),
+            _ => {},
+        }
+        // likewise only the CREATE TABLEs that clone another table are kept
+        if create_table_re.is_match(line) {
+            return Some(StatementType::Clone);
+        }
+        if let Some(statement_type) = dialect.statement_keyword(&first_word) {
+            return Some(statement_type);
+        }
+        if let Some(keyword) = search_query.keywords.iter().find(|keyword| keyword.word.eq_ignore_ascii_case(&first_word)) {
+            return Some(keyword.statement_type);
+        }
+        StatementType::try_from(first_word).ok()
+    };
+    // the statements built for an EXEC are found where they're run instead
+    let try_statement_type_from_line = |line: String| -> Option<StatementType> {
+        if let Some(statement_type) = keyword_statement_type(&line) {
+            return Some(statement_type);
+        }
+        if !opts.dynamic_sql || opts.resolve_exec { return None; }
+        let literal_word: String = literal_re.captures(&line)?.get(1)?.as_str().to_lowercase();
+        StatementType::try_from(literal_word).ok()
+    };
+
+    let clean_text = |s: String| -> String {
+        join_literal_fragments(&dialect.bracket_identifiers(dialect.trim_comment(&s.replace('\t', " "))))
+    };
+
+    // whether the statements of a type are matched by the query: as
+    // themselves, by the reads of their subqueries (which are matched by
+    // SELECT queries even when the statement itself isn't; the syntax tree
+    // also has the reads of the other statements, e.g. INSERT ... SELECT) or
+    // as an upsert
+    let match_types = |statement_type: StatementType, has_ctes: bool| -> (bool, bool, bool) {
+        let match_statement = search_query.statement_types.contains(&statement_type);
+        let match_nested = search_query.statement_types.contains(&StatementType::Select)
+            && (statement_type.has_nested_reads() || has_ctes || opts.engine == Engine::Ast);
+        let match_upsert = search_query.statement_types.contains(&StatementType::Update)
+            && statement_type == StatementType::Insert
+            && dialect.has_upsert();
+        (match_statement, match_nested, match_upsert)
+    };
+    // a statement that begins with CTEs (`WITH name AS (...)`) is of the type
+    // of the statement that follows them, which isn't known until the whole
+    // statement has been read
+    let cte_re = Regex::new(&format!(r"^(?i:with)(\s+(?i:recursive))?\s+{NAME}\s*(\(|(?i:as)\b|$)")).expect("regex didn't compile");
+
+    // the known values of string variables in the current procedure, used to
+    // resolve the object names of dynamic SQL
+    let create_procedure_re = Regex::new(r"^(?i)create\s+(or\s+alter\s+)?proc(edure)?\b").expect("regex didn't compile");
+    let mut variables = Variables::new();
+    // an assignment whose literal goes on over the next lines, with
+    // `--dynamic-sql`
+    let mut open_assignment: Option<String> = None;
+    let mut databases = AttachedDatabases::new();
+    // an upsert updates the existing rows of the table it inserts into
+    let upsert_re = Regex::new(r"\b(?i:on\s+conflict)\b.*?\b(?i:do\s+update)\b|\b(?i:on\s+duplicate\s+key\s+update)\b")
+        .expect("regex didn't compile");
+
+    // T-SQL scripts are run a batch at a time, the batches being separated
+    // by GO (which may be given a count, `GO 5`). Nothing carries over from
+    // one batch to the next: not a statement, a comment or a variable.
+    let go_re = dialect.batch_separator();
+    let mut batch: usize = 0;
+    // in the dialects whose statements end with a delimiter, the `;` or the
+    // delimiter of a MySQL script (`DELIMITER //`, which ends the body of a
+    // procedure whose statements still end with `;`) at the end of a line
+    let mut delimiter = String::from(";");
+    let ends_with_delimiter = |line: &str, delimiter: &str| -> bool {
+        let code = dialect.trim_comment(line).trim_end();
+        dialect.ends_at_delimiter() && (code.ends_with(';') || code.ends_with(delimiter))
+    };
+    // a `;` is kept in the text of a statement, like in the other dialects,
+    // but not a delimiter of the script's own
+    let strip_delimiter = |line: String, delimiter: &str| -> String {
+        if delimiter == ";" { return line; }
+        match dialect.trim_comment(&line).trim_end().strip_suffix(delimiter) {
+            Some(code) => code.to_string(),
+            None => line,
+        }
+    };
+
+    let mut comment_level: u8 = 0;
+    let mut i: isize = -1;
+    // while let Ok(line) = reader.read_line().unwrap().trim().trim_start_matches(';').to_string() {
+    while let Ok(is_more_to_read) = has_data_left(reader.as_mut()) {
+        if !is_more_to_read { break; }
+        i+= 1;
+        let line = read_next_line(reader.as_mut(), true);
+
+        if go_re.is_match(&line) {
+            batch += 1;
+            comment_level = 0;
+            variables.clear();
+            open_assignment = None;
+            continue;
+        }
+        if line.is_empty()         { continue; }
+        if dialect.is_comment(&line) { continue; }
+        if line.contains("/*")    { comment_level +=1; }
+        if line.contains("*/")    { comment_level -=1; }
+        if comment_level > 0 {
+            if opts.why && try_statement_type_from_line(line.clone()).is_some() {
+                println!("line {}: skipped, inside a block comment: {}", i + 1, line);
+            }
+            continue;
+        }
+
+        if let Some(line_delimiter) = dialect.delimiter_change(&line) {
+            delimiter = line_delimiter;
+            continue;
+        }
+        if create_procedure_re.is_match(&line) { variables.clear(); }
+        if opts.resolve_exec {
+            // the lines of a literal aren't statements of their own, they're
+            // assigned once the literal is closed
+            if let Some(mut assignment) = open_assignment.take() {
+                assignment.push(' ');
+                assignment.push_str(&line);
+                match has_open_literal(&assignment) {
+                    true  => open_assignment = Some(assignment),
+                    false => variables.assign(&assignment),
+                }
+                continue;
+            }
+            if has_open_literal(&line) && variables.is_assignment(&line) {
+                open_assignment = Some(line);
+                continue;
+            }
+        }
+        variables.assign(&line);
+        if opts.resolve_exec {
+            if let Some((sql, sql_partial)) = variables.executed_sql(&line) {
+                if opts.why {
+                    println!("line {}: EXEC of dynamic SQL: {}", i + 1, sql);
+                }
+                let line_number: usize = i.try_into().expect("i should be positive by the time the loop starts");
+                let exec_opts = ScanOpts { why: false, resolve_exec: false, ..opts.clone() };
+                let exec_reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new(split_executed_sql(&sql).into_bytes()));
+                let executed = find_reader_statements(exec_reader, file_path, search_query, &exec_opts).unwrap_or_default();
+                for statement in executed {
+                    statements.push(Statement {
+                        begin:   line_number,
+                        end:     line_number,
+                        bytes:   ByteSpan { begin: line_start.get(), end: bytes_read.get() },
+                        batch,
+                        partial: statement.partial || sql_partial,
+                        dynamic: true,
+                        ..statement
+                    });
+                }
+            }
+        }
+        if dialect == Dialect::Sqlite { databases.attach(&line); }
+
+        // check if the first word of the line is the start of a statement that
+        // we care about based on the search query
+        let statement_type = try_statement_type_from_line(line.clone());
+        if let Some(statement_type) = statement_type.filter(|t| opts.why && !dialect.supports(*t)) {
+            println!("line {}: {} isn't a statement of the {:?} dialect", i + 1, statement_type, dialect);
+        }
+        let statement_type = statement_type.filter(|statement_type| dialect.supports(*statement_type));
+        let is_cte = statement_type.is_none() && cte_re.is_match(&line);
+        // a statement that begins in a literal rather than with its keyword
+        let dynamic = statement_type.is_some() && keyword_statement_type(&line).is_none();
+        if statement_type.is_some() || is_cte {
+            let (mut match_statement, mut match_nested, mut match_upsert) = match statement_type {
+                // an ALTER TABLE may turn out to be a partition switch
+                Some(StatementType::Alter) => {
+                    let (alter, switch) = (match_types(StatementType::Alter, false), match_types(StatementType::Switch, false));
+                    (alter.0 || switch.0, alter.1 || switch.1, alter.2 || switch.2)
+                },
+                Some(statement_type) => match_types(statement_type, false),
+                None => (true, true, true),
+            };
+            if !match_statement && !match_nested && !match_upsert {
+                if opts.why {
+                    println!("line {}: {} statement, which isn't a type of the query", i + 1, statement_type.unwrap());
+                }
+                continue;
+            }
+            // if we're in a statement type that was in the search query, then
+            // we need to read the entire query to determine whether contains
+            // a table from the search query
+            // TODO: this can maybe be optimized by checking each line to see
+            // if it has one of the keywords preceeding the table name, adding
+            // the following line to statement_text if it does, and then
+            // checking statement_text for the table.
+            let begin: usize = i.try_into().expect("i should be positive by the time the loop starts");
+            let begin_byte = line_start.get();
+            let begin_batch = batch;
+            // let mut statement_text = line.to_string() + " ";
+            // a statement may end with the line that begins it
+            let mut ended_by_delimiter = ends_with_delimiter(&line, &delimiter);
+            let (line, mut partial) = variables.substitute(&line);
+            let mut statement_text = clean_text(strip_delimiter(line, &delimiter)) + " ";
+            if dialect == Dialect::Sqlite { statement_text = databases.normalize(&statement_text); }
+            while let Ok(is_more_to_read) = has_data_left(reader.as_mut()) {
+                let (line, is_more_to_read) = match ended_by_delimiter {
+                    true  => (String::new(), false),
+                    false => {
+                        i += 1;
+                        (read_next_line(reader.as_mut(), false), is_more_to_read)
+                    },
+                };
+
+                // a GO ends the statement as well as the batch
+                let ends_batch = go_re.is_match(&line);
+                if ends_batch {
+                    batch += 1;
+                    comment_level = 0;
+                    variables.clear();
+                }
+                if dialect.is_comment(&line) { continue; }
+                if line.contains("/*")    { comment_level +=1; }
+                if line.contains("*/")    { comment_level -=1; }
+                if comment_level > 0      { continue; }
+
+                // start building up statement_text by concatenating each line
+                // until we reach an empty line, a semi-colon or a GO, which
+                // signals the end of the statement (or, in the dialects whose
+                // statements end with a delimiter, a line that ends with it)
+                if !line.is_empty() && !line.starts_with(';') && !ends_batch {
+                    ended_by_delimiter = ends_with_delimiter(&line, &delimiter);
+                    let (line, line_partial) = variables.substitute(&line);
+                    partial |= line_partial;
+                    let line = match dialect {
+                        Dialect::Sqlite => databases.normalize(&clean_text(line)),
+                        _ => clean_text(strip_delimiter(line, &delimiter)),
+                    };
+                    statement_text.push_str(&(line + " "));
+                    if is_more_to_read && !ended_by_delimiter { continue; }
+                } else if line.is_empty() && dialect.ends_at_delimiter() && is_more_to_read && !ended_by_delimiter {
+                    continue;
+                }
+
+                // when a table name was resolved from a partially known
+                // variable, only its prefix is known
+                let matches_table = |table: &str| -> bool {
+                    match partial {
+                        true  => search_query.matches_table_prefix(table),
+                        false => search_query.matches_table(table),
+                    }
+                };
+                let end: usize = i.try_into().expect("i should be positive by the time the loop starts");
+                // the line that ends the statement is only part of it if it
+                // is the last line of the file
+                let bytes = ByteSpan {
+                    begin: begin_byte,
+                    end:   match (line.is_empty() || line.starts_with(';') || ends_batch) && !ended_by_delimiter {
+                        true  => line_start.get(),
+                        false => bytes_read.get(),
+                    },
+                };
+                let ctes = match is_cte {
+                    true  => split_ctes(&statement_text),
+                    false => None,
+                };
+                let statement_type = match (statement_type, &ctes) {
+                    (Some(statement_type), _) => statement_type,
+                    (None, Some((_, offset))) if !statement_text[*offset..].trim().is_empty() => {
+                        let cte_statement_type = try_statement_type_from_line(statement_text[*offset..].to_string())
+                            .filter(|statement_type| dialect.supports(*statement_type));
+                        let Some(cte_statement_type) = cte_statement_type else { break; };
+                        (match_statement, match_nested, match_upsert) = match_types(cte_statement_type, true);
+                        if !match_statement && !match_nested && !match_upsert {
+                            if opts.why {
+                                println!("line {}: {} statement, which isn't a type of the query", begin + 1, cte_statement_type);
+                            }
+                            break;
+                        }
+                        cte_statement_type
+                    },
+                    _ => break,
+                };
+                // a statement that begins with a keyword of the config is
+                // read as one of its type to find its tables, e.g.
+                // `AUDIT_INSERT t_order (...)` as `INSERT INTO t_order (...)`
+                let read_text = read_as_keyword(&search_query.keywords, &statement_text);
+                let statement_type = match statement_type {
+                    StatementType::Alter => {
+                        let is_switch = dialect.supports(StatementType::Switch)
+                            && StatementType::Switch.find_table(&read_text).is_some();
+                        let statement_type = if is_switch { StatementType::Switch } else { StatementType::Alter };
+                        (match_statement, match_nested, match_upsert) = match_types(statement_type, false);
+                        if !match_statement && !match_nested && !match_upsert {
+                            if opts.why {
+                                println!("line {}: {} statement, which isn't a type of the query", begin + 1, statement_type);
+                            }
+                            break;
+                        }
+                        statement_type
+                    },
+                    statement_type => statement_type,
+                };
+                // the tables of the statement and the tables it reads, from
+                // its syntax tree if it can be parsed
+                let (tables, nested_tables) = match opts.engine {
+                    Engine::Ast   => ast::find_tables(&read_text, statement_type, dialect),
+                    Engine::Regex => None,
+                }.unwrap_or_else(|| match &ctes {
+                    Some((ctes, offset)) => find_cte_tables(statement_type, &statement_text, ctes, *offset),
+                    None => (statement_type.find_tables(&read_text), statement_type.find_nested_tables(&read_text)),
+                });
+                // temporary objects are only found if they're asked for,
+                // by the options or by their name in the query (the
+                // "table" of a SEND is its message type or conversation)
+                let (mut tables, mut nested_tables) = (tables, nested_tables);
+                if !opts.include_temp && statement_type != StatementType::Send {
+                    tables.retain(|table| !is_temp_object(table) || search_query.names_table(table));
+                    nested_tables.retain(|table| !is_temp_object(table) || search_query.names_table(table));
+                }
+                let references = table_refs(statement_type, &read_text, &tables, &nested_tables);
+                // a query for some columns only matches the statements that
+                // write them, which the tables it reads don't
+                let writes_columns = search_query.columns.is_empty()
+                    || search_query.writes_columns(statement_type, &read_text);
+                let found = statements.len();
+                // a MERGE query matches the target of a MERGE and the tables
+                // of its USING clause, unless it's for either role alone
+                let is_merge = statement_type == StatementType::Merge;
+                let match_target = match_statement && (!is_merge || search_query.matches_merge_role(false));
+                let match_source = match_statement && is_merge && search_query.matches_merge_role(true);
+                // a match of the statement (or of the tables it reads, when
+                // nested) for one of its tables
+                let found_statement = |statement_type: StatementType, table: &str, nested: bool| -> Statement {
+                    Statement {
+                        file_path:      file_path.to_path_buf(),
+                        statement_type,
+                        table:          split_schema(table).1.to_string(),
+                        schema:         split_schema(table).0.map(String::from),
+                        begin,
+                        end,
+                        bytes,
+                        batch:          begin_batch,
+                        text:           statement_text.clone(),
+                        keyword_span:   None,
+                        table_spans:    Vec::new(),
+                        nested,
+                        partial,
+                        dynamic,
+                        captures:       BTreeMap::new(),
+                        object:         None,
+                        module:         None,
+                        header:         None,
+                        queries:        Vec::new(),
+                        tables:         references.clone(),
+                        note:           None,
+                        commits:        Vec::new(),
+                    }
+                };
+                if match_target && writes_columns {
+                    statements.extend(tables.iter().filter(|table| matches_table(table)).map(|table| found_statement(statement_type, table, false)));
+                }
+                if match_nested && search_query.columns.is_empty() {
+                    statements.extend(nested_tables.iter().filter(|table| matches_table(table)).map(|table| found_statement(StatementType::Select, table, true)));
+                }
+                if match_source && search_query.columns.is_empty() {
+                    statements.extend(nested_tables.iter().filter(|table| matches_table(table)).map(|table| found_statement(statement_type, table, true)));
+                }
+                if opts.why {
+                    // the tables the query could have matched: the targets of
+                    // the statement and/or the tables it reads
+                    let mut candidates: Vec<String> = Vec::new();
+                    if match_target { candidates.extend(tables.iter().cloned()); }
+                    if match_nested || match_source { candidates.extend(nested_tables.iter().cloned()); }
+                    let verdict = match (statements.len() > found, candidates.is_empty()) {
+                        (true, _)      => String::from("matched"),
+                        (false, _) if !writes_columns => format!("it writes none of the columns of the query ({})", search_query.columns.join(", ")),
+                        (false, true) if !match_statement => String::from("it reads no tables"),
+                        (false, true)  => String::from("no table could be parsed"),
+                        (false, false) => format!("none of its tables ({}) is a table of the query", candidates.join(", ")),
+                    };
+                    println!("line {}: {} statement, {}: {}", begin + 1, statement_type, verdict, statement_text.trim());
+                }
+                let upsert_writes_columns = search_query.columns.is_empty()
+                    || search_query.writes_columns(StatementType::Update, &statement_text);
+                if match_upsert && upsert_writes_columns && upsert_re.is_match(&statement_text) {
+                    statements.extend(tables.iter().filter(|table| matches_table(table)).map(|table| found_statement(StatementType::Update, table, false)));
+                }
+                break;
+            }
+            // stop reading the file as soon as a statement was found when
+            // only the first match is wanted
+            if opts.first_match && !statements.is_empty() { break; }
+        }
+    }
+    for statement in &mut statements {
+        statement.highlight();
+    }
+    match statements.len() {
+        0 => None,
+        _ => Some(statements),
+    }
+}
+
+pub fn file_is_match(searcher: &mut Searcher, path: &Path, search_query: &SearchQuery) -> bool {
+    prefilter_rejection(searcher, path, search_query).is_none()
+}
+
+/// whether the prefilter matches every query, or any of them
+pub fn file_matches_queries(searcher: &mut Searcher, path: &Path, search_queries: &[SearchQuery], query_match: QueryMatch) -> bool {
+    match query_match {
+        QueryMatch::All => search_queries.iter().all(|query| file_is_match(searcher, path, query)),
+        QueryMatch::Any => search_queries.iter().any(|query| file_is_match(searcher, path, query)),
+    }
+}
+
+pub fn prefilter_rejection(searcher: &mut Searcher, path: &Path, search_query: &SearchQuery) -> Option<String> {
+    // do a basic search for the tables and the statement types of a query.
+    // this search is only the first step to narrow-down the file-list.
+    // e.g. it won't tell us if a file has an update statement to `orders`, only
+    // that a file contains both an update statement and `orders`.
+    // Returns the pattern that didn't match, if any.
+    let mut patterns = vec![search_query.statement_pattern()];
+    if !search_query.any_table() {
+        patterns.push(search_query.table_pattern());
+    }
+    for pattern in patterns {
+        let matcher = RegexMatcher::new(pattern.as_str()).unwrap();
+        let mut is_match = false;
+        let set_found = |_l: u64, _s: &[u8]| -> Result<bool, _> {
+            is_match = true;
+            Ok(false) // return false to stop the search
+        };
+
+        // the SQL text of the file is searched, which isn't the file itself
+        // for every handler (e.g. a DACPAC is compressed)
+        let searched = handler::open(path).and_then(|reader| searcher.search_reader(&matcher, reader, Bytes(set_found)));
+        if searched.is_err() {
+            eprintln!("Error when searching {} for {}", path.display(), pattern);
+            return Some(pattern);
+        }
+
+        // exit early if we didn't find a match
+        if !is_match { return Some(pattern); }
+    }
+    None
+}
+pub fn find_file_statements(file_path: &Path, search_queries: &[SearchQuery], opts: &ScanOpts) -> Option<Vec<Statement>> {
+    // a file that can't be read has no statements to find
+    let text = handler::read_to_string(file_path).ok()?;
+    find_text_statements(file_path, &text, search_queries, opts)
+}
+
+pub fn find_text_statements(file_path: &Path, text: &str, search_queries: &[SearchQuery], opts: &ScanOpts) -> Option<Vec<Statement>> {
+    // return the statements of the text of a file matching each of the search
+    // queries, or None if any one of the queries has no matching statements in
+    // the file (or, when any query may match, if none of them has). A
+    // statement matched by several queries is returned once, with the names
+    // of all of them.
+    let pragmas = match opts.ignore_pragmas {
+        true  => parse_pragmas(text),
+        false => Vec::new(),
+    };
+    let dialect = match opts.dialect {
+        Dialect::Auto => Dialect::detect(text),
+        dialect => dialect,
+    };
+    let opts = &ScanOpts { dialect, ..opts.clone() };
+    let mut statements = Vec::<Statement>::new();
+    for query in search_queries {
+        let found: Vec<Statement> = find_reader_statements(Box::new(text.as_bytes()), file_path, query, opts)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|statement| !is_ignored(&pragmas, statement))
+            .collect();
+        if found.is_empty() && opts.query_match == QueryMatch::All { return None; }
+        for mut statement in found {
+            match statements.iter_mut().find(|s| s.is_same_match(&statement)) {
+                Some(s) if s.queries.contains(&query.name) => {},
+                Some(s) => s.queries.push(query.name.clone()),
+                None => {
+                    statement.queries.push(query.name.clone());
+                    statements.push(statement);
+                },
+            }
+        }
+    }
+    if statements.is_empty() { return None; }
+    let objects = objects::parse_objects(text);
+    for statement in &mut statements {
+        statement.object = objects::object_at(&objects, statement.begin).map(|object| object.name.clone());
+    }
+    Some(statements)
+}
+
+pub fn annotate_file_statements(file_path: &Path, config: &Config, headers: bool, statements: &mut [Statement]) {
+    // add the file-level information (module, captures, object headers) to
+    // each of the statements found in a file
+    let module = config.module(file_path);
+    for statement in statements.iter_mut() {
+        statement.module = module.map(String::from);
+    }
+    if config.captures.is_empty() && !headers { return; }
+    let text = match handler::read_to_string(file_path) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let mut captures: BTreeMap<String, String> = BTreeMap::new();
+    for capture in &config.captures {
+        captures.insert(capture.name.clone(), capture.values(&text).join(" "));
+    }
+    let file_headers = if headers { header::parse_headers(&text) } else { Vec::new() };
+    for statement in statements {
+        statement.captures = captures.clone();
+        statement.header = header::header_at(&file_headers, statement.begin).cloned();
+    }
+}
+
+pub fn author_matches(statement: &Statement, author: &str) -> bool {
+    statement.header.as_ref()
+        .and_then(|header| header.author.as_ref())
+        .is_some_and(|a| a.to_lowercase().contains(&author.to_lowercase()))
+}
+
+/// the statements of the files that match all of the queries, file by file
+pub fn search_files<'a>(file_paths: &'a [PathBuf], search_queries: &'a [SearchQuery]) -> impl Iterator<Item = Statement> + 'a {
+    search(file_paths, search_queries, ScanOpts::default())
+}
+
+/// a search for the statements matching some queries, for the programs that
+/// embed gresql (e.g. a TUI or a server) and show its results as they're found
+pub struct SearchEngine {
+    pub queries: Vec<SearchQuery>,
+    pub opts:    ScanOpts,
+}
+
+impl SearchEngine {
+    pub fn new(queries: Vec<SearchQuery>, opts: ScanOpts) -> SearchEngine {
+        SearchEngine { queries, opts }
+    }
+
+    /// a search for a built query, which decides how its queries are matched
+    /// (`--match`) whatever the options say
+    pub fn from_query(query: &query::Query, opts: ScanOpts) -> Result<SearchEngine, String> {
+        let (queries, query_match) = query.compile()?;
+        Ok(SearchEngine { queries, opts: ScanOpts { query_match, ..opts } })
+    }
+
+    /// the matching statements of the files, yielded as each file is scanned
+    /// rather than once all of them have been. Files are scanned only as the
+    /// iterator is advanced, so a search can be stopped by dropping it.
+    pub fn search_iter<'a>(&'a self, file_paths: &'a [PathBuf]) -> impl Iterator<Item = Statement> + 'a {
+        search(file_paths, &self.queries, self.opts.clone())
+    }
+}
+
+fn search<'a>(file_paths: &'a [PathBuf], search_queries: &'a [SearchQuery], opts: ScanOpts) -> impl Iterator<Item = Statement> + 'a {
+    let mut searcher = Searcher::new();
+    file_paths.iter()
+        .filter(move |path| file_matches_queries(&mut searcher, path, search_queries, opts.query_match))
+        .filter_map(move |path| find_file_statements(path, search_queries, &opts))
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn parse_statement_types() {
+        let invalid_characters: Vec<char> = vec![';', 'z', '\n', '*'];
+        for c in invalid_characters {
+            assert!(super::StatementType::try_from(c).is_err());
+        }
+
+        let invalid_strings: Vec<&str> = vec!["alter", "declare", "apply", "grant", ""];
+        for s in invalid_strings {
+            assert!(super::StatementType::try_from(s.to_string()).is_err());
+        }
+    }
+
+    #[test]
+    fn find_service_broker_tables() {
+        assert_eq!(StatementType::Receive.find_table("RECEIVE TOP(1) @body = message_body FROM q_order_events "),
+            Some(String::from("q_order_events")));
+        assert_eq!(StatementType::Send.find_table("SEND ON CONVERSATION @handle MESSAGE TYPE [//wms/order/created] (@body) "),
+            Some(String::from("[//wms/order/created]")));
+        assert_eq!(StatementType::Send.find_table("SEND ON CONVERSATION (@handle) "),
+            Some(String::from("@handle")));
+        assert_eq!(StatementType::Queue.find_table("CREATE QUEUE q_order_events WITH STATUS = ON "),
+            Some(String::from("q_order_events")));
+    }
+
+    #[test]
+    fn find_ddl_tables() {
+        assert_eq!(StatementType::Truncate.find_tables("TRUNCATE TABLE dbo.t_staging_import "), vec!["dbo.t_staging_import"]);
+        assert_eq!(StatementType::Drop.find_tables("DROP TABLE IF EXISTS #orders, [t_staging_import] "), vec!["#orders", "t_staging_import"]);
+        assert_eq!(StatementType::Alter.find_tables("ALTER TABLE t_order DROP COLUMN legacy_code "), vec!["t_order"]);
+        assert!(StatementType::writes().contains(&StatementType::Drop));
+    }
+
+    #[test]
+    fn find_partition_switch_tables() {
+        let statement = "ALTER TABLE t_order SWITCH PARTITION 3 TO t_order_archive PARTITION 3 ";
+        assert_eq!(StatementType::Switch.find_tables(statement), vec!["t_order", "t_order_archive"]);
+        assert!(StatementType::Switch.find_tables("ALTER TABLE t_order ADD note VARCHAR(20) ").is_empty());
+    }
+
+    #[test]
+    fn find_copy_and_clone_tables() {
+        assert_eq!(StatementType::Merge.find_table("MERGE INTO t_order o USING t_order_stage s ON o.id = s.id "),
+            Some(String::from("t_order")));
+        assert_eq!(StatementType::Copy.find_tables("COPY INTO t_order FROM @order_stage FILE_FORMAT = (TYPE = CSV) "),
+            vec!["t_order"]);
+        assert!(StatementType::Copy.find_nested_tables("COPY INTO t_order FROM @order_stage ").is_empty());
+        assert_eq!(StatementType::Copy.find_nested_tables("COPY INTO @order_unload FROM t_order "),
+            vec!["t_order"]);
+        let statement = "CREATE OR REPLACE TABLE t_order_backup DEEP CLONE t_order ";
+        assert_eq!(StatementType::Clone.find_tables(statement), vec!["t_order_backup"]);
+        assert_eq!(StatementType::Clone.find_nested_tables(statement), vec!["t_order"]);
+        assert!(StatementType::Clone.find_tables("CREATE TABLE t_order (id INT) ").is_empty());
+    }
+
+    #[test]
+    fn find_unicode_tables() {
+        assert_eq!(StatementType::Update.find_table("UPDATE Aufträge SET Menge = 1 "), Some(String::from("Aufträge")));
+        assert_eq!(StatementType::Delete.find_table("DELETE FROM [Commandes été] WHERE id = 1 "), Some(String::from("Commandes été")));
+        assert_eq!(StatementType::Select.find_tables("SELECT * FROM 注文 o JOIN [顧客] c ON c.id = o.顧客_id "), vec!["注文", "顧客"]);
+        let queries = parse_search_queries(&[String::from("u:[Aufträge],注文")]);
+        assert_eq!(queries[0].tables, vec!["Aufträge", "注文"]);
+    }
+
+    #[test]
+    fn find_set_operation_tables() {
+        let statement = "SELECT id FROM t_order UNION ALL SELECT id FROM t_order_archive EXCEPT SELECT id FROM t_order ";
+        assert_eq!(StatementType::Select.find_tables(statement), vec!["t_order", "t_order_archive"]);
+    }
+
+    #[test]
+    fn find_joined_tables() {
+        let statement = "SELECT * FROM t_order o INNER JOIN t_customer c ON c.id = o.customer_id LEFT OUTER JOIN t_region r ON r.id = c.region_id ";
+        assert_eq!(StatementType::Select.find_tables(statement), vec!["t_order", "t_customer", "t_region"]);
+
+        let statement = "UPDATE o SET status = 1 FROM t_order o CROSS JOIN t_config c ";
+        assert_eq!(StatementType::Update.find_table(statement), Some(String::from("t_order")));
+        assert_eq!(StatementType::Update.find_nested_tables(statement), vec!["t_config"]);
+    }
+
+    #[test]
+    fn find_derived_tables() {
+        let statement = "SELECT * FROM (SELECT region, amount FROM t_sales) src PIVOT (SUM(amount) FOR region IN ([north], [south])) p ";
+        assert_eq!(StatementType::Select.find_tables(statement), vec!["t_sales"]);
+
+        let statement = "SELECT * FROM t_order o JOIN (SELECT order_id, COUNT(*) n FROM t_order_detail GROUP BY order_id) d ON d.order_id = o.id ";
+        assert_eq!(StatementType::Select.find_tables(statement), vec!["t_order", "t_order_detail"]);
+    }
+
+    #[test]
+    fn find_subquery_tables() {
+        let statement = "DELETE FROM t_order WHERE id IN (SELECT order_id FROM t_order_detail WHERE x IN (SELECT x FROM t_x)) ";
+        assert_eq!(StatementType::Delete.find_nested_tables(statement), vec!["t_order_detail", "t_x"]);
+        assert!(StatementType::Insert.find_nested_tables(statement).is_empty());
+    }
+
+    #[test]
+    fn find_cte_statement_tables() {
+        let statement = "WITH recent AS (SELECT id FROM t_order), picks (id) AS (SELECT order_id FROM t_pick) \
+            UPDATE t_wave SET status = 1 WHERE id IN (SELECT id FROM recent) ";
+        let (ctes, offset) = split_ctes(statement).unwrap();
+        assert_eq!(ctes.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), vec!["recent", "picks"]);
+        assert!(statement[offset..].starts_with("UPDATE t_wave"));
+        assert_eq!(find_cte_tables(StatementType::Update, statement, &ctes, offset),
+            (vec![String::from("t_wave")], vec![String::from("t_order"), String::from("t_pick")]));
+
+        let statement = "WITH o AS (SELECT id, status FROM t_order_detail WHERE status = 0) UPDATE o SET status = 9 ";
+        let (ctes, offset) = split_ctes(statement).unwrap();
+        assert_eq!(find_cte_tables(StatementType::Update, statement, &ctes, offset),
+            (vec![String::from("t_order_detail")], Vec::new()));
+        assert_eq!(split_ctes("WITH (NOLOCK) "), None);
+    }
+
+    #[test]
+    fn match_qualified_tables() {
+        assert_eq!(StatementType::Update.find_table("UPDATE [Sales DB].[dbo].[t_order] SET x = 1 "), Some(String::from("dbo.t_order")));
+        assert_eq!(StatementType::Insert.find_table("INSERT INTO db..t_order (id) VALUES (1) "), Some(String::from("t_order")));
+        assert_eq!(split_schema("dbo.t_order"), (Some("dbo"), "t_order"));
+        let queries = parse_search_queries(&[String::from("u:[DBO].[t_order]"), String::from("u:t_order")]);
+        assert!(queries[0].matches_table("dbo.t_order"));
+        assert!(!queries[0].matches_table("sales.t_order"));
+        assert!(!queries[0].matches_table("t_order"));
+        assert!(queries[1].matches_table("sales.t_order"));
+    }
+
+    #[test]
+    fn find_table_roles() {
+        let statement = "UPDATE o SET status = 1 FROM t_order o JOIN t_order_detail d ON d.order_id = o.id WHERE d.x IN (SELECT x FROM t_x) ";
+        let tables = StatementType::Update.find_tables(statement);
+        let nested_tables = StatementType::Update.find_nested_tables(statement);
+        let roles: Vec<(String, TableRole)> = table_refs(StatementType::Update, statement, &tables, &nested_tables).into_iter()
+            .map(|table| (table.name, table.role))
+            .collect();
+        assert_eq!(roles, vec![
+            (String::from("t_order"), TableRole::Target),
+            (String::from("t_x"), TableRole::Source),
+            (String::from("t_order_detail"), TableRole::Join),
+        ]);
+    }
+
+    #[test]
+    fn resolve_target_aliases() {
+        let target = |statement_type: StatementType, statement: &str| statement_type.find_table(statement);
+        assert_eq!(target(StatementType::Update, "UPDATE p SET p.status = 9 FROM t_pick_detail d JOIN dbo.t_pick AS p ON p.id = d.pick_id "),
+            Some(String::from("dbo.t_pick")));
+        assert_eq!(target(StatementType::Update, "UPDATE [d] SET status = 1 FROM [t_pick_detail] [d] WITH (NOLOCK) "),
+            Some(String::from("t_pick_detail")));
+        assert_eq!(target(StatementType::Update, "UPDATE t_order SET x = (SELECT MAX(x) FROM t_y t_order) "),
+            Some(String::from("t_order")));
+        assert_eq!(target(StatementType::Delete, "DELETE x FROM t_order o LEFT JOIN t_x AS x ON x.id = o.id "),
+            Some(String::from("t_x")));
+        assert_eq!(target(StatementType::Delete, "DELETE FROM o FROM t_order o WHERE o.status = 9 "),
+            Some(String::from("t_order")));
+        assert_eq!(target(StatementType::Delete, "DELETE TOP (10) FROM t_order WHERE id IN (SELECT id FROM t_x) "),
+            Some(String::from("t_order")));
+        let statement = "DELETE x FROM t_order o JOIN t_x x ON x.id = o.id ";
+        assert_eq!(StatementType::Delete.find_nested_tables(statement), vec![String::from("t_order")]);
+    }
+
+    #[test]
+    fn find_procedure_calls() {
+        let statement = "EXEC dbo.usp_foo 1\nEXEC [dbo].[usp_bar];1 @id = 2\nEXECUTE @rc = usp_baz\nEXEC @proc\nEXEC (@sql)";
+        assert_eq!(StatementType::Exec.find_tables(statement), vec!["dbo.usp_foo", "dbo.usp_bar", "usp_baz"]);
+        assert_eq!(StatementType::try_from(String::from("execute")), Ok(StatementType::Exec));
+        assert!(!StatementType::writes().contains(&StatementType::Exec));
+    }
+
+    #[test]
+    fn exclude_files_and_directories() {
+        let excludes = exclude_globs(&[String::from("vendor"), String::from("build/*.sql")]).unwrap();
+        assert!(is_excluded(&excludes, Path::new("src/vendor/usp_x.sql")));
+        assert!(is_excluded(&excludes, Path::new("build/out.sql")));
+        assert!(!is_excluded(&excludes, Path::new("src/build/out.sql")));
+        assert!(!is_excluded(&excludes, Path::new("src/usp_vendor.sql")));
+        assert!(exclude_globs(&[String::from("[")]).is_err());
+    }
+
+    #[test]
+    fn walk_overlapping_paths_once() {
+        let temp_dir = TempDir::new("walk");
+        let dir = temp_dir.path();
+        let a = temp_dir.write("a.sql", "");
+        let b = temp_dir.write("sub/b.sql", "");
+        temp_dir.write("sub/notes.txt", "");
+        let paths: Vec<OsString> = [dir.join("sub"), b.clone(), dir.to_path_buf(), dir.join("./sub"), dir.join("[.sql"), dir.join("*.sql")]
+            .map(PathBuf::into_os_string).to_vec();
+        let mut walked: Vec<PathBuf> = walk_file_paths(&paths, &Override::empty()).collect();
+        walked.sort();
+        assert_eq!(walked, vec![a, b]);
+        assert_eq!(invalid_globs(&paths), vec![&paths[4]]);
+    }
+
+    #[test]
+    fn count_file_lines() {
+        let dir = TempDir::new("count-lines");
+        for (text, lines) in [("", 0), ("SELECT 1", 1), ("SELECT 1\r\n\r\nGO\r\n", 3), ("\n\nx", 3)] {
+            assert_eq!(count_lines(&dir.write("a.sql", text)).unwrap(), lines);
+        }
+    }
+
+    #[test]
+    fn statement_spans_and_objects() {
+        let text = "CREATE PROCEDURE dbo.usp_order_cancel AS\nUPDATE t_order\nSET status = 9\n\nEXEC usp_order_log\n";
+        let queries = parse_search_queries(&[String::from("us:t_order"), String::from("e:usp_order_log")]);
+        let statements = find_text_statements(Path::new("usp_order_cancel.sql"), text, &queries, &ScanOpts::default()).unwrap();
+        let spans: Vec<(&str, Access, &str)> = statements.iter()
+            .map(|s| (s.object.as_deref().unwrap_or_default(), s.access(), &text[s.bytes.begin..s.bytes.end]))
+            .collect();
+        assert_eq!(spans, vec![
+            ("usp_order_cancel", Access::Write, "UPDATE t_order\nSET status = 9\n"),
+            ("usp_order_cancel", Access::Execute, "EXEC usp_order_log\n"),
+        ]);
+    }
+
+    #[test]
+    fn merge_target_and_source_roles() {
+        let text = "MERGE INTO t_order AS o\nUSING t_order_stage AS s ON o.id = s.id\nWHEN MATCHED THEN UPDATE SET o.status = s.status;\n";
+        let find_merge = |query: &str| -> Vec<Statement> {
+            let reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new(text));
+            find_reader_statements(reader, Path::new("merge.sql"), &parse_search_queries(&[String::from(query)])[0], &ScanOpts::default()).unwrap_or_default()
+        };
+        let find = |query: &str| -> Vec<(StatementType, String, bool)> {
+            find_merge(query).into_iter().map(|s| (s.statement_type, s.table, s.nested)).collect()
+        };
+        let target = (StatementType::Merge, String::from("t_order"), false);
+        let source = (StatementType::Merge, String::from("t_order_stage"), true);
+        assert_eq!(find("m:"), vec![target.clone(), source.clone()]);
+        assert_eq!(find("m>:"), vec![target]);
+        assert_eq!(find("m<:"), vec![source]);
+        assert_eq!(find("m>:t_order_stage"), vec![]);
+        assert_eq!(find("s:t_order_stage"), vec![(StatementType::Select, String::from("t_order_stage"), true)]);
+        assert_eq!(find_merge("m<:")[0].tables.iter().map(|t| (t.name.as_str(), t.role)).collect::<Vec<_>>(),
+            vec![("t_order", TableRole::Target), ("t_order_stage", TableRole::Source)]);
+    }
+
+    #[test]
+    fn split_batches_at_go() {
+        let reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new("UPDATE t_order SET status = 1\nGO\n/* unclosed\nGO 2\nDELETE FROM t_order\n"));
+        let statements = find_reader_statements(reader, Path::new("batches.sql"), &parse_search_queries(&[String::from("ud:t_order")])[0], &ScanOpts::default()).unwrap();
+        let batches: Vec<(usize, usize, &str)> = statements.iter().map(|s| (s.begin, s.batch, s.text.as_str())).collect();
+        assert_eq!(batches, vec![(0, 0, "UPDATE t_order SET status = 1 "), (4, 2, "DELETE FROM t_order ")]);
+    }
+
+    #[test]
+    fn end_statements_at_delimiters() {
+        let reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new("# setup\nDELIMITER //\nUPDATE `t_order`\n\nSET status = 1; DELETE FROM t_order //\nDELIMITER ;\nDELETE FROM t_order;\n"));
+        let opts = ScanOpts { dialect: Dialect::Mysql, ..ScanOpts::default() };
+        let statements = find_reader_statements(reader, Path::new("delimiters.sql"), &parse_search_queries(&[String::from("ud:t_order")])[0], &opts).unwrap();
+        let lines: Vec<(usize, usize, &str)> = statements.iter().map(|s| (s.begin, s.end, s.text.as_str())).collect();
+        assert_eq!(lines, vec![(2, 4, "UPDATE [t_order] SET status = 1; DELETE FROM t_order  "), (6, 6, "DELETE FROM t_order; ")]);
+    }
+
+    #[test]
+    fn highlight_keywords_and_tables() {
+        let reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new("UPDATE o SET status = 9 FROM [dbo].[t_order] o JOIN t_order_line l ON l.id = o.id\n"));
+        let statements = find_reader_statements(reader, Path::new("highlight.sql"), &parse_search_queries(&[String::from("us:t_order")])[0], &ScanOpts::default()).unwrap();
+        let spans: Vec<(Option<&str>, Vec<&str>)> = statements.iter()
+            .map(|s| (
+                s.keyword_span.map(|span| &s.text[span.begin..span.end]),
+                s.table_spans.iter().map(|span| &s.text[span.begin..span.end]).collect(),
+            ))
+            .collect();
+        assert_eq!(spans, vec![(Some("UPDATE"), vec!["[t_order]"])]);
+    }
+
+    #[test]
+    fn exclude_temp_objects() {
+        let text = "INSERT INTO #orders SELECT id FROM t_order\n\nDELETE FROM @picks\n\nDELETE FROM t_pick\n";
+        let tables = |query: &str, include_temp: bool| -> Vec<String> {
+            let opts = ScanOpts { include_temp, ..ScanOpts::default() };
+            let reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new(text));
+            find_reader_statements(reader, Path::new("temp.sql"), &parse_search_queries(&[String::from(query)])[0], &opts)
+                .unwrap_or_default().into_iter().map(|s| s.table).collect()
+        };
+        assert_eq!(tables("id:", false), vec!["t_pick"]);
+        assert_eq!(tables("id:", true), vec!["#orders", "@picks", "t_pick"]);
+        assert_eq!(tables("i:#orders", false), vec!["#orders"]);
+    }
+
+    #[test]
+    fn resolve_executed_sql() {
+        let text = "DECLARE @sql NVARCHAR(MAX) = N'DELETE FROM t_order\n    WHERE id = 1'\nSET @sql = @sql + N'; UPDATE t_order SET x = 1'\nEXEC sp_executesql @sql\n";
+        let found = |resolve_exec: bool| -> Vec<(usize, StatementType, bool)> {
+            let opts = ScanOpts { resolve_exec, ..ScanOpts::default() };
+            let reader: Box<dyn BufRead> = Box::new(std::io::Cursor::new(text));
+            find_reader_statements(reader, Path::new("exec.sql"), &parse_search_queries(&[String::from("ud:t_order")])[0], &opts)
+                .unwrap_or_default().into_iter().map(|s| (s.begin, s.statement_type, s.dynamic)).collect()
+        };
+        assert_eq!(found(false), vec![(0, StatementType::Delete, true)]);
+        assert_eq!(found(true), vec![(3, StatementType::Delete, true), (3, StatementType::Update, true)]);
+    }
+
+    #[test]
+    fn read_file_lists() {
+        let paths = |list: &str| read_file_list(list.as_bytes()).unwrap();
+        assert_eq!(paths("src/a.sql\r\nsrc/b c.sql\n\n"), vec![OsString::from("src/a.sql"), OsString::from("src/b c.sql")]);
+        assert_eq!(paths("src/a.sql\0src/new\nline.sql\0"), vec![OsString::from("src/a.sql"), OsString::from("src/new\nline.sql")]);
+    }
+
+    #[test]
+    fn search_files_incrementally() {
+        let dir = TempDir::new("search");
+        let file_paths: Vec<PathBuf> = vec![
+            dir.write("a.sql", "UPDATE t_order SET status = 1\n"),
+            dir.write("b.sql", "DELETE FROM t_pick\n"),
+            dir.write("c.sql", "DELETE FROM t_order\n"),
+        ];
+        let opts = ScanOpts { query_match: QueryMatch::Any, ..ScanOpts::default() };
+        let engine = SearchEngine::new(parse_search_queries(&[String::from("u:t_order"), String::from("d:t_order")]), opts);
+        let mut results = engine.search_iter(&file_paths);
+        assert_eq!(results.next().map(|s| s.statement_type), Some(StatementType::Update));
+        // the rest of the files are only scanned once they're needed
+        std::fs::remove_file(&file_paths[2]).unwrap();
+        assert_eq!(results.next(), None);
+    }
+
+    #[test]
+    fn parse_labeled_queries() {
+        let queries = parse_search_queries(&[String::from("orders=u:t_order"), String::from("d:t_pick")]);
+        assert_eq!(queries[0].label.as_deref(), Some("orders"));
+        assert_eq!(queries[0].tables, vec!["t_order"]);
+        assert_eq!(queries[1].label, None);
+        let queries = parse_search_queries(&[String::from("d:"), String::from("d:*")]);
+        assert!(queries.iter().all(|query| query.any_table() && query.statement_types == vec![StatementType::Delete]));
+    }
+
+    #[test]
+    fn warn_on_overlapping_queries() {
+        let strings = |queries: &[&str]| queries.iter().map(|q| q.to_string()).collect::<Vec<String>>();
+        assert!(overlapping_queries(&strings(&["u:t_order", "d:t_order", "u:t_pick"]), QueryMatch::All).is_empty());
+        assert_eq!(overlapping_queries(&strings(&["u:t_order,t_pick", "u:T_PICK,t_order"]), QueryMatch::All),
+            vec!["'u:t_order,t_pick' and 'u:T_PICK,t_order' are the same query"]);
+        let warnings = overlapping_queries(&strings(&["ud:t_order", "u:t_order"]), QueryMatch::All);
+        assert!(warnings[0].starts_with("'u:t_order' overlaps 'ud:t_order': the UPDATE statements"));
+        assert_eq!(overlapping_queries(&strings(&["x=u:t_order", "x=d:t_pick"]), QueryMatch::All).len(), 1);
+        let warnings = overlapping_queries(&strings(&["ud:t_order", "u:t_order"]), QueryMatch::Any);
+        assert!(warnings[0].ends_with("so 'u:t_order' finds nothing that 'ud:t_order' doesn't"));
+    }
+
+    #[test]
+    fn parse_query_columns() {
+        let queries = parse_search_queries(&[
+            String::from("u:t_order,/t_order_\\d+/@order_status, ship_date"),
+            String::from("u:@orders@id"),
+            String::from("u:/t_x/@id"),
+        ]);
+        assert_eq!(queries[0].tables, vec!["t_order"]);
+        assert_eq!(queries[0].columns, vec!["order_status", "ship_date"]);
+        assert!(queries[0].matches_table("t_order_1"));
+        assert_eq!(queries[1].tables, vec!["@orders"]);
+        assert_eq!(queries[1].columns, vec!["id"]);
+        assert!(queries[2].matches_table("T_X"));
+        assert_eq!(queries[2].columns, vec!["id"]);
+    }
+
+    #[test]
+    fn parse_table_patterns() {
+        let queries = parse_search_queries(&[String::from("u:t_order(,/t_order_(detail|line)s?/,/t_[a-z]{1,3}:x/")]);
+        assert_eq!(queries[0].tables, vec!["t_order("]);
+        assert!(queries[0].matches_table("t_order("));
+        assert!(queries[0].matches_table("T_Order_Details"));
+        assert!(queries[0].matches_table("t_ab:x"));
+        assert!(!queries[0].matches_table("t_order_detail_log"));
+        assert!(Regex::new(&queries[0].table_pattern()).is_ok());
+        assert_eq!(invalid_table_patterns(&[String::from("s:/t_order(/")]).len(), 1);
+
+        let queries = parse_search_queries(&[String::from("u:t_pick_*,t_wave_?,T_Order")]);
+        assert!(queries[0].matches_table("T_PICK_DETAIL"));
+        assert!(queries[0].matches_table("t_wave_1"));
+        assert!(!queries[0].matches_table("t_wave_10"));
+        assert!(!queries[0].matches_table("t_picks"));
+        assert!(queries[0].matches_table("dbo.t_order"));
+    }
+
+    #[test]
+    fn exclude_query_tables() {
+        let mut queries = parse_search_queries(&[String::from("u:t_order*,!t_order_audit"), String::from("u:!/t_order_(audit|log)/,!@orders")]);
+        assert!(queries[0].matches_table("t_order_log"));
+        assert!(!queries[0].matches_table("dbo.t_order_audit"));
+        assert!(queries[1].matches_table("t_order"));
+        assert!(!queries[1].matches_table("T_ORDER_LOG"));
+        assert!(!queries[1].matches_table("@orders"));
+        queries[0].exclude_table("t_order_log").unwrap();
+        assert!(!queries[0].matches_table("t_order_log"));
+        assert!(queries[0].exclude_table("/t_order(/").is_err());
+        assert_eq!(invalid_table_patterns(&[String::from("u:!/t_order(/")]).len(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+// A rules file declares the statements that mustn't be written at all, each as
+// a search query (optionally narrowed by a regex of its text) with a severity,
+// e.g.
+//
+//   rules:
+//     - name:     audit-log-delete
+//       forbid:   d:t_audit_log
+//       hint:     the audit log is append-only
+//     - name:     pii-select-star
+//       forbid:   s:t_customer_pii
+//       text:     'SELECT\s+\*'
+//       severity: warning
+//
+// `gresql lint --rules rules.yaml` reports every statement that one of them
+// forbids. Unlike a policy, which allows the writes of some objects, a rule
+// forbids the statements wherever they are.
+use regex::Regex;
+use serde::{ Deserialize, Deserializer };
+use std::fmt;
+use std::io::{ Error, ErrorKind };
+use std::path::Path;
+
+use crate::config::interpolate;
+use crate::{ parse_search_queries, SearchQuery, Statement };
+
+pub const DEFAULT_RULES_FILE: &str = "gresql-rules.yaml";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    pub rules: Vec<LintRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LintRule {
+    /// the name of the rule, by which its violations are reported
+    pub name:     String,
+    /// the search query of the forbidden statements, e.g. `d:t_audit_log`
+    pub forbid:   String,
+    /// a regex that the text of a statement also has to match to be
+    /// forbidden, ignoring case
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub text:     Option<Regex>,
+    #[serde(default)]
+    pub severity: Severity,
+    /// how to fix a violation, e.g. which view to read instead
+    #[serde(default)]
+    pub hint:     Option<String>,
+}
+
+/// how serious a violation is, which is also its SARIF level
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error   => "error",
+            Severity::Warning => "warning",
+            Severity::Note    => "note",
+        })
+    }
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let regex = String::deserialize(deserializer)?;
+    Regex::new(&format!("(?i){}", regex)).map(Some).map_err(serde::de::Error::custom)
+}
+
+/// a statement forbidden by a rule
+pub struct LintViolation<'a> {
+    pub statement: &'a Statement,
+    pub rule:      &'a LintRule,
+}
+
+impl Rules {
+    pub fn load(path: &Path) -> Result<Rules, Error> {
+        let text = interpolate(&std::fs::read_to_string(path)?)?;
+        let rules: Rules = serde_yaml::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        for rule in &rules.rules {
+            if rule.name.contains(['=', '/']) || rule.search_query().is_none() {
+                return Err(Error::new(ErrorKind::InvalidData, format!("rule {}: invalid search query {}", rule.name, rule.forbid)));
+            }
+        }
+        Ok(rules)
+    }
+
+    /// the search queries of the rules, named after them, which a file has
+    /// to match any of
+    pub fn search_queries(&self) -> Vec<SearchQuery> {
+        self.rules.iter().filter_map(LintRule::search_query).collect()
+    }
+
+    /// the statements (found by the search queries of the rules) that a rule
+    /// forbids, once for each rule
+    pub fn violations<'a>(&'a self, statements: &'a [Statement]) -> Vec<LintViolation<'a>> {
+        let mut violations: Vec<LintViolation> = Vec::new();
+        for statement in statements {
+            for rule in self.rules.iter().filter(|rule| rule.forbids(statement)) {
+                violations.push(LintViolation { statement, rule });
+            }
+        }
+        violations
+    }
+}
+
+impl LintRule {
+    fn search_query(&self) -> Option<SearchQuery> {
+        let mut queries = parse_search_queries(&[format!("{}={}", self.name, self.forbid)]);
+        match queries.len() {
+            1 => queries.pop(),
+            _ => None,
+        }
+    }
+
+    fn forbids(&self, statement: &Statement) -> bool {
+        statement.queries.contains(&self.name)
+            && self.text.as_ref().is_none_or(|text| text.is_match(&statement.text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ ByteSpan, StatementType };
+    use std::path::PathBuf;
+
+    #[test]
+    fn report_forbidden_statements() {
+        let rules: Rules = serde_yaml::from_str(r"
+            rules:
+              - name:     audit-log-delete
+                forbid:   d:t_audit_log
+                hint:     the audit log is append-only
+              - name:     pii-select-star
+                forbid:   s:t_customer_pii
+                text:     'SELECT\s+\*'
+                severity: warning
+        ").unwrap();
+        assert_eq!(rules.search_queries().iter().map(|q| q.name.as_str()).collect::<Vec<&str>>(),
+            vec!["audit-log-delete", "pii-select-star"]);
+
+        let statement = |statement_type: StatementType, table: &str, text: &str, query: &str| Statement {
+            file_path: PathBuf::from("procs.sql"),
+            statement_type,
+            table:     String::from(table),
+            schema:    None,
+            begin:     0,
+            end:       1,
+            bytes:     ByteSpan::default(),
+            batch:     0,
+            text:      String::from(text),
+            keyword_span: None,
+            table_spans:  Vec::new(),
+            nested:    false,
+            partial:   false,
+            dynamic:   false,
+            captures:  Default::default(),
+            object:    None,
+            module:    None,
+            header:    None,
+            queries:   vec![String::from(query)],
+            tables:    Vec::new(),
+            note:      None,
+            commits:   Vec::new(),
+        };
+        let statements = vec![
+            statement(StatementType::Delete, "t_audit_log", "DELETE FROM t_audit_log", "audit-log-delete"),
+            statement(StatementType::Select, "t_customer_pii", "select * from t_customer_pii", "pii-select-star"),
+            statement(StatementType::Select, "t_customer_pii", "SELECT name FROM t_customer_pii", "pii-select-star"),
+        ];
+        let violations = rules.violations(&statements);
+        assert_eq!(violations.iter().map(|v| (v.statement.text.as_str(), v.rule.severity)).collect::<Vec<_>>(),
+            vec![("DELETE FROM t_audit_log", Severity::Error), ("select * from t_customer_pii", Severity::Warning)]);
+    }
+}
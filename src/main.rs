@@ -24,514 +24,1857 @@
  *     `gresql -s u:t_pick_detail -s d:t_pick_detail`
  *     will search for sprocs that have both updates AND deletes to t_pick_detail
  */
-#![feature(buf_read_has_data_left)]
-#![feature(hash_drain_filter)]
-
 extern crate exitcode;
 
-use clap::Parser;
-use glob::glob;
-use regex::Regex;
-use std::collections::HashSet;
+mod clipboard;
+mod color;
+mod error;
+mod git;
+mod sink;
+mod table;
+
+use clap::{ ArgGroup, Parser, Subcommand, ValueEnum };
+use clipboard::{ ClipboardFile, CLIPBOARD_PATH };
+use color::ColorChoice;
+use encoding_rs::Encoding;
+use error::{ Context, Error, Result };
+use git::GitRange;
+use gresql::ast::Engine;
+use gresql::catalog::{ self, Catalog, Declarations };
+use gresql::checkpoint::Checkpoint;
+use gresql::config::{ self, Config };
+use gresql::dialect::Dialect;
+use gresql::document::Document;
+use gresql::handler;
+use gresql::header;
+use gresql::index::{ self, Index, IndexUpdate };
+use gresql::lint::{ self, Rules };
+use gresql::manifest::Manifest;
+use gresql::objects::{ self, ObjectKind, ObjectPattern };
+use gresql::policy::{ self, Policy, Rule };
+use gresql::pragma::{ is_ignored, parse_pragmas };
+use gresql::report::{ Census, CoOccurrence, Crud, DeployOrder, GroupBy, Groups, Profile, ReportKind };
+use gresql::results::{ self, Comparison, Results, SetOperation };
+use gresql::schema::Schema;
+use gresql::{
+    annotate_file_statements, author_matches, count_lines, exclude_globs, file_is_match, file_matches_queries,
+    find_file_statements, find_statements, find_text_statements, get_file_paths, invalid_globs, invalid_table_patterns, walk_file_paths, overlapping_queries, parse_search_queries,
+    prefilter_rejection, read_file_list, QueryMatch, ScanOpts, SearchQuery, Statement, StatementType,
+};
+use std::collections::{ BTreeMap, HashSet };
 use std::ffi::OsString;
 use std::fs::File;
-use std::io::{ BufRead, BufReader, Write };
+use std::io::Write;
 use std::path::{ Path, PathBuf };
-use grep_regex::RegexMatcher;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::sync::mpsc;
+use std::time::{ Duration, Instant };
 use grep_searcher::Searcher;
-use grep_searcher::sinks::Bytes;
+use ignore::overrides::Override;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use sink::{ parse_sink, Sink };
+use table::Table;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Cli {
-    #[arg(short = 's', long = "search", required = true, help = "Search query")]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(short = 's', long = "search", required_unless_present_any = ["report", "group_by", "list_files", "estimate", "print_config"], help = "Search query")]
     search_queries: Vec<String>,
     #[arg(short = 'd', long = "delimiter", default_value_t=',', help = "Result field-delimiter")]
     delimiter: char,
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Delimited, help = "Result format")]
+    format: OutputFormat,
+    #[arg(short = 'o', long = "output", value_name = "FORMAT[=PATH]", value_parser = parse_sink,
+        conflicts_with_all = ["format", "report", "only_file_paths"],
+        help = "Write the results in FORMAT to PATH (or stdout), may be given more than once")]
+    outputs: Vec<Sink>,
+    #[arg(long = "max-column-width", value_name = "N", default_value_t = 40, help = "Maximum width of the columns of --format table")]
+    max_column_width: usize,
+    #[arg(long = "color", value_name = "WHEN", value_enum, default_value_t = ColorChoice::Auto, help = "Highlight the matches printed to a terminal")]
+    color: ColorChoice,
+    #[arg(long = "sign", default_value_t = false, help = "Add a SHA-256 manifest of the scan to JSON output")]
+    sign: bool,
+    #[arg(long = "annotate", value_name = "FILE", help = "Give the matches the notes of their fingerprints in FILE")]
+    annotate: Option<PathBuf>,
+    #[arg(long = "report", value_enum, help = "Print a report instead of the matching statements")]
+    report: Option<ReportKind>,
+    #[arg(long = "top", value_name = "N", requires = "report", help = "Only print the N tables with the most statements of a crud report")]
+    top: Option<usize>,
+    #[arg(long = "drill-down", value_name = "TABLE", requires = "report", help = "Also list every statement on TABLE in a crud report")]
+    drill_down: Option<String>,
+    #[arg(long = "group-by", value_enum, conflicts_with = "report", help = "Print the number of matches of each module, object, table, type or file")]
+    group_by: Option<GroupBy>,
+    #[arg(long = "dialect", value_enum, default_value_t = Dialect::Tsql, help = "SQL dialect of the files")]
+    dialect: Dialect,
+    #[arg(long = "encoding", value_name = "ENCODING", value_parser = parse_encoding, global = true,
+        help = "Encoding of the files without a BOM, e.g. utf-16le or windows-1252 [default: utf-8]")]
+    encoding: Option<&'static Encoding>,
+    #[arg(long = "engine", value_enum, default_value_t = Engine::Regex, help = "How the tables of a statement are found")]
+    engine: Engine,
+    #[arg(long = "match", value_enum, default_value_t = QueryMatch::All, help = "Whether a file must match all of the search queries or any of them")]
+    query_match: QueryMatch,
+    #[arg(long = "fail-if-more-than", value_name = "[LABEL=]N", value_parser = parse_budget, help = "Exit with an error if there are more than N matches (of the query labeled LABEL)")]
+    budgets: Vec<(Option<String>, usize)>,
+    #[arg(long = "shard", value_name = "I/N", value_parser = parse_shard, help = "Only scan the I-th of N shards of the file list")]
+    shard: Option<(u64, u64)>,
     // boolean flags
     #[arg(short = 'p', long = "path-only", default_value_t = false, help = "Only print the paths of matching files")]
     only_file_paths: bool,
+    #[arg(short = 'L', long = "files-without-match", default_value_t = false,
+        conflicts_with_all = ["only_file_paths", "report", "group_by", "outputs", "format"],
+        help = "Only print the paths of the scanned files without any match")]
+    files_without_match: bool,
     #[arg(short = 'T', long = "no-statement-text", default_value_t = false, help = "Don't print statement text")]
     hide_statement: bool,
+    #[arg(long = "header-columns", default_value_t = false, help = "Print the author and description from object headers")]
+    header_columns: bool,
+    #[arg(long = "object-column", default_value_t = false, help = "Print the procedure, function, trigger or view that contains each statement")]
+    object_column: bool,
+    #[arg(long = "author", value_name = "NAME", help = "Only match statements in objects whose header author contains NAME")]
+    author: Option<String>,
+    #[arg(long = "only", value_enum, value_delimiter = ',', value_name = "KINDS", help = "Only match statements inside these kinds of objects")]
+    only: Vec<ObjectKind>,
+    #[arg(long = "object-pattern", value_name = "PATTERN", help = "Only match statements in objects whose name matches PATTERN (e.g. usp_wave%)")]
+    object_patterns: Vec<ObjectPattern>,
+    #[arg(long = "not-table", value_name = "TABLE", help = "Don't match statements of TABLE (or a /regex/ or glob), may be given more than once")]
+    not_tables: Vec<String>,
+    #[arg(long = "filter-regex", value_name = "REGEX", value_parser = Regex::new, help = "Only match statements whose text matches REGEX (e.g. 'status = 9[0-9]')")]
+    filter_regex: Option<Regex>,
+    #[arg(long = "first-match", default_value_t = false, help = "Only find the first matching statement per file and query")]
+    first_match: bool,
+    #[arg(long = "include-temp", default_value_t = false, overrides_with = "exclude_temp", help = "Find #temp tables and @table variables")]
+    include_temp: bool,
+    #[arg(long = "exclude-temp", default_value_t = false, overrides_with = "include_temp", help = "Don't find #temp tables and @table variables unless a query names them [default]")]
+    exclude_temp: bool,
+    #[arg(long = "no-ignore-pragmas", default_value_t = false, help = "Report the matches silenced by gresql:ignore comments")]
+    no_ignore_pragmas: bool,
+    #[arg(long = "dynamic-sql", default_value_t = false, help = "Rebuild the SQL that EXEC(@sql) and sp_executesql run, and match its statements as dynamic")]
+    dynamic_sql: bool,
+    #[arg(long = "no-summary", default_value_t = false, help = "Don't print the summary of the scan to stderr")]
+    no_summary: bool,
+    #[arg(long = "exclude", value_name = "GLOB", help = "Don't scan the files (or directories) matching GLOB, may be given more than once")]
+    excludes: Vec<String>,
+    #[arg(long = "list-files", default_value_t = false, help = "Only print the files that would be scanned")]
+    list_files: bool,
+    #[arg(long = "estimate", default_value_t = false, help = "Only estimate how long the scan would take")]
+    estimate: bool,
+    #[arg(long = "print-config", value_name = "FORMAT", value_enum, num_args = 0..=1, default_missing_value = "toml",
+        help = "Only print the settings of the scan, with those of the config file, as toml (the default) or json")]
+    print_config: Option<ConfigFormat>,
+    #[arg(long = "stream", default_value_t = false,
+        conflicts_with_all = ["report", "group_by", "files_without_match", "outputs", "sign", "budgets", "checkpoint", "resume", "git_range", "clipboard", "list_files", "estimate"],
+        help = "Print the matches of the files as they're walked and scanned, in the order they're found, naming tables as declared in the files scanned so far (--format ndjson or vimgrep, or -p)")]
+    stream: bool,
+    #[arg(long = "sorted", default_value_t = false, requires = "stream", help = "Print the matches of a stream in the order the files were walked")]
+    sorted: bool,
+    #[arg(long = "why", value_name = "FILE", help = "Explain why the statements of FILE do or don't match")]
+    why: Option<PathBuf>,
+    #[arg(short = 'j', long = "threads", value_name = "N", default_value_t = 0, help = "Number of files to scan at once [default: one per CPU]")]
+    threads: usize,
     #[arg(short = 'v', long = "verbose", default_value_t = false, help = "Verbose output")]
     verbose: bool,
+    #[arg(short = 'c', long = "config", value_name = "FILE", help = "Config file [default: .gresql.toml]")]
+    config: Option<PathBuf>,
+    // checkpoints
+    #[arg(long = "checkpoint", value_name = "FILE", help = "Periodically save scan progress to FILE")]
+    checkpoint: Option<PathBuf>,
+    #[arg(long = "checkpoint-interval", value_name = "SECONDS", default_value_t = 60, help = "Seconds between checkpoint saves")]
+    checkpoint_interval: u64,
+    #[arg(long = "resume", value_name = "FILE", help = "Resume a scan from a checkpoint FILE")]
+    resume: Option<PathBuf>,
+    #[arg(long = "clipboard", default_value_t = false, conflicts_with = "shard", help = "Also process the SQL on the clipboard")]
+    clipboard: bool,
+    #[arg(long = "files-from", value_name = "FILE", help = "Also process the files listed in FILE (- for stdin), one per line or NUL-separated")]
+    files_from: Option<PathBuf>,
+    #[arg(long = "git-range", value_name = "FROM..TO", conflicts_with_all = ["clipboard", "checkpoint", "resume", "files_without_match", "sign"],
+        help = "Only process the files changed in FROM..TO, as they were at TO, giving each match the commits of the range that last changed it")]
+    git_range: Option<String>,
     // remaining arguments are file-paths
-    #[arg(required = false, default_values_os_t = vec![OsString::from(".")], help = "File(s) to process")]
+    #[arg(required = false, help = "File(s) to process, - for a list of files on stdin [default: .]")]
     file_paths: Vec<OsString>,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Merge the JSON results of several scans (e.g. shards) into one
+    Merge {
+        #[arg(required = true, help = "JSON result files")]
+        files: Vec<PathBuf>,
+    },
+    /// Combine the JSON results of scans by the files that they matched
+    #[command(group(ArgGroup::new("operation").required(true).args(["intersect", "union", "subtract"])))]
+    Combine {
+        #[arg(long = "intersect", help = "Files matched by all of the results")]
+        intersect: bool,
+        #[arg(long = "union", help = "Files matched by any of the results")]
+        union: bool,
+        #[arg(long = "subtract", help = "Files matched by the first results but none of the others")]
+        subtract: bool,
+        #[arg(num_args = 2.., required = true, help = "JSON result files")]
+        files: Vec<PathBuf>,
+    },
+    /// Compare the JSON results of two runs, listing the matches added and removed since the first
+    Compare {
+        #[arg(long = "format", value_enum, default_value_t = CompareFormat::Text, help = "Comparison format")]
+        format: CompareFormat,
+        #[arg(help = "JSON results of the earlier run")]
+        before: PathBuf,
+        #[arg(help = "JSON results of the later run")]
+        after: PathBuf,
+    },
+    /// Verify signed JSON results (or reports) against their manifest
+    Verify {
+        #[arg(help = "Signed JSON file")]
+        file: PathBuf,
+    },
+    /// Enforce which objects are allowed to write protected tables
+    Policy {
+        #[command(subcommand)]
+        command: PolicyCommand,
+    },
+    /// Report the statements forbidden by the rules of a rules file
+    Lint {
+        #[arg(long = "rules", value_name = "FILE", default_value = lint::DEFAULT_RULES_FILE, help = "Rules file")]
+        rules: PathBuf,
+        #[arg(long = "dialect", value_enum, default_value_t = Dialect::Tsql, help = "SQL dialect of the files")]
+        dialect: Dialect,
+        #[arg(long = "format", value_enum, default_value_t = ViolationFormat::Text, help = "Violation format")]
+        format: ViolationFormat,
+        #[arg(default_values_os_t = vec![OsString::from(".")], help = "File(s) to check")]
+        file_paths: Vec<OsString>,
+    },
+    /// Report the statements that use tables or columns removed between two schema scripts
+    SchemaDiff {
+        #[arg(help = "Schema script before the deployment")]
+        old: PathBuf,
+        #[arg(help = "Schema script after the deployment")]
+        new: PathBuf,
+        #[arg(long = "used-by", value_name = "PATH", num_args = 1.., default_values_os_t = vec![OsString::from(".")], help = "File(s) to check for uses")]
+        used_by: Vec<OsString>,
+        #[arg(long = "dialect", value_enum, default_value_t = Dialect::Tsql, help = "SQL dialect of the files")]
+        dialect: Dialect,
+    },
+    /// Index every statement of the files, for `gresql query` to search
+    Index {
+        #[arg(long = "index", value_name = "FILE", default_value = index::DEFAULT_INDEX_FILE, help = "Index file")]
+        index: PathBuf,
+        #[arg(long = "dialect", value_enum, default_value_t = Dialect::Tsql, help = "SQL dialect of the files")]
+        dialect: Dialect,
+        #[arg(long = "exclude", value_name = "GLOB", help = "Don't index the files (or directories) matching GLOB, may be given more than once")]
+        excludes: Vec<String>,
+        #[arg(default_values_os_t = vec![OsString::from(".")], help = "File(s) to index")]
+        file_paths: Vec<OsString>,
+    },
+    /// Search the statements of an index, parsing again only the files that changed
+    Query {
+        #[arg(short = 's', long = "search", required = true, help = "Search query")]
+        search_queries: Vec<String>,
+        #[arg(long = "index", value_name = "FILE", default_value = index::DEFAULT_INDEX_FILE, help = "Index file")]
+        index: PathBuf,
+        #[arg(long = "match", value_enum, default_value_t = QueryMatch::All, help = "Whether a file must match all of the search queries or any of them")]
+        query_match: QueryMatch,
+        #[arg(long = "include-temp", default_value_t = false, help = "Find #temp tables and @table variables")]
+        include_temp: bool,
+        #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Delimited, help = "Result format")]
+        format: OutputFormat,
+        #[arg(short = 'd', long = "delimiter", default_value_t=',', help = "Result field-delimiter")]
+        delimiter: char,
+        #[arg(short = 'p', long = "path-only", default_value_t = false, help = "Only print the paths of matching files")]
+        only_file_paths: bool,
+        #[arg(short = 'T', long = "no-statement-text", default_value_t = false, help = "Don't print statement text")]
+        hide_statement: bool,
+        #[arg(long = "color", value_name = "WHEN", value_enum, default_value_t = ColorChoice::Auto, help = "Highlight the matches printed to a terminal")]
+        color: ColorChoice,
+        #[arg(long = "cache-stats", default_value_t = false, help = "Print how many files of the index were parsed again, and the time saved")]
+        cache_stats: bool,
+        #[arg(long = "latency-report", default_value_t = false, conflicts_with_all = ["only_file_paths", "hide_statement"],
+            help = "Only time the searches of each query against the index, printing their p50 and p95 latency")]
+        latency_report: bool,
+        #[arg(long = "runs", value_name = "N", default_value_t = 100, requires = "latency_report", value_parser = clap::value_parser!(u32).range(1..),
+            help = "Number of times each query is searched for the latency report")]
+        runs: u32,
+    },
+    /// Count the objects reading and writing each table of a list, across the indexed files
+    Census {
+        #[arg(long = "tables-from", value_name = "FILE", help = "Read the tables from FILE, one per line")]
+        tables_from: PathBuf,
+        #[arg(long = "index", value_name = "FILE", default_values_os_t = vec![PathBuf::from(index::DEFAULT_INDEX_FILE)], help = "Index file, may be given more than once")]
+        indexes: Vec<PathBuf>,
+        #[arg(long = "format", value_enum, default_value_t = CensusFormat::Text, help = "Census format")]
+        format: CensusFormat,
+        #[arg(long = "cache-stats", default_value_t = false, help = "Print how many files of each index were parsed again, and the time saved")]
+        cache_stats: bool,
+    },
+    /// Print the n-th location of a jump list (`--format jumplist`) as path:line:column
+    Jump {
+        #[arg(help = "Number of the location, from 1")]
+        n: usize,
+        #[arg(long = "jumplist", value_name = "FILE", default_value = sink::DEFAULT_JUMPLIST_FILE, help = "Jump list file")]
+        jumplist: PathBuf,
+    },
+    /// Print the schema of one of the JSON documents that gresql writes
+    Schema {
+        #[arg(long = "format", value_enum, default_value_t = SchemaFormat::JsonSchema, help = "Schema format")]
+        format: SchemaFormat,
+        #[arg(value_enum, default_value_t = Document::Results, help = "Document")]
+        document: Document,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum CompareFormat {
+    /// the matches added (+) and removed (-), and the number of each
+    Text,
+    /// the added, removed and unchanged matches
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum CensusFormat {
+    /// the readers and writers of each table, and the number of unused tables
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum SchemaFormat {
+    JsonSchema,
+}
+
+#[derive(Subcommand)]
+enum PolicyCommand {
+    /// Report the writes to protected tables by objects that aren't allowed to write them
+    Check {
+        #[arg(long = "policy", value_name = "FILE", default_value = policy::DEFAULT_POLICY_FILE, help = "Policy file")]
+        policy: PathBuf,
+        #[arg(long = "dialect", value_enum, default_value_t = Dialect::Tsql, help = "SQL dialect of the files")]
+        dialect: Dialect,
+        #[arg(long = "format", value_enum, default_value_t = ViolationFormat::Text, help = "Violation format")]
+        format: ViolationFormat,
+        #[arg(default_values_os_t = vec![OsString::from(".")], help = "File(s) to check")]
+        file_paths: Vec<OsString>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ViolationFormat {
+    /// `path:line: message` and the hint of each violation
+    Text,
+    /// a SARIF 2.1.0 log with a result per violation, e.g. for code scanning
+    Sarif,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Delimited,
+    Json,
+    Table,
+    /// a JSON statement per line
+    Ndjson,
+    Sarif,
+    /// `path:line:column: message` lines, like `grep -n`
+    Vimgrep,
+    /// `path<TAB>line<TAB>column<TAB>summary` lines, for `gresql jump`
+    Jumplist,
+}
+
 struct PrintOpts {
     only_file_paths: bool,
     hide_statement:  bool,
     delimiter:       char,
+    sinks:           Vec<Sink>,
+    capture_names:   Vec<String>,
+    header_columns:  bool,
+    object_column:   bool,
+    /// whether to print the module of each statement, when the config
+    /// defines modules
+    module_column:   bool,
+    /// whether to print the queries that matched each statement, when there
+    /// is more than one
+    query_column:    bool,
+    max_column_width: usize,
+    /// the scanned files, if JSON results are to be signed
+    signed_inputs:   Option<Vec<PathBuf>>,
+    /// whether the results written to stdout are colored
+    color:           bool,
+    /// whether to print the fingerprint and the note of each match, when
+    /// matches are annotated
+    note_columns:    bool,
+    /// whether to print the commits that last changed each match
+    /// (`--git-range`)
+    commit_column:   bool,
+    /// the tables declared in the scanned files, kept with JSON results
+    declarations:    Declarations,
+}
+
+fn parse_budget(s: &str) -> Result<(Option<String>, usize), String> {
+    // budgets are given as `N` for all matches, or `LABEL=N` for the matches
+    // of the query with that label
+    let (label, n) = match s.rsplit_once('=') {
+        Some((label, n)) => (Some(label.to_string()), n),
+        None => (None, s),
+    };
+    let n: usize = n.parse().map_err(|_| format!("invalid number of matches: {}", n))?;
+    Ok((label, n))
 }
 
-// statement types ============================================================
-#[derive(Debug, PartialEq)]
-pub enum StatementType {
-    Select,
-    Insert,
-    Update,
-    Delete,
-    Merge,
+fn parse_encoding(s: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(s.as_bytes()).ok_or_else(|| format!("unknown encoding: {}", s))
 }
 
-impl StatementType {
-    fn find_table(&self, s: &str) -> Option<String> {
-        let has_from: bool = s.to_string().to_lowercase().contains("from");
-        let re = match self {
-            StatementType::Insert => {
-                Regex::new(r"\b(?i:into)\s+([@#[:alnum:]_]+)").expect("regex didn't compile")
-            },
-            StatementType::Merge => {
-                Regex::new(r"\b(?i:merge)\s+([@#[:alnum:]_]+)").expect("regex didn't compile")
-            },
-            StatementType::Delete if !has_from => {
-                Regex::new(r"\b(?i:delete)\s+([@#[:alnum:]_]+)").expect("regex didn't compile")
-            },
-            StatementType::Select | StatementType::Delete => {
-                Regex::new(r"\b(?i:from)\s+([@#[:alnum:]_]+)").expect("regex didn't compile")
-            },
-            StatementType::Update if !has_from => {
-                Regex::new(r"\b(?i:update)\s+([@#[:alnum:]_]+)").expect("regex didn't compile")
-            },
-            StatementType::Update => {
-                // There are two possible forms here:
-                //   UPDATE <a> ... FROM <table> <a> WHERE ...
-                //   UPDATE <b> ... FROM <table> <a> INNER JOIN <table_2> <b> WHERE ...
-                // We need to find the target of the update statement based on
-                // the alias
-                let re = Regex::new(r"\b(?i:update)\s+([@#[:alnum:]_]+)").expect("regex didn't compile");
-                let target = re.captures(&s)?.get(1)?.as_str().to_string();
-                let exp = format!(r"\b(?i:update)\s+{}.*?\b(?i:from|join)\s+([@#[:alnum:]_]+)\s+{}", &target, &target);
-                Regex::new(&exp).expect("regex didn't compile")
-            }
-        };
-        Some(re.captures(&s)?.get(1)?.as_str().to_string())
-    }
-}
-
-// implement try_from &char for StatementType
-impl TryFrom<char> for StatementType {
-    type Error = ();
-    fn try_from(c: char) -> Result<Self, Self::Error> {
-        match c {
-            's' => Ok(StatementType::Select),
-            'i' => Ok(StatementType::Insert),
-            'u' => Ok(StatementType::Update),
-            'd' => Ok(StatementType::Delete),
-            'm' => Ok(StatementType::Merge),
-            _ => Err(()),
-        }
-    }
-}
-
-// implement try_from String for StatementType
-impl TryFrom<String> for StatementType {
-    type Error = ();
-    fn try_from(s: String) -> Result<Self, Self::Error> {
-        match s.as_str() {
-            "select" => Ok(StatementType::Select),
-            "insert" => Ok(StatementType::Insert),
-            "update" => Ok(StatementType::Update),
-            "delete" => Ok(StatementType::Delete),
-            "merge"  => Ok(StatementType::Merge),
-            _ => Err(()),
-        }
-    }
-}
-
-// display for StatementType
-impl std::fmt::Display for StatementType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            StatementType::Select => write!(f, "SELECT"),
-            StatementType::Insert => write!(f, "INSERT"),
-            StatementType::Update => write!(f, "UPDATE"),
-            StatementType::Delete => write!(f, "DELETE"),
-            StatementType::Merge  => write!(f, "MERGE"),
-        }
-    }
-}
-
-fn parse_statement_types(statement_types: &str) -> Vec<StatementType> {
-    // get set of unique characters in statement_types
-    let char_set: HashSet<char> = statement_types.chars().collect();
-    let mut statement_types: Vec<StatementType> = Vec::new();
-    if char_set.contains(&'*') {
-        // return all statement types except select
-        return vec![
-            StatementType::Insert,
-            StatementType::Update,
-            StatementType::Delete,
-            StatementType::Merge,
-        ];
-    }
-    char_set.into_iter()
-        .filter_map(|c| StatementType::try_from(c).ok())
-        .for_each(|stmt_type| statement_types.push(stmt_type));
-    statement_types
-}
-// ============================================================================
-
-// search queries =============================================================
-#[derive(Debug)]
-struct SearchQuery {
-    statement_types: Vec<StatementType>,
-    tables: Vec<String>,
-}
-
-impl SearchQuery {
-    fn statement_pattern(&self) -> String {
-        let mut pattern = String::new();
-        pattern.push_str(r"\b((?i)");
-        pattern.push_str(
-            &self.statement_types.iter()
-                .map(|st| st.to_string())
-                .collect::<Vec<String>>()
-                .join("|"));
-        pattern.push_str(r")\b");
-        pattern
-    }
-    fn table_pattern(&self) -> String {
-        let mut pattern = String::new();
-        pattern.push_str(r"(");
-        pattern.push_str(&self.tables.join("|"));
-        pattern.push_str(r")\b");
-        pattern
-    }
-}
-
-fn parse_search_queries(strings: Vec<String>) -> Vec<SearchQuery> {
-    strings.iter()
-        .map(|s| { s.split(':').collect() })
-        .filter_map(|ps: Vec<&str>| {
-            match ps.len() {
-                1 => Some(SearchQuery {
-                    statement_types: parse_statement_types("*"),
-                    tables: ps[0].split(",").map(String::from).collect(),
-                }),
-                2 => Some(SearchQuery {
-                    statement_types: parse_statement_types(ps[0]),
-                    tables: ps[1].split(",").map(String::from).collect(),
-                }),
-                _ => None
-            }})
+fn parse_shard(s: &str) -> Result<(u64, u64), String> {
+    // shards are given as `i/n`, where i is 1-based
+    let (i, n) = s.split_once('/').ok_or("expected a shard of the form I/N")?;
+    let i: u64 = i.parse().map_err(|_| format!("invalid shard number: {}", i))?;
+    let n: u64 = n.parse().map_err(|_| format!("invalid shard count: {}", n))?;
+    if n == 0 || i == 0 || i > n {
+        return Err(format!("shard must be between 1/{} and {}/{}", n, n, n));
+    }
+    Ok((i, n))
+}
+
+fn in_shard(path: &Path, (i, n): (u64, u64)) -> bool {
+    // partition files by a hash of their path (FNV-1a) rather than by their
+    // position in the file list, so that adding or removing a file doesn't
+    // move every other file into a different shard
+    let hash = path.to_string_lossy().bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    hash % n == i - 1
+}
+
+/// the scanned files in which no statement matched (after every filter of the
+/// scan, so that a file whose matches were all filtered out is one of them),
+/// leaving out the files that couldn't be read
+fn files_without_match<'a>(file_paths: &'a [PathBuf], statements: &[Statement], unreadable_files: &[&PathBuf]) -> Vec<&'a PathBuf> {
+    let matched_files: HashSet<&PathBuf> = statements.iter().map(|s| &s.file_path).collect();
+    file_paths.iter()
+        .filter(|path| !matched_files.contains(path) && !unreadable_files.contains(path))
         .collect()
 }
-// ============================================================================
 
-// file paths =================================================================
-pub enum PathType {
-    File,
-    Directory,
-    Symlink,
+/// whether the object of a statement matches any of the patterns (statements
+/// outside of any object never do)
+fn object_matches(object: Option<&str>, patterns: &[ObjectPattern]) -> bool {
+    object.is_some_and(|object| patterns.iter().any(|pattern| pattern.is_match(object)))
 }
 
-fn get_path_type(path: &Path) -> Option<PathType> {
-    if !path.exists() { return None; }
-    match path.is_file() {
-        true  => Some(PathType::File),
-        false => match path.is_dir() {
-            true  => Some(PathType::Directory),
-            false => Some(PathType::Symlink),
-        },
+fn explain_file(path: &Path, search_queries: &[SearchQuery], cli: &Cli, opts: &ScanOpts) -> Result<()> {
+    // run a single file through each step of a scan, printing why its
+    // statements did or didn't match
+    let text = handler::read_to_string(path).context(|| format!("Unable to read {}", path.display()))?;
+    let pragmas = if opts.ignore_pragmas { parse_pragmas(&text) } else { Vec::new() };
+    let objects = objects::parse_objects(&text);
+    let headers = header::parse_headers(&text);
+    let mut searcher = Searcher::new();
+    for (i, query) in search_queries.iter().enumerate() {
+        println!("query {}:", cli.search_queries.get(i).map(String::as_str).unwrap_or("*"));
+        if let Some(pattern) = prefilter_rejection(&mut searcher, path, query) {
+            println!("rejected by the prefilter, nothing in the file matches {}", pattern);
+            continue;
+        }
+        let statements = find_statements(path, query, opts).unwrap_or_default();
+        for statement in &statements {
+            let line = statement.begin + 1;
+            if is_ignored(&pragmas, statement) {
+                println!("line {}: {} of {} is ignored by a gresql:ignore comment", line, statement.statement_type, statement.table);
+            }
+            let kind = objects::kind_at(&objects, statement.begin);
+            if !cli.only.is_empty() && !cli.only.contains(&kind) {
+                println!("line {}: {} of {} is in {:?}, not one of --only", line, statement.statement_type, statement.table, kind);
+            }
+            let object = objects::object_at(&objects, statement.begin).map(|object| object.name.as_str());
+            if !cli.object_patterns.is_empty() && !object_matches(object, &cli.object_patterns) {
+                println!("line {}: {} of {} is in {}, which doesn't match --object-pattern", line, statement.statement_type, statement.table,
+                    object.unwrap_or("no object"));
+            }
+            if cli.filter_regex.as_ref().is_some_and(|re| !re.is_match(&statement.text)) {
+                println!("line {}: {} of {} doesn't match --filter-regex", line, statement.statement_type, statement.table);
+            }
+            if let Some(author) = &cli.author {
+                let statement = Statement { header: header::header_at(&headers, statement.begin).cloned(), ..statement.clone() };
+                if !author_matches(&statement, author) {
+                    println!("line {}: {} of {} isn't in an object by {}", line, statement.statement_type, statement.table, author);
+                }
+            }
+        }
+        if statements.is_empty() {
+            match opts.query_match {
+                QueryMatch::All => println!("no statement matched the query, so the file doesn't match"),
+                QueryMatch::Any => println!("no statement matched the query"),
+            }
+        }
     }
+    Ok(())
 }
 
-fn get_file_paths(strings: &Vec<OsString>) -> HashSet<PathBuf> {
-    // return a vector of resolved path buffers from a vector of strings, of
-    // which each string could be a file, a symlink, a directory, or a glob
-    // pattern
-    let mut paths: HashSet<PathBuf> = HashSet::new();
-    for s in strings {
-        let path: &Path = Path::new(s);
-        if let Some(path_type) = get_path_type(path) { // valid path
-            match path_type {
-                PathType::File => { paths.insert(PathBuf::from(s)); },
-                PathType::Symlink => {
-                    if let Ok(link_path) = path.read_link() {
-                        paths.insert(PathBuf::from(link_path.to_str().unwrap()));
-                    }
-                }
-                PathType::Directory => {
-                    // get all files in directory
-                    let mut dir_path = PathBuf::from(s);
-                    dir_path.push("**/*.sql");
-                    for entry in glob(dir_path.to_str().unwrap()).unwrap() {
-                        if let Ok(entry) = entry {
-                            paths.insert(entry);
-                        }
-                    }
-                }
+fn print_statements(opts: PrintOpts, queries: &[String], statements: Vec<Statement>) -> Result<()> {
+    for sink in &opts.sinks {
+        sink.open()
+            .and_then(|mut w| {
+                // files are never colored, whatever is asked for
+                let color = opts.color && sink.path.is_none();
+                write_statements(&mut w, &opts, sink.format, color, queries, &statements).and_then(|_| w.flush())
+            })
+            .context(|| format!("Unable to write results to {}", sink))?;
+    }
+    Ok(())
+}
+
+fn write_statements<W: Write>(mut w: W, opts: &PrintOpts, format: OutputFormat, color: bool, queries: &[String], statements: &[Statement]) -> Result<(), std::io::Error> {
+    let del: char = opts.delimiter;
+    match format {
+        OutputFormat::Json => {
+            let mut results = Results::new(queries, statements.to_vec());
+            results.declarations = opts.declarations.clone();
+            // the results are signed as they're written, so a file that
+            // can't be hashed fails the write
+            if let Some(file_paths) = &opts.signed_inputs {
+                results.sign(file_paths)?;
+            }
+            return results.write(w);
+        },
+        OutputFormat::Ndjson => {
+            for s in statements {
+                serde_json::to_writer(&mut w, s)?;
+                writeln!(w)?;
             }
-        } else if let Some(_) = s.to_str().unwrap().find('*') { // glob pattern
-            for entry in glob(s.to_str().unwrap()).unwrap() {
-                if let Ok(entry) = entry {
-                    paths.insert(entry);
+            return Ok(());
+        },
+        OutputFormat::Sarif => {
+            serde_json::to_writer_pretty(&mut w, &sink::sarif(statements))?;
+            return writeln!(w);
+        },
+        OutputFormat::Vimgrep | OutputFormat::Jumplist => {
+            // the file of the previous statement, as statements are grouped
+            // by file
+            let mut file: Option<(&Path, String)> = None;
+            for s in statements {
+                if file.as_ref().is_none_or(|(path, _)| *path != s.file_path) {
+                    file = Some((&s.file_path, handler::read_to_string(&s.file_path).unwrap_or_default()));
+                }
+                let file_text = file.as_ref().map(|(_, text)| text.as_str()).unwrap_or_default();
+                match format {
+                    OutputFormat::Jumplist => writeln!(w, "{}", sink::jumplist_line(s, file_text))?,
+                    _ => writeln!(w, "{}", sink::vimgrep_line(s, file_text, opts.hide_statement, color))?,
                 }
             }
-        } else {
-            eprintln!("File not found: {}", s.to_str().unwrap());
+            return Ok(());
+        },
+        // an empty table would only be its headers
+        OutputFormat::Table if statements.is_empty() => return Ok(()),
+        OutputFormat::Delimited | OutputFormat::Table => {},
+    }
+
+    let mut headers: Vec<String> = ["path", "begin", "end", "type", "table"].map(String::from).to_vec();
+    if opts.query_column {
+        headers.push(String::from("queries"));
+    }
+    if opts.object_column {
+        headers.push(String::from("object"));
+    }
+    if opts.module_column {
+        headers.push(String::from("module"));
+    }
+    headers.extend(opts.capture_names.iter().cloned());
+    if opts.header_columns {
+        headers.extend(["author", "description"].map(String::from));
+    }
+    if opts.note_columns {
+        headers.extend(["fingerprint", "note"].map(String::from));
+    }
+    if opts.commit_column {
+        headers.push(String::from("commits"));
+    }
+    let fingerprints = match opts.note_columns {
+        true  => results::fingerprints(statements),
+        false => Vec::new(),
+    };
+    if !opts.hide_statement {
+        headers.push(String::from("text"));
+    }
+    let mut table = Table::new(headers);
+
+    for (i, s) in statements.iter().enumerate() {
+        // a table's columns are aligned by the width of their text, so
+        // only the delimited results are colored
+        let color = color && format == OutputFormat::Delimited;
+        let mut fields: Vec<String> = match color {
+            true  => vec![
+                color::path(&s.file_path.display().to_string()),
+                color::line(&s.begin.to_string()),
+                color::line(&s.end.to_string()),
+            ],
+            false => vec![s.file_path.display().to_string(), s.begin.to_string(), s.end.to_string()],
+        };
+        fields.extend([s.type_with_flags(), s.table.clone()]);
+        if opts.query_column {
+            fields.push(s.queries.join(" "));
+        }
+        if opts.object_column {
+            fields.push(s.object.clone().unwrap_or_default());
+        }
+        if opts.module_column {
+            fields.push(s.module.clone().unwrap_or_default());
+        }
+        fields.extend(opts.capture_names.iter()
+            .map(|name| s.captures.get(name).cloned().unwrap_or_default()));
+        if opts.header_columns {
+            let header = s.header.clone().unwrap_or_default();
+            fields.push(header.author.unwrap_or_default());
+            fields.push(header.description.unwrap_or_default());
+        }
+        if opts.note_columns {
+            fields.push(fingerprints[i].clone());
+            fields.push(s.note.clone().unwrap_or_default());
+        }
+        if opts.commit_column {
+            fields.push(s.commits.join(" "));
+        }
+        if !opts.hide_statement {
+            fields.push(match color {
+                true  => color::highlight(&s.text, s.keyword_span, &s.table_spans),
+                false => s.text.clone(),
+            });
+        }
+        match format {
+            OutputFormat::Table => table.push(fields),
+            _ => writeln!(w, "{}", fields.join(&del.to_string()))?,
         }
     }
-    paths
+
+    if format == OutputFormat::Table {
+        // the table fills the terminal, or a reasonable width when the
+        // output isn't a terminal
+        let width = terminal_size::terminal_size()
+            .map(|(terminal_size::Width(width), _)| width as usize)
+            .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+            .unwrap_or(120);
+        table.write(w, width, opts.max_column_width)?;
+    }
+    Ok(())
+}
+
+fn load_config(path: Option<&Path>) -> Result<Config> {
+    match config_path(path) {
+        Some(path) => Config::load(path).context(|| format!("Unable to load config {}", path.display())),
+        None => Ok(Config::default()),
+    }
 }
-// ============================================================================
-#[derive(Debug)]
-struct Statement {
-    file_path:      PathBuf,
-    statement_type: StatementType,
-    table:          String,
-    begin:          usize,
-    end:            usize,
-    text:           String,
+
+/// the config file of a scan: an explicitly given config file must exist, the
+/// default one is optional
+fn config_path(path: Option<&Path>) -> Option<&Path> {
+    let default_path = Path::new(config::DEFAULT_CONFIG_FILE);
+    match path {
+        Some(path) => Some(path),
+        None => default_path.is_file().then_some(default_path),
+    }
 }
 
-fn find_statements(file_path: &PathBuf, search_query: &SearchQuery) -> Option<Vec<Statement>> {
-    // return a vector of all the statements from a file that match the search query
-    // TODO: add support for statements that begin with CTEs
-    let file = File::open(file_path).unwrap();
-    let mut reader = BufReader::new(file);
-    let mut statements = Vec::<Statement>::new();
+/// the settings that a scan runs with, those of its options (or their
+/// defaults) and of its config file (`--print-config`)
+#[derive(Serialize)]
+struct EffectiveConfig<'a> {
+    scan:   ScanSettings<'a>,
+    #[serde(flatten)]
+    config: &'a Config,
+}
+
+#[derive(Serialize)]
+struct ScanSettings<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config:         Option<&'a Path>,
+    /// the queries, or `*` for every statement if none are given
+    search:         Vec<&'a str>,
+    not_table:      &'a [String],
+    #[serde(rename = "match")]
+    query_match:    String,
+    dialect:        String,
+    engine:         String,
+    encoding:       String,
+    format:         String,
+    threads:        usize,
+    exclude:        &'a [String],
+    paths:          Vec<String>,
+    first_match:    bool,
+    include_temp:   bool,
+    dynamic_sql:    bool,
+    ignore_pragmas: bool,
+}
+
+/// the name of a value of an option, as it's given on the command line
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
+}
 
-    let read_next_line = |reader: &mut BufReader<File>| -> String {
-        let mut line = String::new();
-        let _ = reader.read_line(&mut line);
-        line
+fn print_config(cli: &Cli, config: &Config, search_queries: &[SearchQuery], format: ConfigFormat) -> Result<i32> {
+    let settings = ScanSettings {
+        config:         config_path(cli.config.as_deref()),
+        search:         search_queries.iter().map(|query| query.name.as_str()).collect(),
+        not_table:      &cli.not_tables,
+        query_match:    value_name(cli.query_match),
+        dialect:        value_name(cli.dialect),
+        engine:         value_name(cli.engine),
+        encoding:       cli.encoding.unwrap_or(encoding_rs::UTF_8).name().to_lowercase(),
+        format:         value_name(cli.format),
+        threads:        match cli.threads {
+            0 => rayon::current_num_threads(),
+            n => n,
+        },
+        exclude:        &cli.excludes,
+        paths:          file_path_args(cli)?.iter().map(|path| path.to_string_lossy().to_string()).collect(),
+        first_match:    cli.first_match,
+        include_temp:   cli.include_temp,
+        dynamic_sql:    cli.dynamic_sql,
+        ignore_pragmas: !cli.no_ignore_pragmas,
+    };
+    let effective = EffectiveConfig { scan: settings, config };
+    let text = match format {
+        ConfigFormat::Toml => toml::to_string(&effective).context(|| String::from("Unable to write the config"))?,
+        ConfigFormat::Json => serde_json::to_string_pretty(&effective).context(|| String::from("Unable to write the config"))? + "\n",
     };
+    print!("{}", text);
+    Ok(exitcode::OK)
+}
+
+fn load_results(files: &[PathBuf]) -> Result<Vec<Results>> {
+    files.iter()
+        .map(|file| Results::load(file).context(|| format!("Unable to read results from {}", file.display())))
+        .collect()
+}
+
+fn merge_results(files: &[PathBuf]) -> Result<i32> {
+    let merged = Results::merge(load_results(files)?).context(|| String::from("Unable to merge results"))?;
+    merged.write(std::io::stdout().lock()).context(|| String::from("Unable to write results to stdout"))?;
+    Ok(exitcode::OK)
+}
+
+fn combine_results(files: &[PathBuf], operation: SetOperation) -> Result<i32> {
+    let combined = Results::combine(load_results(files)?, operation).context(|| String::from("Unable to combine results"))?;
+    combined.write(std::io::stdout().lock()).context(|| String::from("Unable to write results to stdout"))?;
+    Ok(exitcode::OK)
+}
 
-    let try_statement_type_from_line = |line: String| -> Option<StatementType> {
-        let first_word: String = line.split_whitespace().next().unwrap().to_lowercase();
-        if let Ok(statement_type) = StatementType::try_from(first_word) {
-            return Some(statement_type);
+/// compare the matches of two runs, which exits with DIFFERENT if any were
+/// added or removed
+fn compare_results(before: &Path, after: &Path, format: CompareFormat) -> Result<i32> {
+    let load = |path: &Path| Results::load(path).context(|| format!("Unable to read results from {}", path.display()));
+    let comparison = load(before)?.compare(load(after)?);
+    let stdout = std::io::stdout();
+    match format {
+        CompareFormat::Json => write_json(stdout.lock(), &comparison),
+        CompareFormat::Text => write_comparison(stdout.lock(), &comparison),
+    }.context(stdout_error)?;
+    match comparison.added.is_empty() && comparison.removed.is_empty() {
+        true  => Ok(exitcode::OK),
+        false => Ok(error::DIFFERENT),
+    }
+}
+
+fn write_comparison<W: Write>(mut w: W, comparison: &Comparison) -> std::io::Result<()> {
+    for (sign, matches) in [('+', &comparison.added), ('-', &comparison.removed)] {
+        for m in matches {
+            let s = &m.statement;
+            writeln!(w, "{} {} {}:{}: {} of {}", sign, m.fingerprint, s.file_path.display(), s.begin + 1, s.statement_type, s.table)?;
         }
-        None
+    }
+    writeln!(w, "{} added, {} removed, {} unchanged",
+        thousands(comparison.added.len()), thousands(comparison.removed.len()), thousands(comparison.unchanged.len()))
+}
+
+/// verify a signed file, which fails (with DATAERR) if it doesn't match its
+/// manifest
+fn verify_results(file: &Path) -> Result<i32> {
+    let document: serde_json::Value = std::fs::read_to_string(file)
+        .and_then(|text| Ok(serde_json::from_str(&text)?))
+        .context(|| format!("Unable to read {}", file.display()))?;
+    let problems = Manifest::verify(document).context(|| format!("Unable to verify {}", file.display()))?;
+    if problems.is_empty() {
+        println!("{}: verified", file.display());
+        return Ok(exitcode::OK);
+    }
+    for problem in problems {
+        println!("{}: {}", file.display(), problem);
+    }
+    Ok(exitcode::DATAERR)
+}
+
+/// check the files against a policy, which fails (with DATAERR) if there are
+/// any violations
+fn check_policy(policy_path: &Path, dialect: Dialect, format: ViolationFormat, file_paths: &[OsString]) -> Result<i32> {
+    let policy = Policy::load(policy_path).context(|| format!("Unable to load policy {}", policy_path.display()))?;
+    // read-only paths mayn't write any table
+    let tables = match policy.read_only.is_empty() {
+        true  => policy.table_names(),
+        false => Vec::new(),
     };
+    let query = SearchQuery::new(&policy_path.display().to_string(), StatementType::writes(), tables);
+    let scan_opts = ScanOpts { dialect, ..ScanOpts::default() };
+    let mut file_paths: Vec<PathBuf> = get_file_paths(file_paths, &Override::empty()).into_iter().collect();
+    file_paths.sort();
 
-    let trim_comment = |s: String| -> String {
-        match s.find("--") {
-            Some(i) => s[..i].to_string(),
-            None => s
+    let stdout   = std::io::stdout();
+    let mut lock = stdout.lock();
+    let mut searcher = Searcher::new();
+    let mut violations = 0;
+    let mut sarif_results: Vec<serde_json::Value> = Vec::new();
+    for path in &file_paths {
+        if !file_is_match(&mut searcher, path, &query) { continue; }
+        let Some(statements) = find_file_statements(path, std::slice::from_ref(&query), &scan_opts) else { continue; };
+        let text = handler::read_to_string(path).unwrap_or_default();
+        for violation in policy.violations(&text, &statements) {
+            let statement = violation.statement;
+            let writer = match &violation.object {
+                Some(object) => object.clone(),
+                None => String::from("the file"),
+            };
+            let message = format!("{} of {} by {} isn't allowed", statement.statement_type, statement.table, writer);
+            let hint = match violation.rule {
+                Rule::Table(table) => match &table.hint {
+                    Some(hint) => hint.clone(),
+                    None if table.writers.is_empty() => format!("nothing may write {} (see {})",
+                        statement.table, policy_path.display()),
+                    None => format!("only {} may write {} (see {})",
+                        table.writers.iter().map(|w| w.as_str()).collect::<Vec<&str>>().join(", "),
+                        statement.table, policy_path.display()),
+                },
+                Rule::ReadOnly(read_only) => match &read_only.hint {
+                    Some(hint) => hint.clone(),
+                    None => format!("{} may only read (see {})", read_only.path, policy_path.display()),
+                },
+            };
+            match format {
+                ViolationFormat::Text => {
+                    writeln!(lock, "{}:{}: {}", path.display(), statement.begin + 1, message).context(stdout_error)?;
+                    writeln!(lock, "    hint: {}", hint).context(stdout_error)?;
+                },
+                ViolationFormat::Sarif => {
+                    let rule_id = match violation.rule {
+                        Rule::Table(_)    => "protected-table",
+                        Rule::ReadOnly(_) => "read-only-path",
+                    };
+                    sarif_results.push(sink::sarif_result(statement, rule_id, "error", &format!("{} ({})", message, hint)));
+                },
+            }
+            violations += 1;
         }
-    };
+    }
+    if format == ViolationFormat::Sarif {
+        write_json(&mut lock, &sink::sarif_log(sarif_results)).context(stdout_error)?;
+    }
+    if violations > 0 {
+        eprintln!("{} policy violations", violations);
+        return Ok(exitcode::DATAERR);
+    }
+    Ok(exitcode::OK)
+}
 
-    let clean_text = |s: String| -> String { trim_comment(s.replace("\t", " ")) };
-
-    let mut comment_level: u8 = 0;
-    let mut i: isize = -1;
-    // while let Ok(line) = reader.read_line().unwrap().trim().trim_start_matches(';').to_string() {
-    while let Ok(is_more_to_read) = reader.has_data_left() {
-        if !is_more_to_read { break; }
-        i+= 1;
-        let line = read_next_line(&mut reader)
-            .trim()
-            .trim_start_matches(';')
-            .to_string();
-
-        if line.is_empty()        { continue; }
-        if line.starts_with("--") { continue; }
-        if line.contains("/*")    { comment_level +=1; }
-        if line.contains("*/")    { comment_level -=1; }
-        if comment_level > 0      { continue; }
-
-        // check if the first word of the line is the start of a statement that
-        // we care about based on the search query
-        if let Some(statement_type) = try_statement_type_from_line(line.clone()) {
-            if !search_query.statement_types.contains(&statement_type) { continue; }
-            // if we're in a statement type that was in the search query, then
-            // we need to read the entire query to determine whether contains
-            // a table from the search query
-            // TODO: this can maybe be optimized by checking each line to see
-            // if it has one of the keywords preceeding the table name, adding
-            // the following line to statement_text if it does, and then
-            // checking statement_text for the table.
-            let begin: usize = i.try_into().expect("i should be positive by the time the loop starts");
-            // let mut statement_text = line.to_string() + " ";
-            let mut statement_text = clean_text(line) + " ";
-            while let Ok(is_more_to_read) = reader.has_data_left() {
-                i += 1;
-                let line = read_next_line(&mut reader)
-                    .trim()
-                    .to_string();
-
-                if line.starts_with("--") { continue; }
-                if line.contains("/*")    { comment_level +=1; }
-                if line.contains("*/")    { comment_level -=1; }
-                if comment_level > 0      { continue; }
-
-                // start building up statement_text by concatenating each line
-                // until we reach an empty line or a semi-colon, which signals
-                // the end of the statement
-                if !line.is_empty() && !line.starts_with(";") {
-                    statement_text.push_str(&(clean_text(line) + " "));
-                    if is_more_to_read { continue; }
-                }
+/// check the files against the rules of a rules file, which fails (with
+/// DATAERR) if any statement is forbidden
+fn lint(rules_path: &Path, dialect: Dialect, format: ViolationFormat, file_paths: &[OsString]) -> Result<i32> {
+    let rules = Rules::load(rules_path).context(|| format!("Unable to load rules {}", rules_path.display()))?;
+    let queries = rules.search_queries();
+    let scan_opts = ScanOpts { dialect, query_match: QueryMatch::Any, ..ScanOpts::default() };
+    let mut file_paths: Vec<PathBuf> = get_file_paths(file_paths, &Override::empty()).into_iter().collect();
+    file_paths.sort();
 
-                if let Some(table) = &statement_type.find_table(&statement_text) {
-                    if search_query.tables.contains(&table) {
-                        statements.push(Statement {
-                            file_path:      file_path.to_path_buf(),
-                            statement_type,
-                            table:          table.to_string(),
-                            begin,
-                            end:            i.try_into().expect("i should be positive by the time the loop starts"),
-                            text:           statement_text,
-                        });
+    let stdout   = std::io::stdout();
+    let mut lock = stdout.lock();
+    let mut searcher = Searcher::new();
+    let mut violations = 0;
+    let mut sarif_results: Vec<serde_json::Value> = Vec::new();
+    for path in &file_paths {
+        if !file_matches_queries(&mut searcher, path, &queries, QueryMatch::Any) { continue; }
+        let Some(statements) = find_file_statements(path, &queries, &scan_opts) else { continue; };
+        for violation in rules.violations(&statements) {
+            let (statement, rule) = (violation.statement, violation.rule);
+            let message = match &statement.object {
+                Some(object) => format!("{} of {} in {} is forbidden", statement.statement_type, statement.table, object),
+                None => format!("{} of {} is forbidden", statement.statement_type, statement.table),
+            };
+            match format {
+                ViolationFormat::Text => {
+                    writeln!(lock, "{}:{}: {}[{}]: {}", path.display(), statement.begin + 1, rule.severity, rule.name, message)
+                        .context(stdout_error)?;
+                    if let Some(hint) = &rule.hint {
+                        writeln!(lock, "    hint: {}", hint).context(stdout_error)?;
                     }
-                }
-                break;
+                },
+                ViolationFormat::Sarif => {
+                    let message = match &rule.hint {
+                        Some(hint) => format!("{} ({})", message, hint),
+                        None => message,
+                    };
+                    sarif_results.push(sink::sarif_result(statement, &rule.name, &rule.severity.to_string(), &message));
+                },
             }
+            violations += 1;
         }
     }
-    match statements.len() {
-        0 => None,
-        _ => Some(statements),
+    if format == ViolationFormat::Sarif {
+        write_json(&mut lock, &sink::sarif_log(sarif_results)).context(stdout_error)?;
+    }
+    if violations > 0 {
+        eprintln!("{} lint violations", violations);
+        return Ok(exitcode::DATAERR);
     }
+    Ok(exitcode::OK)
 }
 
-fn print_statements(opts: PrintOpts, statements: Vec<Statement>) {
-    let del: char = opts.delimiter;
-    let stdout    = std::io::stdout();
-    let mut lock  = stdout.lock();
+fn stdout_error() -> String {
+    String::from("Unable to write to stdout")
+}
+
+fn sign_report<T: Serialize>(queries: &[String], file_paths: &[PathBuf], report: &T) -> Result<Manifest> {
+    Manifest::new(queries, file_paths, report).context(|| String::from("Unable to sign report"))
+}
+
+fn write_json<W: Write, T: Serialize>(mut w: W, document: &T) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut w, document)?;
+    writeln!(w)
+}
+
+fn load_schema(path: &Path) -> Result<Schema> {
+    let text = std::fs::read_to_string(path).context(|| format!("Unable to read schema {}", path.display()))?;
+    Ok(Schema::parse(&text))
+}
 
-    if opts.hide_statement {
-        for s in statements {
-            writeln!(lock, "{}{}{}{}{}{}{}{}{}",
-                s.file_path.display(), del,
-                s.begin, del,
-                s.end, del,
-                s.statement_type, del,
-                s.table
-                ).unwrap();
+/// report the uses of what a deployment removes, which fails (with DATAERR)
+/// if there are any
+fn schema_diff(old_path: &Path, new_path: &Path, file_paths: &[OsString], dialect: Dialect) -> Result<i32> {
+    let removed = load_schema(old_path)?.removed(&load_schema(new_path)?);
+    if removed.is_empty() { return Ok(exitcode::OK); }
+    let tables: Vec<String> = removed.tables.iter().chain(removed.columns.keys()).cloned().collect();
+    let query = SearchQuery::new("schema-diff", StatementType::all(), tables);
+    let scan_opts = ScanOpts { dialect, ..ScanOpts::default() };
+    let column_res: Vec<(&String, Vec<(&String, Regex)>)> = removed.columns.iter()
+        .map(|(table, columns)| (table, columns.iter()
+            .map(|column| (column, Regex::new(&format!(r"(?i)\b{}\b", regex::escape(column))).expect("regex didn't compile")))
+            .collect()))
+        .collect();
+    let mut file_paths: Vec<PathBuf> = get_file_paths(file_paths, &Override::empty()).into_iter().collect();
+    file_paths.sort();
+
+    let stdout   = std::io::stdout();
+    let mut lock = stdout.lock();
+    let mut searcher = Searcher::new();
+    let mut breakages = 0;
+    for path in &file_paths {
+        if !file_is_match(&mut searcher, path, &query) { continue; }
+        let Some(statements) = find_file_statements(path, std::slice::from_ref(&query), &scan_opts) else { continue; };
+        for statement in &statements {
+            if removed.tables.iter().any(|table| table.eq_ignore_ascii_case(&statement.table)) {
+                writeln!(lock, "{}:{}: {} of the removed table {}",
+                    path.display(), statement.begin + 1, statement.statement_type, statement.table).context(stdout_error)?;
+                breakages += 1;
+                continue;
+            }
+            let uses = column_res.iter()
+                .filter(|(table, _)| table.eq_ignore_ascii_case(&statement.table))
+                .flat_map(|(table, columns)| columns.iter().map(move |(column, re)| (table, column, re)))
+                .filter(|(_, _, re)| re.is_match(&statement.text));
+            for (table, column, _) in uses {
+                writeln!(lock, "{}:{}: {} of {} uses the removed column {}.{}",
+                    path.display(), statement.begin + 1, statement.statement_type, statement.table, table, column).context(stdout_error)?;
+                breakages += 1;
+            }
         }
-        return;
     }
+    if breakages > 0 {
+        eprintln!("{} uses of removed tables or columns", breakages);
+        return Ok(exitcode::DATAERR);
+    }
+    Ok(exitcode::OK)
+}
 
-    for s in statements {
-        writeln!(lock, "{}{}{}{}{}{}{}{}{}{}{}",
-            s.file_path.display(), del,
-            s.begin, del,
-            s.end, del,
-            s.statement_type, del,
-            s.table, del,
-            s.text
-            ).unwrap();
+fn main() {
+    let cli = Cli::parse();
+    if let Some(encoding) = cli.encoding {
+        handler::set_encoding(encoding);
+    }
+    let code = match &cli.command {
+        Some(command) => run_command(command),
+        None => scan(&cli),
+    };
+    match code {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(error::ERROR);
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    #[allow(unused_imports)]
-    use super::*;
+fn run_command(command: &Command) -> Result<i32> {
+    match command {
+        Command::Merge { files } => merge_results(files),
+        Command::Combine { intersect, union, files, .. } => {
+            let operation = match (intersect, union) {
+                (true, _) => SetOperation::Intersect,
+                (_, true) => SetOperation::Union,
+                _         => SetOperation::Subtract,
+            };
+            combine_results(files, operation)
+        },
+        Command::Policy { command: PolicyCommand::Check { policy, dialect, format, file_paths } } => {
+            check_policy(policy, *dialect, *format, file_paths)
+        },
+        Command::Lint { rules, dialect, format, file_paths } => lint(rules, *dialect, *format, file_paths),
+        Command::Compare { format, before, after } => compare_results(before, after, *format),
+        Command::Verify { file } => verify_results(file),
+        Command::SchemaDiff { old, new, used_by, dialect } => schema_diff(old, new, used_by, *dialect),
+        Command::Index { index, dialect, excludes, file_paths } => build_index(index, file_paths, excludes, *dialect),
+        Command::Query { search_queries, index, include_temp, cache_stats, latency_report: true, runs, .. } => {
+            latency_report(index, search_queries, *include_temp, *cache_stats, *runs as usize)
+        },
+        Command::Query { search_queries, index, query_match, include_temp, format, delimiter, only_file_paths, hide_statement, color, cache_stats, .. } => {
+            let print_opts = PrintOpts {
+                only_file_paths: *only_file_paths,
+                hide_statement:  *hide_statement,
+                delimiter:       *delimiter,
+                sinks:           vec![Sink::stdout(*format)],
+                capture_names:   Vec::new(),
+                header_columns:  false,
+                object_column:   false,
+                module_column:   false,
+                query_column:    search_queries.len() > 1,
+                max_column_width: 40,
+                signed_inputs:   None,
+                color:           color.enabled(),
+                note_columns:    false,
+                commit_column:   false,
+                declarations:    Declarations::new(),
+            };
+            query_index(index, search_queries, *query_match, *include_temp, *cache_stats, print_opts)
+        },
+        Command::Census { tables_from, indexes, format, cache_stats } => census(tables_from, indexes, *format, *cache_stats),
+        Command::Jump { n, jumplist } => jump(jumplist, *n),
+        Command::Schema { format: SchemaFormat::JsonSchema, document } => {
+            write_json(std::io::stdout().lock(), &document.json_schema()).context(stdout_error)?;
+            Ok(exitcode::OK)
+        },
+    }
+}
 
-    #[test]
-    fn parse_statement_types() {
-        let invalid_characters: Vec<char> = vec![';', 'a', '\n', '*'];
-        for c in invalid_characters {
-            assert!(super::StatementType::try_from(c).is_err());
-        }
+/// parse every statement of the files into a new index
+fn build_index(index_path: &Path, file_paths: &[OsString], excludes: &[String], dialect: Dialect) -> Result<i32> {
+    let mut index = Index::new(file_paths, excludes, dialect);
+    index.update().context(|| String::from("Invalid --exclude glob"))?;
+    index.save(index_path).context(|| format!("Unable to write the index {}", index_path.display()))?;
+    eprintln!("indexed {} statements in {} files to {}",
+        thousands(index.statements()), thousands(index.files()), index_path.display());
+    Ok(exitcode::OK)
+}
 
-        let invalid_strings: Vec<&str> = vec!["alter", "declare", "apply", "grant", ""];
-        for s in invalid_strings {
-            assert!(super::StatementType::try_from(s.to_string()).is_err());
+/// load an index and bring it up to date with the files, saving it if any
+/// changed so that the next search doesn't parse them again. With
+/// `--cache-stats`, how much of the index was reused is printed to stderr.
+fn load_index(index_path: &Path, cache_stats: bool) -> Result<Index> {
+    let mut index = Index::load(index_path).context(|| format!("Unable to load the index {} (run gresql index first)", index_path.display()))?;
+    let update = index.update().context(|| String::from("Invalid --exclude glob"))?;
+    // an index saved by another process in the meantime is kept, the next
+    // search loading it instead
+    if update.is_changed() {
+        index.save(index_path).context(|| format!("Unable to write the index {}", index_path.display()))?;
+    }
+    if cache_stats {
+        eprintln!("{}: {}", index_path.display(), cache_summary(&update));
+    }
+    Ok(index)
+}
+
+/// the hits and misses of an update of an index, e.g. `118 hits, 3 misses (2
+/// new files, 1 stale file parsed again), 1 removed file, parsed in 0.4s,
+/// saved about 12.3s`
+fn cache_summary(update: &IndexUpdate) -> String {
+    format!("{} hits, {} misses ({} new files, {} stale files parsed again), {} removed files, parsed in {}, saved about {}",
+        thousands(update.unchanged), thousands(update.added + update.changed), thousands(update.added), thousands(update.changed),
+        thousands(update.removed), human_duration(update.parsed_in.as_secs_f64()), human_duration(update.saved.as_secs_f64()))
+}
+
+/// search an index for the queries, after bringing it up to date with the
+/// files
+fn query_index(index_path: &Path, queries: &[String], query_match: QueryMatch, include_temp: bool, cache_stats: bool, print_opts: PrintOpts) -> Result<i32> {
+    if let Some((table, e)) = invalid_table_patterns(queries).first() {
+        return Err(Error::new(format!("Invalid table pattern {}: {}", table, e)));
+    }
+    let search_queries = parse_search_queries(queries);
+    let index = load_index(index_path, cache_stats)?;
+    let statements = index.search(&search_queries, query_match, include_temp);
+    let matched = !statements.is_empty();
+    if !matched {
+        eprintln!("No statements found");
+    }
+    if print_opts.only_file_paths {
+        let mut matched_files: Vec<&PathBuf> = statements.iter().map(|s| &s.file_path).collect();
+        matched_files.dedup();
+        let stdout   = std::io::stdout();
+        let mut lock = stdout.lock();
+        for f in matched_files {
+            let path = f.display().to_string();
+            writeln!(lock, "{}", if print_opts.color { color::path(&path) } else { path }).context(stdout_error)?;
         }
+    } else {
+        print_statements(print_opts, queries, statements)?;
     }
+    Ok(if matched { error::MATCHED } else { error::NO_MATCH })
+}
 
+/// time the searches of each query against the index (brought up to date with
+/// its files first), as a service answering them from a loaded index would
+/// take, e.g.
+/// `u:t_order  100 runs  12 matches  p50 0.412ms  p95 0.530ms`
+fn latency_report(index_path: &Path, queries: &[String], include_temp: bool, cache_stats: bool, runs: usize) -> Result<i32> {
+    if let Some((table, e)) = invalid_table_patterns(queries).first() {
+        return Err(Error::new(format!("Invalid table pattern {}: {}", table, e)));
+    }
+    let search_queries = parse_search_queries(queries);
+    let index = load_index(index_path, cache_stats)?;
+    let ms = |time: Duration| format!("{:.3}ms", time.as_secs_f64() * 1000.0);
+    let width = queries.iter().map(|query| query.chars().count()).max().unwrap_or(0).max("all queries".len());
+    let stdout   = std::io::stdout();
+    let mut lock = stdout.lock();
+    writeln!(lock, "{} files, {} statements", thousands(index.files()), thousands(index.statements())).context(stdout_error)?;
+    let mut all_times: Vec<Duration> = Vec::new();
+    for (query, search_query) in queries.iter().zip(&search_queries) {
+        let (matches, times) = index.time_search(search_query, include_temp, runs);
+        writeln!(lock, "{:<width$}  {} runs  {} matches  p50 {}  p95 {}", query, runs, thousands(matches),
+            ms(index::percentile(&times, 50)), ms(index::percentile(&times, 95)), width = width).context(stdout_error)?;
+        all_times.extend(times);
+    }
+    if queries.len() > 1 {
+        all_times.sort();
+        writeln!(lock, "{:<width$}  {} runs  p50 {}  p95 {}", "all queries", all_times.len(),
+            ms(index::percentile(&all_times, 50)), ms(index::percentile(&all_times, 95)), width = width).context(stdout_error)?;
+    }
+    Ok(exitcode::OK)
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// count the readers and writers of the listed tables across the statements
+/// of the indexes, each brought up to date with its files first. The list has
+/// a table per line, blank lines and `#` comments being skipped.
+fn census(tables_from: &Path, index_paths: &[PathBuf], format: CensusFormat, cache_stats: bool) -> Result<i32> {
+    let tables: Vec<String> = std::fs::read_to_string(tables_from)
+        .context(|| format!("Unable to read {}", tables_from.display()))?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect();
+    let indexes: Vec<Index> = index_paths.iter()
+        .map(|index_path| load_index(index_path, cache_stats))
+        .collect::<Result<_>>()?;
+    let census = Census::new(&tables, indexes.iter().flat_map(Index::indexed_statements));
+    let stdout = std::io::stdout();
+    match format {
+        CensusFormat::Json => write_json(stdout.lock(), &census),
+        CensusFormat::Text => census.write(stdout.lock()),
+    }.context(stdout_error)?;
+    Ok(exitcode::OK)
+}
+
+/// print the n-th location of a jump list, e.g. for an editor to open
+fn jump(jumplist: &Path, n: usize) -> Result<i32> {
+    let text = std::fs::read_to_string(jumplist).context(|| format!("Unable to read {}", jumplist.display()))?;
+    let location = sink::jump_location(&text, n)
+        .ok_or_else(|| Error::new(format!("{} has no location {}", jumplist.display(), n)))?;
+    writeln!(std::io::stdout(), "{}", location).context(stdout_error)?;
+    Ok(exitcode::OK)
+}
 
-    let search_queries: Vec<SearchQuery> = parse_search_queries(cli.search_queries);
-    let file_paths: HashSet<PathBuf> = get_file_paths(&cli.file_paths);
-    let print_opts: PrintOpts = PrintOpts {
+/// the paths to scan: those given as arguments and those listed by
+/// `--files-from` or on stdin (`-`), or the current directory if there are
+/// none (and the clipboard isn't scanned either). Only the listed files that
+/// a handler reads are kept, so that a list of every changed file can be given.
+fn file_path_args(cli: &Cli) -> Result<Vec<OsString>> {
+    let stdin = Path::new("-");
+    let reads_stdin = cli.file_paths.iter().any(|path| path == "-") || cli.files_from.as_deref() == Some(stdin);
+    let mut listed: Vec<OsString> = Vec::new();
+    if reads_stdin {
+        listed.extend(read_file_list(std::io::stdin().lock()).context(|| String::from("Unable to read the file list from stdin"))?);
+    }
+    if let Some(path) = cli.files_from.as_deref().filter(|path| *path != stdin) {
+        let file = File::open(path).context(|| format!("Unable to read {}", path.display()))?;
+        listed.extend(read_file_list(file).context(|| format!("Unable to read {}", path.display()))?);
+    }
+    listed.retain(|path| handler::is_handled(Path::new(path)));
+
+    let mut paths: Vec<OsString> = cli.file_paths.iter().filter(|path| *path != "-").cloned().collect();
+    if paths.is_empty() && !reads_stdin && cli.files_from.is_none() && !cli.clipboard {
+        paths.push(OsString::from("."));
+    }
+    paths.extend(listed);
+    Ok(paths)
+}
+
+/// scan the files for the statements matching the search queries. The exit
+/// code tells whether any matched (MATCHED or NO_MATCH), unless the matches
+/// are over budget (DATAERR).
+fn scan(cli: &Cli) -> Result<i32> {
+    let sinks: Vec<Sink> = match cli.outputs.is_empty() {
+        true  => vec![Sink::stdout(cli.format)],
+        false => cli.outputs.clone(),
+    };
+    if sinks.iter().filter(|sink| sink.path.is_none()).count() > 1 {
+        return Err(Error::new("--output: only one of the outputs can be written to stdout"));
+    }
+    if cli.sign && !sinks.iter().any(|sink| sink.format == OutputFormat::Json) {
+        return Err(Error::new("--sign requires --format json"));
+    }
+    let started = Instant::now();
+    let config: Config = load_config(cli.config.as_deref())?;
+    let notes = match &cli.annotate {
+        Some(path) => Some(results::load_notes(path).context(|| format!("Unable to load notes from {}", path.display()))?),
+        None => None,
+    };
+    if let Some((table, e)) = invalid_table_patterns(&cli.search_queries).first() {
+        return Err(Error::new(format!("Invalid table pattern {}: {}", table, e)));
+    }
+    for warning in overlapping_queries(&cli.search_queries, cli.query_match) {
+        eprintln!("Warning: {}", warning);
+    }
+    let mut search_queries: Vec<SearchQuery> = parse_search_queries(&cli.search_queries);
+    if search_queries.is_empty() {
+        search_queries.push(SearchQuery::all());
+    }
+    for query in &mut search_queries {
+        for table in &cli.not_tables {
+            query.exclude_table(table).map_err(|e| Error::new(format!("Invalid table pattern {}: {}", table, e)))?;
+        }
+        query.keywords = config.keywords.clone();
+    }
+    if let Some(format) = cli.print_config {
+        return print_config(cli, &config, &search_queries, format);
+    }
+    for (label, _) in &cli.budgets {
+        let Some(label) = label else { continue; };
+        if !search_queries.iter().any(|query| query.label.as_ref() == Some(label)) {
+            return Err(Error::new(format!("--fail-if-more-than: no search query is labeled {}", label)));
+        }
+    }
+    let excludes = exclude_globs(&cli.excludes).context(|| String::from("Invalid --exclude glob"))?;
+    let path_args = file_path_args(cli)?;
+    let git_range = match &cli.git_range {
+        Some(range) => Some(GitRange::checkout(range, &path_args, &excludes)?),
+        None => None,
+    };
+    if cli.stream && !cli.only_file_paths && !matches!(cli.format, OutputFormat::Ndjson | OutputFormat::Vimgrep) {
+        return Err(Error::new("--stream prints the matches with --format ndjson or vimgrep, or -p"));
+    }
+    if (cli.top.is_some() || cli.drill_down.is_some()) && !matches!(cli.report, Some(ReportKind::Crud | ReportKind::CrudByFile)) {
+        return Err(Error::new("--top and --drill-down only apply to the crud reports"));
+    }
+    // the files of the other paths are still scanned, but the scan fails like
+    // one with unreadable files
+    let invalid_globs: Vec<&OsString> = match &git_range {
+        Some(_) => Vec::new(),
+        None    => invalid_globs(&path_args),
+    };
+    let mut file_paths: Vec<PathBuf> = match &git_range {
+        Some(git_range) => git_range.paths(),
+        // a stream walks the files as it scans them
+        None if cli.stream => Vec::new(),
+        None => get_file_paths(&path_args, &excludes).into_iter().collect(),
+    };
+    file_paths.sort();
+    let clipboard = match cli.clipboard {
+        true  => Some(ClipboardFile::read()?),
+        false => None,
+    };
+    file_paths.extend(clipboard.as_ref().map(|clipboard| clipboard.path().to_path_buf()));
+    let scan_opts: ScanOpts = ScanOpts {
+        first_match:    cli.first_match,
+        dialect:        cli.dialect,
+        ignore_pragmas: !cli.no_ignore_pragmas,
+        engine:         cli.engine,
+        why:            cli.why.is_some(),
+        query_match:    cli.query_match,
+        include_temp:   cli.include_temp,
+        dynamic_sql:    true,
+        resolve_exec:   cli.dynamic_sql,
+    };
+    if cli.list_files {
+        let stdout   = std::io::stdout();
+        let mut lock = stdout.lock();
+        for path in file_paths.iter().filter(|path| cli.shard.is_none_or(|shard| in_shard(path, shard))) {
+            writeln!(lock, "{}", path.display()).context(stdout_error)?;
+        }
+        return Ok(if invalid_globs.is_empty() { exitcode::OK } else { error::ERROR });
+    }
+    if cli.estimate {
+        let file_paths: Vec<&PathBuf> = file_paths.iter()
+            .filter(|path| cli.shard.is_none_or(|shard| in_shard(path, shard)))
+            .collect();
+        estimate_scan(&file_paths, &search_queries, &scan_opts, cli.threads);
+        return Ok(exitcode::OK);
+    }
+    if let Some(shard) = cli.shard {
+        file_paths.retain(|path| in_shard(path, shard));
+    }
+    let mut print_opts: PrintOpts = PrintOpts {
         only_file_paths: cli.only_file_paths,
         hide_statement:  cli.hide_statement,
         delimiter:       cli.delimiter,
+        sinks,
+        capture_names:   config.captures.iter().map(|c| c.name.clone()).collect(),
+        header_columns:  cli.header_columns,
+        object_column:   cli.object_column,
+        module_column:   !config.modules.is_empty(),
+        query_column:    cli.search_queries.len() > 1,
+        max_column_width: cli.max_column_width,
+        signed_inputs:   if cli.sign { Some(file_paths.clone()) } else { None },
+        color:           cli.color.enabled(),
+        note_columns:    cli.annotate.is_some(),
+        commit_column:   cli.git_range.is_some(),
+        declarations:    Declarations::new(),
     };
+    let parse_headers = cli.header_columns || cli.author.is_some() || cli.format == OutputFormat::Json;
+
+    if let Some(path) = &cli.why {
+        explain_file(path, &search_queries, cli, &config.scan_opts(path, &scan_opts))?;
+        return Ok(exitcode::OK);
+    }
 
     if cli.verbose {
         dbg!(&search_queries);
 
         println!("Paths given:");
-        dbg!(&cli.file_paths);
+        dbg!(&path_args);
 
         println!("Paths found:");
         dbg!(&file_paths);
     }
 
-    // first step is to do a basic search for all the files that contain the
-    // tables and the statement types.
-    // this search is only the first step to narrow-down the file-list.
-    // e.g. it won't tell us if a file has an update statement to `orders`, only
-    // that a file contains both an update statement and `orders`.
-    let mut matched_files: HashSet<PathBuf> = HashSet::new();
-    let mut searcher = Searcher::new();
-    for path in &file_paths {
-        let file_is_match = |search_query: &SearchQuery| -> bool {
-            for pattern in vec![&search_query.statement_pattern(), &search_query.table_pattern()] {
-                let matcher = RegexMatcher::new(pattern.as_str()).unwrap();
-                let mut is_match = false;
-                let set_found = |_l: u64, _s: &[u8]| -> Result<bool, _> {
-                    is_match = true;
-                    Ok(false) // return false to stop the search
-                };
+    // when resuming, keep writing progress to the checkpoint that was resumed
+    // from unless a different checkpoint file was given
+    let checkpoint_interval = Duration::from_secs(cli.checkpoint_interval);
+    let mut checkpoint: Option<Checkpoint> = match (&cli.resume, &cli.checkpoint) {
+        (Some(resume_path), checkpoint_path) => {
+            let checkpoint = Checkpoint::load(resume_path, &cli.search_queries, checkpoint_interval)
+                .context(|| format!("Unable to resume from {}", resume_path.display()))?;
+            match checkpoint_path {
+                Some(checkpoint_path) => Some(checkpoint.with_path(checkpoint_path)),
+                None => Some(checkpoint),
+            }
+        },
+        (None, Some(checkpoint_path)) => Some(Checkpoint::new(checkpoint_path, &cli.search_queries, checkpoint_interval)),
+        (None, None) => None,
+    };
 
-                if let Err(_) = searcher.search_path(&matcher, path, Bytes(set_found)) {
-                    eprintln!("Error when searching {} for {}", path.display(), pattern);
-                    return false;
-                }
+    // statements from files that were completed before the scan was resumed
+    let mut statements: Vec<Statement> = match &checkpoint {
+        Some(checkpoint) => checkpoint.statements().to_vec(),
+        None => Vec::new(),
+    };
 
-                // exit early if we didn't find a match
-                if !is_match { return false; }
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(cli.threads).build()
+        .context(|| format!("Unable to start {} threads", cli.threads))?;
+    // the prefilter has to match every query (or any of them) before the file
+    // is read statement-by-statement. Returns whether it did, the statements found
+    // and the tables the file declares, or the error if the file can't be read
+    // (e.g. it's locked by another program).
+    let lines_scanned = AtomicUsize::new(0);
+    let scan_file = |searcher: &mut Searcher, path: &PathBuf| -> FileScan {
+        lines_scanned.fetch_add(count_lines(path)?, Ordering::Relaxed);
+        if !file_matches_queries(searcher, path, &search_queries, cli.query_match) {
+            return Ok((false, Vec::new(), Vec::new()));
+        }
+        // the text read for the matches also has the tables the file declares
+        let text = handler::read_to_string(path)?;
+        let declared = catalog::declarations(&text);
+        // the files of a git range are matched against the config by their
+        // path in the repository
+        let config_path = git_range.as_ref().and_then(|git_range| git_range.source(path)).unwrap_or(path);
+        let Some(mut found_statements) = find_text_statements(path, &text, &search_queries, &config.scan_opts(config_path, &scan_opts)) else {
+            return Ok((true, Vec::new(), declared));
+        };
+        annotate_file_statements(path, &config, parse_headers, &mut found_statements);
+        if config_path != path {
+            let module = config.module(config_path);
+            for statement in &mut found_statements {
+                statement.module = module.map(String::from);
             }
-            true
+        }
+        if let Some(author) = &cli.author {
+            found_statements.retain(|s| author_matches(s, author));
+        }
+        if !cli.only.is_empty() {
+            let objects = objects::parse_objects(&text);
+            found_statements.retain(|s| cli.only.contains(&objects::kind_at(&objects, s.begin)));
+        }
+        if !cli.object_patterns.is_empty() {
+            found_statements.retain(|s| object_matches(s.object.as_deref(), &cli.object_patterns));
+        }
+        if let Some(filter_regex) = &cli.filter_regex {
+            found_statements.retain(|s| filter_regex.is_match(&s.text));
+        }
+        Ok((true, found_statements, declared))
+    };
+
+    // a stream prints the matches of each file as soon as it's scanned, the
+    // files being scanned in parallel as they're walked, so that neither the
+    // files nor their matches are ever all held at once. With --sorted, the
+    // files scanned ahead of one that's still being scanned wait for it.
+    if cli.stream {
+        // the tables are named as they're declared in the files scanned so
+        // far, not in those that are still to be walked
+        let mut catalog = Catalog::default();
+        let files = walk_file_paths(&path_args, &excludes).filter(|path| cli.shard.is_none_or(|shard| in_shard(path, shard)));
+        let (sender, receiver) = mpsc::channel::<(usize, PathBuf, FileScan)>();
+        let (mut scanned_files, mut matches, mut matched_files) = (0, 0, 0);
+        let mut unreadable_files: Vec<(PathBuf, std::io::Error)> = Vec::new();
+        let (pool, scan_file) = (&pool, &scan_file);
+        std::thread::scope(|scope| -> Result<()> {
+            // the scan stops once the matches can't be printed
+            scope.spawn(move || pool.install(|| {
+                let _ = files.enumerate().par_bridge()
+                    .map_init(Searcher::new, |searcher, (n, path)| {
+                        let result = scan_file(searcher, &path);
+                        (n, path, result)
+                    })
+                    .try_for_each_with(sender, |sender, scanned| sender.send(scanned));
+            }));
+            let stdout = std::io::stdout();
+            let mut waiting: BTreeMap<usize, (PathBuf, FileScan)> = BTreeMap::new();
+            let mut next = 0;
+            for (n, path, result) in receiver {
+                let ready = match cli.sorted {
+                    true  => {
+                        waiting.insert(n, (path, result));
+                        let mut ready = Vec::new();
+                        while let Some(file) = waiting.remove(&next) {
+                            ready.push(file);
+                            next += 1;
+                        }
+                        ready
+                    },
+                    false => vec![(path, result)],
+                };
+                let mut lock = stdout.lock();
+                for (path, result) in ready {
+                    scanned_files += 1;
+                    let mut found_statements = match result {
+                        Ok((_, found_statements, declared)) => {
+                            catalog.declare(&declared);
+                            found_statements
+                        },
+                        Err(e) => {
+                            unreadable_files.push((path, e));
+                            continue;
+                        }
+                    };
+                    if found_statements.is_empty() { continue; }
+                    catalog.name_tables(&mut found_statements);
+                    if let Some(notes) = &notes {
+                        results::annotate(&mut found_statements, notes);
+                    }
+                    matches += found_statements.len();
+                    matched_files += 1;
+                    match print_opts.only_file_paths {
+                        true  => {
+                            let path = path.display().to_string();
+                            writeln!(lock, "{}", if print_opts.color { color::path(&path) } else { path })
+                        },
+                        false => write_statements(&mut lock, &print_opts, cli.format, print_opts.color, &cli.search_queries, &found_statements),
+                    }.context(stdout_error)?;
+                }
+                lock.flush().context(stdout_error)?;
+            }
+            Ok(())
+        })?;
+        warn_unreadable(&unreadable_files);
+        if !cli.no_summary {
+            eprintln!("matched {} statements in {} files across {} ({} lines) scanned in {:.1}s{}",
+                thousands(matches), thousands(matched_files), thousands(scanned_files),
+                thousands(lines_scanned.load(Ordering::Relaxed)), started.elapsed().as_secs_f64(), skipped_globs(&invalid_globs));
+        }
+        return match (unreadable_files.is_empty() && invalid_globs.is_empty(), matches > 0) {
+            (false, _)    => Ok(error::ERROR),
+            (true, true)  => Ok(error::MATCHED),
+            (true, false) => Ok(error::NO_MATCH),
         };
+    }
 
-        if search_queries.iter().map(file_is_match).all(|b| b) {
-            matched_files.insert(path.clone());
+    let remaining_files: Vec<&PathBuf> = file_paths.iter()
+        .filter(|path| !checkpoint.as_ref().is_some_and(|checkpoint| checkpoint.is_complete(path)))
+        .collect();
+    let mut prefiltered_files: Vec<&PathBuf> = Vec::new();
+    let mut unreadable_files: Vec<(&PathBuf, std::io::Error)> = Vec::new();
+    // the tables declared in the files completed before the scan was resumed
+    let mut declarations: Declarations = match &checkpoint {
+        Some(checkpoint) => checkpoint.declarations().clone(),
+        None => Declarations::new(),
+    };
+    // files are scanned in parallel a chunk at a time, and their results are
+    // collected in the order of the files, so that the output is the same
+    // for any number of threads and the checkpoint keeps up with the scan
+    for chunk in remaining_files.chunks(pool.current_num_threads() * 16) {
+        let results: Vec<FileScan> = pool.install(|| {
+            chunk.par_iter().map_init(Searcher::new, |searcher, path| scan_file(searcher, path)).collect()
+        });
+        for (path, result) in chunk.iter().zip(results) {
+            // unreadable files aren't recorded in the checkpoint, so that
+            // resuming the scan tries them again
+            let (prefiltered, found_statements, declared) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    unreadable_files.push((path, e));
+                    continue;
+                }
+            };
+            if prefiltered { prefiltered_files.push(path); }
+            if !declared.is_empty() {
+                declarations.insert(path.to_path_buf(), declared.clone());
+            }
+            if let Some(checkpoint) = &mut checkpoint {
+                if let Err(e) = checkpoint.record(path, &found_statements, &declared) {
+                    eprintln!("Unable to write checkpoint: {}", e);
+                }
+            }
+            statements.extend(found_statements);
         }
     }
 
-    if cli.verbose {
-        println!("STEP 1 RESULTS: {} files matched", matched_files.len());
-        dbg!(&matched_files);
+    if let Some(checkpoint) = &mut checkpoint {
+        if let Err(e) = checkpoint.save() {
+            eprintln!("Unable to write checkpoint: {}", e);
+        }
     }
 
-    // build list of matching statements
-    // if no matching statements are found in a given file, remove it from
-    // matched_files
-    let mut statements: Vec<Statement> = Vec::new();
-    for query in search_queries.iter() {
-        matched_files.drain_filter(|file_path| {
-            if let Some(found_statements) = find_statements(file_path, query) {
-                statements.extend(found_statements);
-                true
-            } else {
-                false
-            }
-        });
+    // the tables are named as they're declared in the files of the shard (the
+    // JSON results keep the declarations, so that merging the shards names
+    // them as declared in any of them)
+    Catalog::new(&declarations).name_tables(&mut statements);
+    print_opts.declarations = declarations;
+
+    // the files are inverted before the clipboard's file is renamed, like
+    // its statements
+    let unmatched_files: Vec<&PathBuf> = match cli.files_without_match {
+        true  => {
+            let unreadable: Vec<&PathBuf> = unreadable_files.iter().map(|(path, _)| *path).collect();
+            files_without_match(&file_paths, &statements, &unreadable)
+        },
+        false => Vec::new(),
+    };
+
+    if let Some(clipboard) = &clipboard {
+        for statement in statements.iter_mut().filter(|s| s.file_path == clipboard.path()) {
+            statement.file_path = PathBuf::from(CLIPBOARD_PATH);
+        }
+    }
+    if let Some(git_range) = &git_range {
+        git_range.restore(&mut statements);
     }
+    // the matches are fingerprinted as they're written
+    if let Some(notes) = &notes {
+        results::annotate(&mut statements, notes);
+    }
+
+    // statements are grouped by file, so the list of matching files is the
+    // list of distinct (consecutive) statement paths
+    let mut matched_files: Vec<&PathBuf> = statements.iter().map(|s| &s.file_path).collect();
+    matched_files.dedup();
 
     if cli.verbose {
+        println!("STEP 1 RESULTS: {} files matched", prefiltered_files.len());
+        dbg!(&prefiltered_files);
         println!("STEP 2 RESULTS: {} files matched", matched_files.len());
         dbg!(&matched_files);
+        println!("Statements (file:line batch):");
+        for statement in &statements {
+            println!("{}:{} batch {}", statement.file_path.display(), statement.begin + 1, statement.batch + 1);
+        }
     }
 
-    if statements.is_empty() {
-        eprintln!("No statements found");
-        return;
+    // the budgets are checked after the results have been printed, so that
+    // the matches over budget can be seen
+    let mut over_budget: Vec<String> = Vec::new();
+    for (label, n) in &cli.budgets {
+        let matches = match label {
+            Some(label) => {
+                // every label was checked to be a query's before the scan
+                let query = search_queries.iter().find(|query| query.label.as_ref() == Some(label)).expect("budget label isn't a query's");
+                statements.iter().filter(|statement| query.matches(statement)).count()
+            },
+            None => statements.len(),
+        };
+        if matches > *n {
+            over_budget.push(format!("{}: {} matches, more than the budget of {}",
+                label.as_deref().unwrap_or("all queries"), matches, n));
+        }
     }
 
-    if print_opts.only_file_paths {
+    let summary = format!("matched {} statements in {} files across {} ({} lines) scanned in {:.1}s{}",
+        thousands(statements.len()), thousands(matched_files.len()), thousands(file_paths.len()),
+        thousands(lines_scanned.load(Ordering::Relaxed)),
+        started.elapsed().as_secs_f64(), skipped_globs(&invalid_globs));
+
+    // like `grep -L`, a scan for the files without a match succeeds if it
+    // finds any
+    let matched = match cli.files_without_match {
+        true  => !unmatched_files.is_empty(),
+        false => !statements.is_empty(),
+    };
+    if let Some(report) = cli.report {
+        let stdout = std::io::stdout();
+        match report {
+            ReportKind::Profile => {
+                let mut profile = Profile::new(&file_paths, &statements);
+                if cli.sign { profile.manifest = Some(sign_report(&cli.search_queries, &file_paths, &profile)?); }
+                match cli.format {
+                    OutputFormat::Json => write_json(stdout.lock(), &profile),
+                    _ => profile.write(stdout.lock()),
+                }.context(stdout_error)?;
+            },
+            ReportKind::DeployOrder => {
+                let mut deploy_order = DeployOrder::new(&file_paths, &statements);
+                if cli.sign { deploy_order.manifest = Some(sign_report(&cli.search_queries, &file_paths, &deploy_order)?); }
+                match cli.format {
+                    OutputFormat::Json => write_json(stdout.lock(), &deploy_order),
+                    _ => deploy_order.write(stdout.lock()),
+                }.context(stdout_error)?;
+            },
+            ReportKind::Crud | ReportKind::CrudByFile => {
+                let mut crud = Crud::new(&statements, report == ReportKind::CrudByFile);
+                if let Some(n) = cli.top { crud.top(n); }
+                if let Some(table) = &cli.drill_down { crud.drill_down(table, &statements); }
+                if cli.sign { crud.manifest = Some(sign_report(&cli.search_queries, &file_paths, &crud)?); }
+                match cli.format {
+                    OutputFormat::Json => write_json(stdout.lock(), &crud),
+                    _ => crud.write(stdout.lock()),
+                }.context(stdout_error)?;
+            },
+            ReportKind::CoOccurrence => {
+                let mut co_occurrence = CoOccurrence::new(&statements);
+                if cli.sign { co_occurrence.manifest = Some(sign_report(&cli.search_queries, &file_paths, &co_occurrence)?); }
+                match cli.format {
+                    OutputFormat::Json => write_json(stdout.lock(), &co_occurrence),
+                    _ => co_occurrence.write(stdout.lock()),
+                }.context(stdout_error)?;
+            },
+        }
+    } else if let Some(group_by) = cli.group_by {
+        let groups = Groups::new(&statements, group_by);
+        let stdout = std::io::stdout();
+        match cli.format {
+            OutputFormat::Json => write_json(stdout.lock(), &groups),
+            _ => groups.write(stdout.lock()),
+        }.context(stdout_error)?;
+    } else if cli.files_without_match {
         let stdout   = std::io::stdout();
         let mut lock = stdout.lock();
-        for f in matched_files.iter() {
-            writeln!(lock, "{}", f.display()).unwrap();
+        for f in unmatched_files {
+            let path = match &clipboard {
+                Some(clipboard) if f == clipboard.path() => CLIPBOARD_PATH.to_string(),
+                _ => f.display().to_string(),
+            };
+            writeln!(lock, "{}", if print_opts.color { color::path(&path) } else { path }).context(stdout_error)?;
+        }
+    } else {
+        if statements.is_empty() {
+            eprintln!("No statements found");
+        }
+        if print_opts.only_file_paths {
+            let stdout   = std::io::stdout();
+            let mut lock = stdout.lock();
+            for f in matched_files.iter() {
+                let path = f.display().to_string();
+                writeln!(lock, "{}", if print_opts.color { color::path(&path) } else { path }).context(stdout_error)?;
+            }
+        } else {
+            // an empty result set is still a valid result (e.g. for a shard)
+            print_statements(print_opts, &cli.search_queries, statements)?;
+        }
+    }
+
+    warn_unreadable(&unreadable_files);
+    if !cli.no_summary {
+        eprintln!("{}", summary);
+    }
+    if !over_budget.is_empty() {
+        for message in over_budget {
+            eprintln!("{}", message);
+        }
+        return Ok(exitcode::DATAERR);
+    }
+    // like grep, a file that couldn't be read (or an invalid glob) is an error
+    // even when others matched
+    match (unreadable_files.is_empty() && invalid_globs.is_empty(), matched) {
+        (false, _)    => Ok(error::ERROR),
+        (true, true)  => Ok(error::MATCHED),
+        (true, false) => Ok(error::NO_MATCH),
+    }
+}
+
+/// the scan of a file: whether it matched the prefilter, the statements found
+/// and the tables it declares, or the error if it couldn't be read
+type FileScan = std::io::Result<(bool, Vec<Statement>, Vec<String>)>;
+
+/// list the files that couldn't be read after the results, so that they aren't
+/// lost among them
+fn warn_unreadable<P: AsRef<Path>>(unreadable_files: &[(P, std::io::Error)]) {
+    if unreadable_files.is_empty() { return; }
+    eprintln!("Warning: {} files couldn't be read and weren't scanned:", thousands(unreadable_files.len()));
+    for (path, e) in unreadable_files {
+        eprintln!("  {}: {}", path.as_ref().display(), e);
+    }
+}
+
+/// the end of the summary of a scan that skipped invalid globs (each of which
+/// was reported as it was walked)
+fn skipped_globs(invalid_globs: &[&OsString]) -> String {
+    match invalid_globs.len() {
+        0 => String::new(),
+        n => format!(", skipping {} invalid globs: {}", thousands(n),
+            invalid_globs.iter().map(|glob| glob.to_string_lossy()).collect::<Vec<_>>().join(" ")),
+    }
+}
+
+/// the number of files that are scanned to time a scan for --estimate
+const ESTIMATE_SAMPLE: usize = 20;
+
+/// print the number and size of the files in scope, and an estimate of how
+/// long scanning them would take, timed by scanning a sample of them
+fn estimate_scan(file_paths: &[&PathBuf], search_queries: &[SearchQuery], scan_opts: &ScanOpts, threads: usize) {
+    let sizes: Vec<u64> = file_paths.iter()
+        .map(|path| std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0))
+        .collect();
+    let total_bytes: u64 = sizes.iter().sum();
+
+    // the sample is spread over the (sorted) files, rather than all being
+    // taken from the first directory
+    let step = (file_paths.len() / ESTIMATE_SAMPLE).max(1);
+    let mut searcher = Searcher::new();
+    let mut sampled_files = 0;
+    let mut sampled_bytes: u64 = 0;
+    let started = Instant::now();
+    for (path, size) in file_paths.iter().zip(&sizes).step_by(step).take(ESTIMATE_SAMPLE) {
+        if file_matches_queries(&mut searcher, path, search_queries, scan_opts.query_match) {
+            find_file_statements(path, search_queries, scan_opts);
         }
-        return;
+        sampled_files += 1;
+        sampled_bytes += size;
     }
+    let elapsed = started.elapsed().as_secs_f64();
+
+    // the sample is scanned by a single thread
+    let threads_used = match threads {
+        0 => rayon::current_num_threads(),
+        n => n,
+    };
+    let scale = match sampled_bytes {
+        0 => file_paths.len() as f64 / sampled_files.max(1) as f64,
+        _ => total_bytes as f64 / sampled_bytes as f64,
+    };
+    println!("Files:    {}", thousands(file_paths.len()));
+    println!("Size:     {}", human_bytes(total_bytes));
+    println!("Sample:   {} files ({}) scanned in {}", sampled_files, human_bytes(sampled_bytes), human_duration(elapsed));
+    let threads = match threads_used {
+        1 => String::from("1 thread"),
+        n => format!("{} threads", n),
+    };
+    println!("Estimate: {} with {}", human_duration(elapsed * scale / threads_used as f64), threads);
+}
 
-    print_statements(print_opts, statements);
+/// a number of bytes in the largest unit it has at least one of, e.g. 48.2 MB
+fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["bytes", "KB", "MB", "GB", "TB"];
+    let mut size = n as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} {}", n, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+/// a duration in seconds, in hours and minutes when it is that long, e.g.
+/// 24.1s, 3m 20s or 1h 04m
+fn human_duration(seconds: f64) -> String {
+    let whole = seconds.round() as u64;
+    match whole {
+        0..=59    => format!("{:.1}s", seconds),
+        60..=3599 => format!("{}m {:02}s", whole / 60, whole % 60),
+        _         => format!("{}h {:02}m", whole / 3600, whole % 3600 / 60),
+    }
+}
+
+/// a number with thousands separators, e.g. 1,204
+fn thousands(n: usize) -> String {
+    let digits: Vec<char> = n.to_string().chars().collect();
+    digits.rchunks(3)
+        .rev()
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_budgets() {
+        assert_eq!(parse_budget("12"), Ok((None, 12)));
+        assert_eq!(parse_budget("orders=12"), Ok((Some(String::from("orders")), 12)));
+        assert!(parse_budget("orders=").is_err());
+    }
+
+    #[test]
+    fn format_thousands() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1204), "1,204");
+        assert_eq!(thousands(1234567), "1,234,567");
+    }
+
+    #[test]
+    fn format_estimates() {
+        assert_eq!(human_bytes(812), "812 bytes");
+        assert_eq!(human_bytes(50_541_363), "48.2 MB");
+        assert_eq!(human_duration(24.06), "24.1s");
+        assert_eq!(human_duration(200.0), "3m 20s");
+        assert_eq!(human_duration(3840.0), "1h 04m");
+    }
+
+    #[test]
+    fn invert_matched_files() {
+        let file_paths: Vec<PathBuf> = ["a.sql", "b.sql", "c.sql", "d.sql"].iter().map(PathBuf::from).collect();
+        let statement = Statement {
+            file_path: PathBuf::from("b.sql"), statement_type: StatementType::Update, table: String::from("t_order"), schema: None,
+            begin: 0, end: 1, bytes: Default::default(), batch: 0, text: String::new(), keyword_span: None, table_spans: Vec::new(),
+            nested: false, partial: false, dynamic: false, captures: Default::default(), object: None, module: None,
+            header: None, queries: Vec::new(), tables: Vec::new(), note: None, commits: Vec::new(),
+        };
+        let unreadable = PathBuf::from("d.sql");
+        assert_eq!(files_without_match(&file_paths, &[statement], &[&unreadable]), vec![&file_paths[0], &file_paths[2]]);
+    }
+
+    #[test]
+    fn shards_partition_files() {
+        assert!(parse_shard("0/2").is_err());
+        assert!(parse_shard("3/2").is_err());
+        assert_eq!(parse_shard("2/3"), Ok((2, 3)));
+
+        let paths: Vec<PathBuf> = (0..50).map(|i| PathBuf::from(format!("usp_{}.sql", i))).collect();
+        for path in &paths {
+            let shards: Vec<u64> = (1..=3).filter(|&i| in_shard(path, (i, 3))).collect();
+            assert_eq!(shards.len(), 1);
+        }
+    }
 }
@@ -0,0 +1,92 @@
+// A manifest (`--sign`) records what a JSON document was produced from (the
+// scanned files, the queries and the version of gresql) together with the
+// SHA-256 of the document itself, so that an archived scan can later be
+// verified as untampered (`gresql verify`).
+use schemars::JsonSchema;
+use serde::{ Deserialize, Serialize };
+use serde_json::Value;
+use sha2::{ Digest, Sha256 };
+use std::io::{ Error, ErrorKind };
+use std::path::{ Path, PathBuf };
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Manifest {
+    pub version:        String,
+    pub queries:        Vec<String>,
+    pub inputs:         Vec<Input>,
+    /// the SHA-256 of the document, without its manifest
+    pub results_sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Input {
+    pub path:   PathBuf,
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn new<T: Serialize>(queries: &[String], file_paths: &[PathBuf], document: &T) -> Result<Manifest, Error> {
+        let mut inputs: Vec<Input> = Vec::new();
+        for path in file_paths {
+            inputs.push(Input { path: path.clone(), sha256: file_sha256(path)? });
+        }
+        Ok(Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            queries: queries.to_vec(),
+            inputs,
+            results_sha256: document_sha256(serde_json::to_value(document)?),
+        })
+    }
+
+    /// check a signed document against its manifest, returning what doesn't
+    /// match: the document itself, or the inputs that changed since
+    pub fn verify(document: Value) -> Result<Vec<String>, Error> {
+        let manifest: Manifest = match document.get("manifest") {
+            Some(manifest) => serde_json::from_value(manifest.clone())?,
+            None => return Err(Error::new(ErrorKind::InvalidData, "the document isn't signed")),
+        };
+        let mut problems: Vec<String> = Vec::new();
+        if document_sha256(document) != manifest.results_sha256 {
+            problems.push(String::from("the results don't match their manifest"));
+        }
+        for input in &manifest.inputs {
+            match file_sha256(&input.path) {
+                Ok(sha256) if sha256 == input.sha256 => {},
+                Ok(_)  => problems.push(format!("{} has changed", input.path.display())),
+                Err(e) => problems.push(format!("{} can't be read: {}", input.path.display(), e)),
+            }
+        }
+        Ok(problems)
+    }
+}
+
+fn file_sha256(path: &Path) -> Result<String, Error> {
+    Ok(hex::encode(Sha256::digest(std::fs::read(path)?)))
+}
+
+/// the hash of a document's (key-sorted) JSON without its manifest
+fn document_sha256(mut document: Value) -> String {
+    if let Some(object) = document.as_object_mut() {
+        object.remove("manifest");
+    }
+    hex::encode(Sha256::digest(document.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn verify_signed_documents() {
+        let document = json!({ "queries": ["u:t_order"], "statements": [] });
+        let manifest = Manifest::new(&[String::from("u:t_order")], &[], &document).unwrap();
+        let mut signed = document.clone();
+        signed["manifest"] = serde_json::to_value(&manifest).unwrap();
+        assert!(Manifest::verify(signed.clone()).unwrap().is_empty());
+
+        signed["queries"] = json!(["d:t_order"]);
+        assert_eq!(Manifest::verify(signed).unwrap(), vec!["the results don't match their manifest"]);
+        assert!(Manifest::verify(document).is_err());
+    }
+}
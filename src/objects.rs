@@ -0,0 +1,136 @@
+// The objects (procedures, triggers, ...) of a file, which contain most of the
+// statements of a T-SQL codebase. An object extends from its CREATE statement
+// to the end of its batch (GO) or the next object; statements outside of any
+// object are part of a script.
+use clap::ValueEnum;
+use regex::Regex;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ObjectKind {
+    #[value(name = "procedures")]
+    Procedure,
+    #[value(name = "triggers")]
+    Trigger,
+    #[value(name = "views")]
+    View,
+    #[value(name = "functions")]
+    Function,
+    /// statements outside of any object
+    #[value(name = "scripts")]
+    Script,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub kind:  ObjectKind,
+    /// the name of the object, without its schema or brackets
+    pub name:  String,
+    /// the (0-based) lines of the CREATE statement and the end of the object
+    pub begin: usize,
+    pub end:   usize,
+}
+
+pub fn parse_objects(text: &str) -> Vec<Object> {
+    let create_re = Regex::new(r"^(?i)create\s+(or\s+alter\s+)?(proc|procedure|function|trigger|view)\s+([\[\]\.\w]+)")
+        .expect("regex didn't compile");
+    let go_re = Regex::new(r"^(?i)go\b").expect("regex didn't compile");
+    let mut objects: Vec<Object> = Vec::new();
+    let mut current: Option<Object> = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if go_re.is_match(line) {
+            if let Some(mut object) = current.take() {
+                object.end = i;
+                objects.push(object);
+            }
+            continue;
+        }
+        let Some(captures) = create_re.captures(line) else { continue; };
+        if let Some(mut object) = current.take() {
+            object.end = i.saturating_sub(1);
+            objects.push(object);
+        }
+        let kind = match captures[2].to_lowercase().as_str() {
+            "function" => ObjectKind::Function,
+            "trigger"  => ObjectKind::Trigger,
+            "view"     => ObjectKind::View,
+            _          => ObjectKind::Procedure,
+        };
+        let name = captures[3].rsplit('.').next().unwrap_or_default().trim_matches(|c| c == '[' || c == ']');
+        current = Some(Object { kind, name: name.to_string(), begin: i, end: usize::MAX });
+    }
+    objects.extend(current);
+    objects
+}
+
+/// the object that contains the given (0-based) line, if any
+pub fn object_at(objects: &[Object], line: usize) -> Option<&Object> {
+    objects.iter().find(|object| object.begin <= line && line <= object.end)
+}
+
+/// the kind of object that contains the given line
+pub fn kind_at(objects: &[Object], line: usize) -> ObjectKind {
+    object_at(objects, line).map(|object| object.kind).unwrap_or(ObjectKind::Script)
+}
+
+/// a pattern for the names of objects (`--object-pattern usp_wave%`), written
+/// like the pattern of a LIKE: `%` matches any characters and `_` any one
+/// character. Names are matched ignoring case.
+#[derive(Debug, Clone)]
+pub struct ObjectPattern {
+    matcher: Regex,
+}
+
+impl ObjectPattern {
+    pub fn is_match(&self, name: &str) -> bool {
+        self.matcher.is_match(name)
+    }
+}
+
+impl FromStr for ObjectPattern {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<ObjectPattern, String> {
+        let regex: String = pattern.chars()
+            .map(|c| match c {
+                '%' => String::from(".*"),
+                '_' => String::from("."),
+                c   => regex::escape(&c.to_string()),
+            })
+            .collect();
+        Regex::new(&format!("^(?i:{})$", regex))
+            .map(|matcher| ObjectPattern { matcher })
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_objects_of_lines() {
+        let text = "\
+UPDATE t_order SET status = 1
+GO
+CREATE TRIGGER [dbo].[tr_order_update] ON t_order AFTER UPDATE AS
+UPDATE t_order_log SET x = 1
+GO
+DELETE FROM t_order_log
+CREATE PROCEDURE dbo.usp_wave_release AS
+UPDATE t_order SET status = 2
+";
+        let objects = parse_objects(text);
+        assert_eq!(kind_at(&objects, 0), ObjectKind::Script);
+        assert_eq!(kind_at(&objects, 3), ObjectKind::Trigger);
+        assert_eq!(object_at(&objects, 3).unwrap().name, "tr_order_update");
+        assert_eq!(kind_at(&objects, 5), ObjectKind::Script);
+        assert_eq!(kind_at(&objects, 7), ObjectKind::Procedure);
+        assert_eq!(object_at(&objects, 7).unwrap().name, "usp_wave_release");
+
+        let pattern: ObjectPattern = "usp_wave%".parse().unwrap();
+        assert!(pattern.is_match("USP_WAVE_RELEASE"));
+        assert!(!pattern.is_match("tr_order_update"));
+    }
+}
@@ -0,0 +1,230 @@
+// A policy declares which objects (or files) are allowed to write each of the
+// protected tables, e.g.
+//
+//   [[table]]
+//   name    = "t_order"
+//   writers = ["usp_order_*", "migrations/*.sql"]
+//   hint    = "orders are only changed through usp_order_update"
+//
+// `gresql policy check` reports every write to t_order by anything else.
+//
+// It can also declare folders that may only read, e.g.
+//
+//   [[read_only]]
+//   path    = "reports/**"
+//   schemas = ["dbo"]
+//
+// which makes every write under reports/ (to a table of the dbo schema) a
+// violation.
+use glob::{ MatchOptions, Pattern };
+use serde::{ Deserialize, Deserializer };
+use std::io::{ Error, ErrorKind };
+use std::path::Path;
+
+use crate::config::interpolate;
+use crate::objects::{ object_at, parse_objects };
+use crate::{ is_temp_object, Statement, StatementType };
+
+pub const DEFAULT_POLICY_FILE: &str = "gresql-policy.toml";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default, rename = "table")]
+    pub tables:    Vec<ProtectedTable>,
+    #[serde(default)]
+    pub read_only: Vec<ReadOnlyPath>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtectedTable {
+    pub name:    String,
+    /// glob patterns of the objects (procedures, triggers, ...) or the file
+    /// paths that may write the table
+    #[serde(default, deserialize_with = "deserialize_patterns")]
+    pub writers: Vec<Pattern>,
+    /// how to fix a violation, e.g. which procedure to call instead
+    #[serde(default)]
+    pub hint:    Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReadOnlyPath {
+    /// glob pattern of the file paths that may only read
+    #[serde(deserialize_with = "deserialize_pattern")]
+    pub path:    Pattern,
+    /// the schemas that may only be read, or every schema if there are none.
+    /// A table without a schema may be in any of them.
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default)]
+    pub hint:    Option<String>,
+}
+
+fn deserialize_pattern<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+    Pattern::new(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_patterns<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Pattern>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|p| Pattern::new(p).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// a write to a protected table by an object that isn't one of its writers
+pub struct Violation<'a> {
+    pub statement: &'a Statement,
+    /// the object containing the statement, if it is in one
+    pub object:    Option<String>,
+    pub rule:      Rule<'a>,
+}
+
+/// the part of the policy that a violation breaks
+pub enum Rule<'a> {
+    Table(&'a ProtectedTable),
+    ReadOnly(&'a ReadOnlyPath),
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Policy, Error> {
+        let text = interpolate(&std::fs::read_to_string(path)?)?;
+        toml::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        self.tables.iter().map(|t| t.name.clone()).collect()
+    }
+
+    /// the writes among the statements of a file (with the given text) that
+    /// aren't allowed by the policy
+    pub fn violations<'a>(&'a self, text: &str, statements: &'a [Statement]) -> Vec<Violation<'a>> {
+        let objects = parse_objects(text);
+        let mut violations: Vec<Violation> = Vec::new();
+        for statement in statements.iter().filter(|s| !s.nested && StatementType::writes().contains(&s.statement_type)) {
+            let object = object_at(&objects, statement.begin).map(|object| object.name.clone());
+            if let Some(read_only) = self.read_only.iter().find(|r| r.forbids(statement)) {
+                violations.push(Violation { statement, object, rule: Rule::ReadOnly(read_only) });
+                continue;
+            }
+            let Some(table) = self.tables.iter().find(|t| t.name == statement.table) else { continue; };
+            if !table.allows(&statement.file_path, object.as_deref()) {
+                violations.push(Violation { statement, object, rule: Rule::Table(table) });
+            }
+        }
+        violations
+    }
+}
+
+impl ProtectedTable {
+    fn allows(&self, path: &Path, object: Option<&str>) -> bool {
+        let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+        let path = path.strip_prefix(".").unwrap_or(path);
+        self.writers.iter().any(|writer| {
+            writer.matches_path_with(path, options)
+                || object.is_some_and(|object| writer.matches_with(object, options))
+        })
+    }
+}
+
+impl ReadOnlyPath {
+    fn forbids(&self, statement: &Statement) -> bool {
+        let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+        let path = statement.file_path.strip_prefix(".").unwrap_or(&statement.file_path);
+        // temporary tables and table variables don't outlive the code that
+        // writes them
+        if !self.path.matches_path_with(path, options) || is_temp_object(&statement.table) {
+            return false;
+        }
+        match &statement.schema {
+            Some(schema) if !self.schemas.is_empty() => self.schemas.iter().any(|s| s.eq_ignore_ascii_case(schema)),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ByteSpan;
+    use std::path::PathBuf;
+
+    fn statement(file_path: &str, begin: usize) -> Statement {
+        Statement {
+            file_path: PathBuf::from(file_path),
+            statement_type: StatementType::Update,
+            table:     String::from("t_order"),
+            schema:    None,
+            begin,
+            end:       begin + 1,
+            bytes:     ByteSpan::default(),
+            batch:     0,
+            text:      String::new(),
+            keyword_span: None,
+            table_spans:  Vec::new(),
+            nested:    false,
+            partial:   false,
+            dynamic:   false,
+            captures:  Default::default(),
+            object:    None,
+            module:    None,
+            header:    None,
+            queries:   Vec::new(),
+            tables:    Vec::new(),
+            note:      None,
+            commits:   Vec::new(),
+        }
+    }
+
+    #[test]
+    fn report_disallowed_writers() {
+        let policy: Policy = toml::from_str(r#"
+            [[table]]
+            name    = "t_order"
+            writers = ["usp_order_*", "migrations/*.sql"]
+        "#).unwrap();
+        let text = "\
+UPDATE t_order SET status = 1
+CREATE PROCEDURE [dbo].[usp_order_cancel] AS
+UPDATE t_order SET status = 9
+GO
+CREATE PROCEDURE dbo.usp_wave_release AS
+UPDATE t_order SET status = 2
+";
+        let statements = vec![statement("procs.sql", 0), statement("procs.sql", 2), statement("procs.sql", 5)];
+        let violations = policy.violations(text, &statements);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].object, None);
+        assert_eq!(violations[1].object.as_deref(), Some("usp_wave_release"));
+
+        let statements = vec![statement("migrations/001_orders.sql", 0)];
+        assert!(policy.violations(text, &statements).is_empty());
+    }
+
+    #[test]
+    fn report_writes_under_read_only_paths() {
+        let policy: Policy = toml::from_str(r#"
+            [[read_only]]
+            path    = "reports/**"
+            schemas = ["dbo"]
+        "#).unwrap();
+        let write = |file_path: &str, schema: Option<&str>, table: &str, text: &str| Statement {
+            table:  String::from(table),
+            schema: schema.map(String::from),
+            text:   String::from(text),
+            ..statement(file_path, 0)
+        };
+        let statements = vec![
+            write("reports/orders.sql", None, "t_order", "UPDATE t_order SET status = 1"),
+            write("reports/orders.sql", Some("dbo"), "t_order", "UPDATE [dbo].[t_order] SET status = 1"),
+            write("reports/orders.sql", Some("scratch"), "t_report", "INSERT INTO scratch.t_report (id) VALUES (1)"),
+            write("reports/orders.sql", None, "#orders", "INSERT INTO #orders (id) VALUES (1)"),
+            write("procs/orders.sql", Some("dbo"), "t_order", "UPDATE dbo.t_order SET status = 1"),
+        ];
+        let violations = policy.violations("", &statements);
+        assert_eq!(violations.iter().map(|v| v.statement.text.as_str()).collect::<Vec<&str>>(),
+            vec!["UPDATE t_order SET status = 1", "UPDATE [dbo].[t_order] SET status = 1"]);
+    }
+}
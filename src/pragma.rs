@@ -0,0 +1,100 @@
+// Pragmas are comments that acknowledge known exceptions in the code itself,
+// e.g.
+//
+//   -- gresql:ignore d:t_order
+//   DELETE FROM t_order WHERE status = 9
+//
+// silences the matches of `d:t_order` queries for the following statement.
+use regex::Regex;
+
+use crate::{ parse_search_queries, SearchQuery, Statement };
+
+pub struct Pragma {
+    /// the (0-based) line at which the ignored statement begins
+    line:    usize,
+    /// the queries whose matches are ignored, or every match if empty
+    queries: Vec<SearchQuery>,
+}
+
+/// parse the `gresql:ignore` pragmas of a file. A pragma applies to the
+/// statement on its own line (after the code) or to the next statement.
+pub fn parse_pragmas(text: &str) -> Vec<Pragma> {
+    let pragma_re = Regex::new(r"--\s*gresql:ignore\b(.*)$").expect("regex didn't compile");
+    let lines: Vec<&str> = text.lines().collect();
+    let mut pragmas: Vec<Pragma> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(captures) = pragma_re.captures(line) else { continue; };
+        let queries: Vec<String> = captures[1].split_whitespace().map(String::from).collect();
+        let code = line[..captures.get(0).unwrap().start()].trim();
+        let target = match code.is_empty() {
+            true => lines.iter()
+                .enumerate()
+                .skip(i + 1)
+                .find(|(_, l)| !l.trim().is_empty() && !l.trim().starts_with("--"))
+                .map(|(j, _)| j),
+            false => Some(i),
+        };
+        if let Some(line) = target {
+            pragmas.push(Pragma { line, queries: parse_search_queries(&queries) });
+        }
+    }
+    pragmas
+}
+
+/// whether the match of a statement is silenced by one of the pragmas
+pub fn is_ignored(pragmas: &[Pragma], statement: &Statement) -> bool {
+    pragmas.iter()
+        .filter(|pragma| pragma.line == statement.begin)
+        .any(|pragma| pragma.queries.is_empty() || pragma.queries.iter().any(|query| query.matches(statement)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ ByteSpan, StatementType };
+    use std::path::PathBuf;
+
+    fn statement(statement_type: StatementType, table: &str, begin: usize) -> Statement {
+        Statement {
+            file_path: PathBuf::from("a.sql"),
+            statement_type,
+            table:     String::from(table),
+            schema:    None,
+            begin,
+            end:       begin + 1,
+            bytes:     ByteSpan::default(),
+            batch:     0,
+            text:      String::new(),
+            keyword_span: None,
+            table_spans:  Vec::new(),
+            nested:    false,
+            partial:   false,
+            dynamic:   false,
+            captures:  Default::default(),
+            object:    None,
+            module:    None,
+            header:    None,
+            queries:   Vec::new(),
+            tables:    Vec::new(),
+            note:      None,
+            commits:   Vec::new(),
+        }
+    }
+
+    #[test]
+    fn ignore_statements_after_pragmas() {
+        let text = "\
+-- gresql:ignore d:t_order
+
+-- purged nightly
+DELETE FROM t_order WHERE status = 9
+DELETE FROM t_order_detail WHERE status = 9 -- gresql:ignore
+DELETE FROM t_order WHERE status = 8
+";
+        let pragmas = parse_pragmas(text);
+        assert!(is_ignored(&pragmas, &statement(StatementType::Delete, "t_order", 3)));
+        assert!(!is_ignored(&pragmas, &statement(StatementType::Select, "t_order", 3)));
+        assert!(is_ignored(&pragmas, &statement(StatementType::Delete, "t_order_detail", 4)));
+        assert!(!is_ignored(&pragmas, &statement(StatementType::Delete, "t_order", 5)));
+    }
+}
@@ -0,0 +1,195 @@
+// Search queries built in Rust rather than written in the command line syntax,
+// e.g. `Query::writes_to("t_order").or(Query::deletes_from("t_order_detail"))`
+// is `-s '*:t_order' -s d:t_order_detail --match any`. A query compiles to the
+// same search queries as its command line, so both search the same way.
+use std::fmt;
+
+use crate::{ parse_search_queries, QueryMatch, SearchQuery, StatementType };
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// the statements of some types to any of the tables (or to any table if
+    /// there are none). Tables are written as on the command line, so a
+    /// `/regex/` matches every table whose name it matches.
+    Match {
+        label:           Option<String>,
+        statement_types: Vec<StatementType>,
+        tables:          Vec<String>,
+        /// the columns that the statements have to write (`@order_status`)
+        columns:         Vec<String>,
+    },
+    /// files that match every query
+    All(Vec<Query>),
+    /// files that match at least one of the queries
+    Any(Vec<Query>),
+}
+
+impl Query {
+    pub fn new(statement_types: &[StatementType], tables: &[&str]) -> Query {
+        Query::Match {
+            label:           None,
+            statement_types: statement_types.to_vec(),
+            tables:          tables.iter().map(|table| table.to_string()).collect(),
+            columns:         Vec::new(),
+        }
+    }
+
+    pub fn reads(table: &str) -> Query {
+        Query::new(&[StatementType::Select], &[table])
+    }
+    /// every statement that modifies the table (`*`)
+    pub fn writes_to(table: &str) -> Query {
+        Query::new(&StatementType::writes(), &[table])
+    }
+    pub fn inserts_into(table: &str) -> Query {
+        Query::new(&[StatementType::Insert], &[table])
+    }
+    pub fn updates(table: &str) -> Query {
+        Query::new(&[StatementType::Update], &[table])
+    }
+    pub fn deletes_from(table: &str) -> Query {
+        Query::new(&[StatementType::Delete], &[table])
+    }
+    pub fn merges_into(table: &str) -> Query {
+        Query::new(&[StatementType::Merge], &[table])
+    }
+    pub fn truncates(table: &str) -> Query {
+        Query::new(&[StatementType::Truncate], &[table])
+    }
+    pub fn drops(table: &str) -> Query {
+        Query::new(&[StatementType::Drop], &[table])
+    }
+    pub fn alters(table: &str) -> Query {
+        Query::new(&[StatementType::Alter], &[table])
+    }
+    pub fn executes(procedure: &str) -> Query {
+        Query::new(&[StatementType::Exec], &[procedure])
+    }
+
+    /// name the query (`LABEL=QUERY`). Only a single query has a label, the
+    /// queries that are combined keep their own.
+    pub fn label(self, label: &str) -> Query {
+        match self {
+            Query::Match { statement_types, tables, columns, .. } => Query::Match {
+                label: Some(label.to_string()),
+                statement_types,
+                tables,
+                columns,
+            },
+            query => query,
+        }
+    }
+
+    /// only match the statements that write any of the columns, e.g.
+    /// `Query::updates("t_order").columns(&["order_status"])`
+    pub fn columns(self, columns: &[&str]) -> Query {
+        match self {
+            Query::Match { label, statement_types, tables, .. } => Query::Match {
+                label,
+                statement_types,
+                tables,
+                columns: columns.iter().map(|column| column.to_string()).collect(),
+            },
+            query => query,
+        }
+    }
+
+    pub fn or(self, other: Query) -> Query {
+        match self {
+            Query::Any(mut queries) => { queries.push(other); Query::Any(queries) }
+            query => Query::Any(vec![query, other]),
+        }
+    }
+
+    pub fn and(self, other: Query) -> Query {
+        match self {
+            Query::All(mut queries) => { queries.push(other); Query::All(queries) }
+            query => Query::All(vec![query, other]),
+        }
+    }
+
+    /// the search queries and the `--match` of the query. Queries that
+    /// combine `and` and `or` can't be written on the command line, so
+    /// neither can they be compiled.
+    pub fn compile(&self) -> Result<(Vec<SearchQuery>, QueryMatch), String> {
+        let (strings, query_match) = match self {
+            Query::Match { .. } => (vec![self.to_string()], QueryMatch::All),
+            Query::All(queries) => (match_strings(queries)?, QueryMatch::All),
+            Query::Any(queries) => (match_strings(queries)?, QueryMatch::Any),
+        };
+        let search_queries = parse_search_queries(&strings);
+        if search_queries.len() != strings.len() {
+            return Err(format!("Invalid query: {}", self));
+        }
+        Ok((search_queries, query_match))
+    }
+}
+
+fn match_strings(queries: &[Query]) -> Result<Vec<String>, String> {
+    queries.iter()
+        .map(|query| match query {
+            Query::Match { .. } => Ok(query.to_string()),
+            _ => Err(String::from("Queries that combine `and` and `or` aren't supported")),
+        })
+        .collect()
+}
+
+/// the query in the command line syntax, e.g. `orders=ud:t_order`. Combined
+/// queries are separated by spaces.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Match { label, statement_types, tables, columns } => {
+                if let Some(label) = label {
+                    write!(f, "{}=", label)?;
+                }
+                let mut writes = StatementType::writes();
+                let mut types = statement_types.clone();
+                for types in [&mut writes, &mut types] {
+                    types.sort_by_key(|t| char::from(*t));
+                    types.dedup();
+                }
+                if types == writes {
+                    write!(f, "*")?;
+                } else {
+                    write!(f, "{}", types.iter().map(|t| char::from(*t)).collect::<String>())?;
+                }
+                write!(f, ":{}", tables.join(","))?;
+                if !columns.is_empty() {
+                    write!(f, "@{}", columns.join(","))?;
+                }
+                Ok(())
+            }
+            Query::All(queries) | Query::Any(queries) => {
+                let strings: Vec<String> = queries.iter().map(|query| query.to_string()).collect();
+                write!(f, "{}", strings.join(" "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_queries() {
+        let query = Query::writes_to("t_order").columns(&["order_status"])
+            .or(Query::deletes_from("t_order_detail").label("details"))
+            .or(Query::new(&[StatementType::Update, StatementType::Insert], &["t_order_log", "/t_order_\\d+/"]));
+        let (search_queries, query_match) = query.compile().unwrap();
+        let strings = [
+            String::from("*:t_order@order_status"),
+            String::from("details=d:t_order_detail"),
+            String::from("iu:t_order_log,/t_order_\\d+/"),
+        ];
+        assert_eq!(query_match, QueryMatch::Any);
+        for (compiled, parsed) in search_queries.iter().zip(parse_search_queries(&strings)) {
+            assert_eq!(format!("{:?}", compiled), format!("{:?}", parsed));
+        }
+        assert_eq!(search_queries.len(), 3);
+
+        assert_eq!(Query::reads("t_order").and(Query::updates("t_order")).compile().unwrap().1, QueryMatch::All);
+        assert!(Query::reads("t_order").or(Query::reads("a").and(Query::reads("b"))).compile().is_err());
+    }
+}
@@ -0,0 +1,793 @@
+// Reports summarize the matched statements (and the scanned files) instead of
+// printing each statement.
+use clap::ValueEnum;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{ BTreeMap, BTreeSet, HashMap, HashSet };
+use std::io::{ Error, Write };
+use std::path::PathBuf;
+
+use crate::handler;
+use crate::manifest::Manifest;
+use crate::{ is_temp_object, normalize_name, split_schema, table_matches, Access, Statement, StatementType };
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ReportKind {
+    /// statement sizes, table fan-out and procedures per file
+    Profile,
+    /// the tables each script writes and reads, in execution order
+    DeployOrder,
+    /// the number of statements of each type on each table
+    Crud,
+    /// the number of statements of each type on each table, file by file
+    CrudByFile,
+    /// the pairs of tables written by the same statements and objects, the
+    /// most frequent first
+    CoOccurrence,
+}
+
+// profile ====================================================================
+const LENGTH_BUCKETS: [(usize, usize); 7] = [
+    (1, 1), (2, 5), (6, 10), (11, 25), (26, 50), (51, 100), (101, usize::MAX),
+];
+
+// a table following one of the keywords that introduce tables, with its alias
+const TABLE_PATTERN: &str = r"\b(?i:(from|join|into|update|merge|using))\s+([@#\[\]\.\w]+)(?:\s+(?i:as\s+)?(\w+))?";
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct LengthBucket {
+    lines:      String,
+    statements: usize,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct Profile {
+    files:                    usize,
+    statements:               usize,
+    statement_types:          BTreeMap<String, usize>,
+    statement_lines:          Vec<LengthBucket>,
+    median_lines:             usize,
+    max_lines:                usize,
+    avg_tables:               f64,
+    max_tables:               usize,
+    avg_procedures:           f64,
+    max_procedures:           usize,
+    files_without_procedures: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest:             Option<Manifest>,
+}
+
+/// the number of distinct tables referenced by a statement's text, not
+/// counting aliases (e.g. the `o` of `UPDATE o ... FROM t_order o`)
+fn statement_tables(re: &Regex, text: &str) -> usize {
+    const KEYWORDS: [&str; 14] = [
+        "as", "on", "set", "where", "inner", "left", "right", "full", "cross",
+        "join", "with", "select", "values", "output",
+    ];
+    let mut names: HashSet<String> = HashSet::new();
+    let mut aliases: HashSet<String> = HashSet::new();
+    for captures in re.captures_iter(text) {
+        names.insert(captures[2].to_lowercase());
+        if let Some(alias) = captures.get(3) {
+            let alias = alias.as_str().to_lowercase();
+            if !KEYWORDS.contains(&alias.as_str()) { aliases.insert(alias); }
+        }
+    }
+    names.difference(&aliases).count()
+}
+
+impl Profile {
+    pub fn new(file_paths: &[PathBuf], statements: &[Statement]) -> Profile {
+        let table_re = Regex::new(TABLE_PATTERN).expect("regex didn't compile");
+        let procedure_re = Regex::new(r"(?im)^\s*create\s+(or\s+alter\s+)?proc(edure)?\b")
+            .expect("regex didn't compile");
+
+        // reads by subqueries are reported separately from their statement,
+        // but the profile is about the statements themselves
+        let statements: Vec<&Statement> = statements.iter().filter(|s| !s.nested).collect();
+        let mut profile = Profile {
+            files:      file_paths.len(),
+            statements: statements.len(),
+            ..Profile::default()
+        };
+
+        // the end of a statement is the line that terminated it
+        let mut lines: Vec<usize> = statements.iter().map(|s| (s.end - s.begin).max(1)).collect();
+        lines.sort();
+        profile.median_lines = lines.get(lines.len() / 2).copied().unwrap_or(0);
+        profile.max_lines    = lines.last().copied().unwrap_or(0);
+        for (min, max) in LENGTH_BUCKETS {
+            let lines_label = match (min, max) {
+                (min, usize::MAX)        => format!("{}+", min),
+                (min, max) if min == max => min.to_string(),
+                (min, max)               => format!("{}-{}", min, max),
+            };
+            let count = lines.iter().filter(|&&l| l >= min && l <= max).count();
+            profile.statement_lines.push(LengthBucket { lines: lines_label, statements: count });
+        }
+
+        let mut total_tables = 0;
+        for statement in &statements {
+            *profile.statement_types.entry(statement.statement_type.to_string()).or_insert(0) += 1;
+            let tables = statement_tables(&table_re, &statement.text);
+            total_tables += tables;
+            profile.max_tables = profile.max_tables.max(tables);
+        }
+        if !statements.is_empty() {
+            profile.avg_tables = total_tables as f64 / statements.len() as f64;
+        }
+
+        let mut total_procedures = 0;
+        for path in file_paths {
+            let procedures = match handler::read_to_string(path) {
+                Ok(text) => procedure_re.find_iter(&text).count(),
+                Err(_) => continue,
+            };
+            total_procedures += procedures;
+            profile.max_procedures = profile.max_procedures.max(procedures);
+            if procedures == 0 { profile.files_without_procedures += 1; }
+        }
+        if !file_paths.is_empty() {
+            profile.avg_procedures = total_procedures as f64 / file_paths.len() as f64;
+        }
+        profile
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), Error> {
+        let types = self.statement_types.iter()
+            .map(|(t, n)| format!("{} {}", t, n))
+            .collect::<Vec<String>>()
+            .join(", ");
+        writeln!(w, "Files:                {}", self.files)?;
+        writeln!(w, "Statements:           {} ({})", self.statements, types)?;
+        writeln!(w, "Statement lines:      median {}, max {}", self.median_lines, self.max_lines)?;
+        let widest = self.statement_lines.iter().map(|b| b.statements).max().unwrap_or(0).max(1);
+        for bucket in &self.statement_lines {
+            let bar = "#".repeat((bucket.statements * 40).div_ceil(widest));
+            writeln!(w, "  {:>8} {:>8} {}", bucket.lines, bucket.statements, bar)?;
+        }
+        writeln!(w, "Tables per statement: {:.2} (max {})", self.avg_tables, self.max_tables)?;
+        writeln!(w, "Procedures per file:  {:.2} (max {}, {} files without a procedure)",
+            self.avg_procedures, self.max_procedures, self.files_without_procedures)?;
+        Ok(())
+    }
+}
+// ============================================================================
+
+// deploy order ===============================================================
+// a part of a file name: numbers are ordered by value, so that `2_x.sql` runs
+// before `10_x.sql`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum NamePart {
+    Number(u64),
+    Text(String),
+}
+
+fn execution_key(path: &std::path::Path) -> Vec<NamePart> {
+    let name = path.to_string_lossy().to_lowercase();
+    let mut parts: Vec<NamePart> = Vec::new();
+    let mut chars = name.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let is_digit = c.is_ascii_digit();
+        let mut part = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_ascii_digit() != is_digit { break; }
+            part.push(c);
+            chars.next();
+        }
+        parts.push(match is_digit {
+            true  => NamePart::Number(part.parse().unwrap_or(u64::MAX)),
+            false => NamePart::Text(part),
+        });
+    }
+    parts
+}
+
+/// the tables that a statement reads, found in its text so that the sources of
+/// e.g. an `INSERT ... SELECT` count too
+fn statement_reads(re: &Regex, statement: &Statement) -> Vec<String> {
+    let mut reads: Vec<String> = Vec::new();
+    if statement.statement_type == StatementType::Select { reads.push(statement.table.clone()); }
+    for captures in re.captures_iter(&statement.text) {
+        if !matches!(captures[1].to_lowercase().as_str(), "from" | "join" | "using") { continue; }
+        let name = captures[2].rsplit('.').next().unwrap_or_default().trim_matches(|c| c == '[' || c == ']');
+        if name.is_empty() || name.eq_ignore_ascii_case(&statement.table) { continue; }
+        if !reads.iter().any(|t| t.eq_ignore_ascii_case(name)) { reads.push(name.to_string()); }
+    }
+    reads
+}
+
+/// a table that a script reads before the first script that writes it runs
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+struct Hazard {
+    table:      String,
+    written_by: PathBuf,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct Script {
+    path:    PathBuf,
+    writes:  Vec<String>,
+    reads:   Vec<String>,
+    hazards: Vec<Hazard>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DeployOrder {
+    scripts:      Vec<Script>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+}
+
+impl DeployOrder {
+    pub fn new(file_paths: &[PathBuf], statements: &[Statement]) -> DeployOrder {
+        let mut file_paths: Vec<&PathBuf> = file_paths.iter().collect();
+        file_paths.sort_by_key(|path| execution_key(path));
+
+        let table_re = Regex::new(TABLE_PATTERN).expect("regex didn't compile");
+        // variables and temporary tables don't outlive a script
+        let is_table = |table: &String| !is_temp_object(table);
+        let push_table = |tables: &mut Vec<String>, table: String| {
+            if !tables.iter().any(|t| t.eq_ignore_ascii_case(&table)) { tables.push(table); }
+        };
+        let mut scripts: Vec<Script> = file_paths.into_iter().map(|path| {
+            let mut script = Script { path: path.clone(), writes: Vec::new(), reads: Vec::new(), hazards: Vec::new() };
+            for statement in statements.iter().filter(|s| &s.file_path == path) {
+                if StatementType::writes().contains(&statement.statement_type) && is_table(&statement.table) {
+                    push_table(&mut script.writes, statement.table.clone());
+                }
+                for table in statement_reads(&table_re, statement).into_iter().filter(is_table) {
+                    push_table(&mut script.reads, table);
+                }
+            }
+            script
+        }).collect();
+
+        for i in 0..scripts.len() {
+            let hazards: Vec<Hazard> = scripts[i].reads.iter()
+                .filter(|table| !scripts[..=i].iter().any(|s| s.writes.iter().any(|t| t.eq_ignore_ascii_case(table))))
+                .filter_map(|table| scripts[i + 1..].iter()
+                    .find(|s| s.writes.iter().any(|t| t.eq_ignore_ascii_case(table)))
+                    .map(|s| Hazard { table: table.clone(), written_by: s.path.clone() }))
+                .collect();
+            scripts[i].hazards = hazards;
+        }
+        DeployOrder { scripts, manifest: None }
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), Error> {
+        for (i, script) in self.scripts.iter().enumerate() {
+            writeln!(w, "{:>3}. {}", i + 1, script.path.display())?;
+            if !script.writes.is_empty() {
+                writeln!(w, "     writes: {}", script.writes.join(", "))?;
+            }
+            if !script.reads.is_empty() {
+                writeln!(w, "     reads:  {}", script.reads.join(", "))?;
+            }
+            for hazard in &script.hazards {
+                writeln!(w, "     hazard: reads {} before {} writes it", hazard.table, hazard.written_by.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+// ============================================================================
+
+// crud =======================================================================
+/// the statements of each type on a table, in the order of the types of the
+/// report
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+struct CrudRow {
+    table:  String,
+    counts: Vec<usize>,
+}
+
+impl CrudRow {
+    fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct CrudMatrix {
+    /// none for the matrix of the whole tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_path: Option<PathBuf>,
+    tables:    Vec<CrudRow>,
+    /// the number of tables left out of the matrix by `--top`
+    #[serde(skip_serializing_if = "is_zero")]
+    omitted:   usize,
+}
+
+/// a statement on the table of a drill-down
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+struct CrudMatch {
+    file_path:      PathBuf,
+    /// the line the statement begins on, counted from 1
+    line:           usize,
+    statement_type: StatementType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object:         Option<String>,
+}
+
+/// every statement on one table, in the order of the files
+#[derive(Debug, Serialize, JsonSchema)]
+struct CrudDrillDown {
+    table:      String,
+    statements: Vec<CrudMatch>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// the number of statements of each type on each table (the reads of
+/// subqueries count as SELECTs), across the tree or file by file
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Crud {
+    /// the columns of the matrices: the types of the statements found
+    statement_types: Vec<StatementType>,
+    matrices:        Vec<CrudMatrix>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    drill_down:      Option<CrudDrillDown>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest:    Option<Manifest>,
+}
+
+impl Crud {
+    pub fn new(statements: &[Statement], by_file: bool) -> Crud {
+        let statement_types: Vec<StatementType> = StatementType::all().into_iter()
+            .filter(|t| statements.iter().any(|s| s.statement_type == *t))
+            .collect();
+        let mut file_paths: Vec<Option<&PathBuf>> = Vec::new();
+        for statement in statements {
+            let file_path = by_file.then_some(&statement.file_path);
+            if !file_paths.contains(&file_path) { file_paths.push(file_path); }
+        }
+        file_paths.sort();
+
+        let matrices = file_paths.into_iter().map(|file_path| {
+            // tables are compared ignoring case, and named as first found
+            let mut tables: BTreeMap<String, CrudRow> = BTreeMap::new();
+            for statement in statements.iter().filter(|s| file_path.is_none_or(|path| &s.file_path == path)) {
+                let row = tables.entry(statement.table.to_lowercase()).or_insert_with(|| CrudRow {
+                    table:  statement.table.clone(),
+                    counts: vec![0; statement_types.len()],
+                });
+                if let Some(i) = statement_types.iter().position(|t| *t == statement.statement_type) {
+                    row.counts[i] += 1;
+                }
+            }
+            CrudMatrix { file_path: file_path.cloned(), tables: tables.into_values().collect(), omitted: 0 }
+        }).collect();
+        Crud { statement_types, matrices, drill_down: None, manifest: None }
+    }
+
+    /// keep the `n` tables of each matrix with the most statements, the most
+    /// first (and the first by name of those with as many)
+    pub fn top(&mut self, n: usize) {
+        for matrix in &mut self.matrices {
+            matrix.tables.sort_by_key(|row| std::cmp::Reverse(row.total()));
+            matrix.omitted = matrix.tables.len().saturating_sub(n);
+            matrix.tables.truncate(n);
+        }
+    }
+
+    /// list every statement on a table (ignoring case), whether or not it's
+    /// in the matrices
+    pub fn drill_down(&mut self, table: &str, statements: &[Statement]) {
+        let table = table.to_lowercase();
+        let mut matches: Vec<&Statement> = statements.iter().filter(|s| s.table.to_lowercase() == table).collect();
+        matches.sort_by_key(|s| (&s.file_path, s.begin));
+        self.drill_down = Some(CrudDrillDown {
+            table:      matches.first().map_or(table, |s| s.table.clone()),
+            statements: matches.into_iter().map(|s| CrudMatch {
+                file_path:      s.file_path.clone(),
+                line:           s.begin + 1,
+                statement_type: s.statement_type,
+                object:         s.object.clone(),
+            }).collect(),
+        });
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), Error> {
+        let headers: Vec<String> = self.statement_types.iter().map(|t| t.to_string()).collect();
+        let width = self.matrices.iter().flat_map(|m| &m.tables)
+            .map(|row| row.table.chars().count())
+            .max().unwrap_or(0).max("table".len());
+        for matrix in &self.matrices {
+            let indent = match &matrix.file_path {
+                Some(path) => {
+                    writeln!(w, "{}", path.display())?;
+                    "  "
+                },
+                None => "",
+            };
+            write!(w, "{}{:<width$}", indent, "table", width = width)?;
+            for header in &headers {
+                write!(w, " {:>w$}", header, w = header.len().max(3))?;
+            }
+            writeln!(w)?;
+            for row in &matrix.tables {
+                write!(w, "{}{:<width$}", indent, row.table, width = width)?;
+                for (header, count) in headers.iter().zip(&row.counts) {
+                    let count = match count {
+                        0 => String::from("-"),
+                        n => n.to_string(),
+                    };
+                    write!(w, " {:>w$}", count, w = header.len().max(3))?;
+                }
+                writeln!(w)?;
+            }
+            if matrix.omitted > 0 {
+                let s = if matrix.omitted == 1 { "" } else { "s" };
+                writeln!(w, "{}({} more table{})", indent, matrix.omitted, s)?;
+            }
+        }
+        if let Some(drill_down) = &self.drill_down {
+            writeln!(w)?;
+            writeln!(w, "{}: {} statements", drill_down.table, drill_down.statements.len())?;
+            for m in &drill_down.statements {
+                write!(w, "  {}:{} {}", m.file_path.display(), m.line, m.statement_type)?;
+                match &m.object {
+                    Some(object) => writeln!(w, " ({})", object)?,
+                    None => writeln!(w)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+// ============================================================================
+
+// co-occurrence ==============================================================
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+struct TablePair {
+    table_a:    String,
+    table_b:    String,
+    /// the number of statements that write both tables
+    statements: usize,
+    /// the number of objects (or scripts outside of any object) that write
+    /// both tables
+    objects:    usize,
+}
+
+/// the pairs of tables that are written together, by the same statement (e.g.
+/// a partition switch) or the same procedure, the most frequent first, which
+/// shows the coupling of tables that a split of the schema would break
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CoOccurrence {
+    pairs:        Vec<TablePair>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+}
+
+impl CoOccurrence {
+    pub fn new(statements: &[Statement]) -> CoOccurrence {
+        // tables are compared ignoring case, and named as first found
+        let mut names: HashMap<String, &str> = HashMap::new();
+        let mut by_statement: HashMap<(&PathBuf, usize), BTreeSet<String>> = HashMap::new();
+        let mut by_object: HashMap<(&PathBuf, Option<&str>), BTreeSet<String>> = HashMap::new();
+        // the "table" of a SEND is its message type, and temporary tables
+        // couple nothing
+        let writes = statements.iter().filter(|s| {
+            s.access() == Access::Write && !s.partial && s.statement_type != StatementType::Send && !is_temp_object(&s.table)
+        });
+        for statement in writes {
+            let table = statement.table.to_lowercase();
+            names.entry(table.clone()).or_insert(&statement.table);
+            by_statement.entry((&statement.file_path, statement.begin)).or_default().insert(table.clone());
+            by_object.entry((&statement.file_path, statement.object.as_deref())).or_default().insert(table);
+        }
+        let mut counts: BTreeMap<(&str, &str), (usize, usize)> = BTreeMap::new();
+        let mut count = |tables: &BTreeSet<String>, object: bool| {
+            let tables: Vec<&str> = tables.iter().map(|table| names[table]).collect();
+            for (i, table_a) in tables.iter().enumerate() {
+                for table_b in &tables[i + 1..] {
+                    let (statements, objects) = counts.entry((table_a, table_b)).or_default();
+                    match object {
+                        true  => *objects += 1,
+                        false => *statements += 1,
+                    }
+                }
+            }
+        };
+        by_statement.values().for_each(|tables| count(tables, false));
+        by_object.values().for_each(|tables| count(tables, true));
+        let mut pairs: Vec<TablePair> = counts.into_iter()
+            .map(|((table_a, table_b), (statements, objects))| TablePair {
+                table_a: table_a.to_string(),
+                table_b: table_b.to_string(),
+                statements,
+                objects,
+            })
+            .collect();
+        // the sort is stable, so pairs as frequent stay sorted by name
+        pairs.sort_by_key(|pair| std::cmp::Reverse((pair.objects, pair.statements)));
+        CoOccurrence { pairs, manifest: None }
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), Error> {
+        let width = |table: fn(&TablePair) -> &String| self.pairs.iter()
+            .map(|pair| table(pair).chars().count())
+            .max().unwrap_or(0).max("table".len());
+        let (width_a, width_b) = (width(|pair| &pair.table_a), width(|pair| &pair.table_b));
+        writeln!(w, "{:<width_a$}  {:<width_b$}  {:>10}  {:>7}", "table", "table", "statements", "objects")?;
+        for pair in &self.pairs {
+            writeln!(w, "{:<width_a$}  {:<width_b$}  {:>10}  {:>7}", pair.table_a, pair.table_b, pair.statements, pair.objects)?;
+        }
+        Ok(())
+    }
+}
+// ============================================================================
+
+// census =====================================================================
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+struct CensusRow {
+    table:   String,
+    /// the number of objects (or scripts outside of any object) that read
+    /// and that write the table
+    readers: usize,
+    writers: usize,
+}
+
+/// the number of objects reading and writing each table of a list, across
+/// the indexed files (`gresql census`)
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Census {
+    tables: Vec<CensusRow>,
+}
+
+impl Census {
+    /// a census of the tables, which may be qualified by their schema to only
+    /// count the statements naming that schema. The statements whose table
+    /// name is only partly known aren't counted.
+    pub fn new<'a>(tables: &[String], statements: impl IntoIterator<Item = &'a Statement>) -> Census {
+        let tables: Vec<String> = tables.iter().map(|table| normalize_name(table.trim())).collect();
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, table) in tables.iter().enumerate() {
+            by_name.entry(split_schema(table).1.to_lowercase()).or_default().push(i);
+        }
+        let mut readers: Vec<HashSet<(&PathBuf, Option<&str>)>> = vec![HashSet::new(); tables.len()];
+        let mut writers: Vec<HashSet<(&PathBuf, Option<&str>)>> = vec![HashSet::new(); tables.len()];
+        for statement in statements.into_iter().filter(|s| !s.partial) {
+            let Some(rows) = by_name.get(&statement.table.to_lowercase()) else { continue };
+            let object = (&statement.file_path, statement.object.as_deref());
+            for &i in rows.iter().filter(|&&i| table_matches(&tables[i], statement.schema.as_deref(), &statement.table)) {
+                match statement.access() {
+                    Access::Read    => readers[i].insert(object),
+                    Access::Write   => writers[i].insert(object),
+                    Access::Execute => false,
+                };
+            }
+        }
+        let tables = tables.into_iter().enumerate()
+            .map(|(i, table)| CensusRow { table, readers: readers[i].len(), writers: writers[i].len() })
+            .collect();
+        Census { tables }
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), Error> {
+        let width = self.tables.iter().map(|row| row.table.chars().count()).max().unwrap_or(0).max("table".len());
+        writeln!(w, "{:<width$}  {:>7}  {:>7}", "table", "readers", "writers", width = width)?;
+        for row in &self.tables {
+            writeln!(w, "{:<width$}  {:>7}  {:>7}", row.table, row.readers, row.writers, width = width)?;
+        }
+        let unused = self.tables.iter().filter(|row| row.readers + row.writers == 0).count();
+        writeln!(w, "{} of {} tables are neither read nor written", unused, self.tables.len())
+    }
+}
+// ============================================================================
+
+// groups =====================================================================
+/// what the matches are counted by (`--group-by`)
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GroupBy {
+    /// the module of the file, from the `[[module]]` patterns of the config
+    Module,
+    /// the procedure (or other object) containing the statement
+    Object,
+    Table,
+    Type,
+    File,
+}
+
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+struct Group {
+    /// none for the statements without a module (or outside of any object)
+    name:       Option<String>,
+    statements: usize,
+    files:      usize,
+}
+
+/// the number of matches (and of the files they're in) of each module, object,
+/// table, ... with the largest groups first
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Groups {
+    groups: Vec<Group>,
+}
+
+impl Groups {
+    pub fn new(statements: &[Statement], group_by: GroupBy) -> Groups {
+        let mut groups: BTreeMap<Option<String>, (usize, HashSet<&PathBuf>)> = BTreeMap::new();
+        for statement in statements {
+            let name = match group_by {
+                GroupBy::Module => statement.module.clone(),
+                GroupBy::Object => statement.object.clone(),
+                GroupBy::Table  => Some(statement.table.clone()),
+                GroupBy::Type   => Some(statement.type_with_flags()),
+                GroupBy::File   => Some(statement.file_path.display().to_string()),
+            };
+            let (count, files) = groups.entry(name).or_default();
+            *count += 1;
+            files.insert(&statement.file_path);
+        }
+        let mut groups: Vec<Group> = groups.into_iter()
+            .map(|(name, (statements, files))| Group { name, statements, files: files.len() })
+            .collect();
+        // the sort is stable, so groups of the same size stay sorted by name
+        groups.sort_by_key(|group| std::cmp::Reverse(group.statements));
+        Groups { groups }
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), Error> {
+        let name = |group: &Group| group.name.clone().unwrap_or_else(|| String::from("(none)"));
+        let width = self.groups.iter().map(|group| name(group).chars().count()).max().unwrap_or(0);
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        for group in &self.groups {
+            writeln!(w, "{:<width$}  {:>6} statement{} in {} file{}", name(group),
+                group.statements, plural(group.statements), group.files, plural(group.files), width = width)?;
+        }
+        Ok(())
+    }
+}
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ByteSpan;
+
+    #[test]
+    fn count_statement_tables() {
+        let re = Regex::new(TABLE_PATTERN).unwrap();
+        assert_eq!(statement_tables(&re, "UPDATE o SET x = 1 FROM t_order o JOIN t_order_detail d ON 1 = 1"), 2);
+        assert_eq!(statement_tables(&re, "UPDATE t_order SET x = 1 WHERE id = 2"), 1);
+        assert_eq!(statement_tables(&re, "INSERT INTO t_log SELECT * FROM T_LOG"), 1);
+    }
+
+    #[test]
+    fn deploy_order_hazards() {
+        let statement = |path: &str, statement_type: StatementType, table: &str| Statement {
+            file_path: PathBuf::from(path), statement_type, table: String::from(table), schema: None,
+            begin: 0, end: 1, bytes: ByteSpan::default(), batch: 0, text: String::new(), keyword_span: None, table_spans: Vec::new(),
+            nested: false, partial: false, dynamic: false, captures: BTreeMap::new(), object: None, module: None,
+            header: None, queries: Vec::new(), tables: Vec::new(), note: None, commits: Vec::new(),
+        };
+        let paths = vec![PathBuf::from("10_report.sql"), PathBuf::from("2_wave.sql"), PathBuf::from("1_order.sql")];
+        let statements = vec![
+            statement("10_report.sql", StatementType::Select, "t_order"),
+            statement("2_wave.sql", StatementType::Select, "t_order"),
+            Statement { text: String::from("INSERT INTO t_wave SELECT * FROM dbo.[t_pick]"),
+                ..statement("2_wave.sql", StatementType::Insert, "t_wave") },
+            statement("1_order.sql", StatementType::Insert, "t_order"),
+            statement("10_report.sql", StatementType::Insert, "t_pick"),
+        ];
+        let order = DeployOrder::new(&paths, &statements);
+        let order: Vec<(&str, &Vec<Hazard>)> = order.scripts.iter()
+            .map(|s| (s.path.to_str().unwrap(), &s.hazards))
+            .collect();
+        assert_eq!(order, vec![
+            ("1_order.sql", &vec![]),
+            ("2_wave.sql", &vec![Hazard { table: String::from("t_pick"), written_by: PathBuf::from("10_report.sql") }]),
+            ("10_report.sql", &vec![]),
+        ]);
+    }
+
+    #[test]
+    fn crud_matrix() {
+        let statement = |path: &str, statement_type: StatementType, table: &str| Statement {
+            file_path: PathBuf::from(path), statement_type, table: String::from(table), schema: None,
+            begin: 0, end: 1, bytes: ByteSpan::default(), batch: 0, text: String::new(), keyword_span: None, table_spans: Vec::new(),
+            nested: false, partial: false, dynamic: false, captures: BTreeMap::new(), object: None, module: None,
+            header: None, queries: Vec::new(), tables: Vec::new(), note: None, commits: Vec::new(),
+        };
+        let statements = vec![
+            statement("b.sql", StatementType::Update, "t_order"),
+            statement("a.sql", StatementType::Select, "t_order"),
+            statement("a.sql", StatementType::Update, "T_ORDER"),
+            statement("a.sql", StatementType::Delete, "t_wave"),
+        ];
+        let crud = Crud::new(&statements, false);
+        assert_eq!(crud.statement_types, vec![StatementType::Select, StatementType::Update, StatementType::Delete]);
+        assert_eq!(crud.matrices[0].tables, vec![
+            CrudRow { table: String::from("t_order"), counts: vec![1, 2, 0] },
+            CrudRow { table: String::from("t_wave"), counts: vec![0, 0, 1] },
+        ]);
+        let crud = Crud::new(&statements, true);
+        let files: Vec<(Option<&str>, usize)> = crud.matrices.iter()
+            .map(|m| (m.file_path.as_ref().and_then(|p| p.to_str()), m.tables.len()))
+            .collect();
+        assert_eq!(files, vec![(Some("a.sql"), 2), (Some("b.sql"), 1)]);
+
+        let mut crud = Crud::new(&statements, false);
+        crud.top(1);
+        assert_eq!(crud.matrices[0].tables, vec![CrudRow { table: String::from("t_order"), counts: vec![1, 2, 0] }]);
+        assert_eq!(crud.matrices[0].omitted, 1);
+        crud.drill_down("T_Wave", &statements);
+        assert_eq!(crud.drill_down.unwrap().statements, vec![
+            CrudMatch { file_path: PathBuf::from("a.sql"), line: 1, statement_type: StatementType::Delete, object: None },
+        ]);
+    }
+
+    #[test]
+    fn co_occurrence_of_tables() {
+        let statement = |object: Option<&str>, begin: usize, statement_type: StatementType, table: &str| Statement {
+            file_path: PathBuf::from("a.sql"), statement_type, table: String::from(table), schema: None,
+            begin, end: begin, bytes: ByteSpan::default(), batch: 0, text: String::new(), keyword_span: None, table_spans: Vec::new(),
+            nested: false, partial: false, dynamic: false, captures: BTreeMap::new(), object: object.map(String::from), module: None,
+            header: None, queries: Vec::new(), tables: Vec::new(), note: None, commits: Vec::new(),
+        };
+        let statements = vec![
+            statement(Some("usp_wave_release"), 1, StatementType::Update, "t_order"),
+            statement(Some("usp_wave_release"), 2, StatementType::Insert, "t_pick"),
+            statement(Some("usp_wave_release"), 3, StatementType::Select, "t_wave"),
+            statement(Some("usp_order_archive"), 5, StatementType::Switch, "T_ORDER"),
+            statement(Some("usp_order_archive"), 5, StatementType::Switch, "t_order_archive"),
+            statement(Some("usp_order_cancel"), 8, StatementType::Delete, "t_pick"),
+            statement(Some("usp_order_cancel"), 9, StatementType::Update, "t_order"),
+        ];
+        let pair = |table_a: &str, table_b: &str, statements: usize, objects: usize| TablePair {
+            table_a: String::from(table_a), table_b: String::from(table_b), statements, objects,
+        };
+        assert_eq!(CoOccurrence::new(&statements).pairs, vec![
+            pair("t_order", "t_pick", 0, 2),
+            pair("t_order", "t_order_archive", 1, 1),
+        ]);
+    }
+
+    #[test]
+    fn census_of_tables() {
+        let statement = |object: Option<&str>, statement_type: StatementType, table: &str, schema: Option<&str>| Statement {
+            file_path: PathBuf::from("a.sql"), statement_type, table: String::from(table), schema: schema.map(String::from),
+            begin: 0, end: 1, bytes: ByteSpan::default(), batch: 0, text: String::new(), keyword_span: None, table_spans: Vec::new(),
+            nested: false, partial: false, dynamic: false, captures: BTreeMap::new(), object: object.map(String::from), module: None,
+            header: None, queries: Vec::new(), tables: Vec::new(), note: None, commits: Vec::new(),
+        };
+        let statements = vec![
+            statement(Some("usp_wave_release"), StatementType::Update, "t_order", None),
+            statement(Some("usp_wave_release"), StatementType::Delete, "T_ORDER", None),
+            statement(Some("usp_order_cancel"), StatementType::Update, "t_order", Some("dbo")),
+            statement(None, StatementType::Select, "t_order", Some("archive")),
+            Statement { nested: true, ..statement(Some("usp_order_cancel"), StatementType::Update, "t_pick", None) },
+        ];
+        let tables = [String::from("[dbo].[t_order]"), String::from("t_order"), String::from("t_pick"), String::from("t_wave")];
+        assert_eq!(Census::new(&tables, &statements).tables, vec![
+            CensusRow { table: String::from("dbo.t_order"), readers: 0, writers: 1 },
+            CensusRow { table: String::from("t_order"), readers: 1, writers: 2 },
+            CensusRow { table: String::from("t_pick"), readers: 1, writers: 0 },
+            CensusRow { table: String::from("t_wave"), readers: 0, writers: 0 },
+        ]);
+    }
+
+    #[test]
+    fn group_by_module() {
+        let statement = |path: &str, module: Option<&str>| Statement {
+            file_path: PathBuf::from(path), statement_type: StatementType::Update, table: String::from("t_order"), schema: None,
+            begin: 0, end: 1, bytes: ByteSpan::default(), batch: 0, text: String::new(), keyword_span: None, table_spans: Vec::new(),
+            nested: false, partial: false, dynamic: false, captures: BTreeMap::new(), object: None, module: module.map(String::from),
+            header: None, queries: Vec::new(), tables: Vec::new(), note: None, commits: Vec::new(),
+        };
+        let statements = vec![
+            statement("usp_wave_release.sql", Some("WaveMgmt")),
+            statement("usp_wave_release.sql", Some("WaveMgmt")),
+            statement("usp_order_cancel.sql", None),
+            statement("usp_wave_plan.sql", Some("WaveMgmt")),
+        ];
+        assert_eq!(Groups::new(&statements, GroupBy::Module).groups, vec![
+            Group { name: Some(String::from("WaveMgmt")), statements: 3, files: 2 },
+            Group { name: None, statements: 1, files: 1 },
+        ]);
+    }
+}
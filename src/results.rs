@@ -0,0 +1,309 @@
+// Results are the machine-readable form of a scan (`--format json`), which can
+// be saved and later merged or combined with the results of other scans.
+use schemars::JsonSchema;
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+use std::collections::{ BTreeMap, HashMap, HashSet };
+use std::fs::File;
+use std::io::{ BufReader, Error, ErrorKind, Write };
+use std::path::{ Path, PathBuf };
+
+use crate::catalog::{ Catalog, Declarations };
+use crate::manifest::Manifest;
+use crate::Statement;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOperation {
+    Intersect,
+    Union,
+    Subtract,
+}
+
+impl std::fmt::Display for SetOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetOperation::Intersect => write!(f, "INTERSECT"),
+            SetOperation::Union     => write!(f, "UNION"),
+            SetOperation::Subtract  => write!(f, "EXCEPT"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Results {
+    pub version:      String,
+    pub queries:      Vec<String>,
+    pub statements:   Vec<Statement>,
+    /// the tables declared in the scanned files, which name the tables of the
+    /// statements when results are merged
+    #[serde(default, skip_serializing_if = "Declarations::is_empty")]
+    pub declarations: Declarations,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest:     Option<Manifest>,
+}
+
+impl Results {
+    pub fn new(queries: &[String], statements: Vec<Statement>) -> Results {
+        Results {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            queries: queries.to_vec(),
+            statements,
+            declarations: Declarations::new(),
+            manifest: None,
+        }
+    }
+
+    /// add a manifest of the scanned files to the results
+    pub fn sign(&mut self, file_paths: &[PathBuf]) -> Result<(), Error> {
+        self.manifest = Some(Manifest::new(&self.queries, file_paths, self)?);
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Results, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)
+    }
+
+    /// combine the results of several scans of the same queries (e.g. the
+    /// shards of a distributed scan) into a single set of results, naming
+    /// their tables as they're declared in any of them
+    pub fn merge(results: Vec<Results>) -> Result<Results, Error> {
+        let mut results = results.into_iter();
+        let mut merged = match results.next() {
+            Some(first) => first,
+            None => return Err(Error::new(ErrorKind::InvalidInput, "no results to merge")),
+        };
+        for other in results {
+            if other.queries != merged.queries {
+                return Err(Error::new(ErrorKind::InvalidData, format!(
+                    "results were produced by different search queries: {} / {}",
+                    merged.queries.join(" "), other.queries.join(" "))));
+            }
+            merged.statements.extend(other.statements);
+            merged.declarations.extend(other.declarations);
+        }
+        Catalog::new(&merged.declarations).name_tables(&mut merged.statements);
+        merged.version = env!("CARGO_PKG_VERSION").to_string();
+        // the manifest of the first results doesn't describe the merged ones
+        merged.manifest = None;
+        merged.statements.sort_by(|a, b| (&a.file_path, a.begin).cmp(&(&b.file_path, b.begin)));
+        merged.statements.dedup();
+        Ok(merged)
+    }
+
+    /// combine the results of scans for different queries by the files they
+    /// matched, e.g. the intersection of `u:t_order` and `d:t_order` results
+    /// contains the statements of files that both update and delete t_order.
+    /// Subtracting keeps the files of the first results that are not in any of
+    /// the others.
+    pub fn combine(results: Vec<Results>, operation: SetOperation) -> Result<Results, Error> {
+        if results.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "no results to combine"));
+        }
+        let file_sets: Vec<HashSet<PathBuf>> = results.iter()
+            .map(|r| r.statements.iter().map(|s| s.file_path.clone()).collect())
+            .collect();
+        let keep_file = |path: &PathBuf| -> bool {
+            match operation {
+                SetOperation::Intersect => file_sets.iter().all(|files| files.contains(path)),
+                SetOperation::Union     => true,
+                SetOperation::Subtract  => !file_sets[1..].iter().any(|files| files.contains(path)),
+            }
+        };
+
+        let query = results.iter()
+            .map(|r| format!("({})", r.queries.join(" ")))
+            .collect::<Vec<String>>()
+            .join(&format!(" {} ", operation));
+        let mut statements: Vec<Statement> = Vec::new();
+        for (i, r) in results.into_iter().enumerate() {
+            // only the first results contribute statements to a subtraction
+            if operation == SetOperation::Subtract && i > 0 { break; }
+            statements.extend(r.statements.into_iter().filter(|s| keep_file(&s.file_path)));
+        }
+        statements.sort_by(|a, b| (&a.file_path, a.begin).cmp(&(&b.file_path, b.begin)));
+        statements.dedup();
+        Ok(Results::new(&[query], statements))
+    }
+}
+
+/// the matches of two runs of an audit (`gresql compare`): those only in the
+/// second run, those only in the first, and those in both
+#[derive(Serialize, JsonSchema)]
+pub struct Comparison {
+    pub added:     Vec<Fingerprinted>,
+    pub removed:   Vec<Fingerprinted>,
+    pub unchanged: Vec<Fingerprinted>,
+}
+
+/// a match and its fingerprint, which identifies it across runs
+#[derive(Serialize, JsonSchema)]
+pub struct Fingerprinted {
+    pub fingerprint: String,
+    #[serde(flatten)]
+    pub statement:   Statement,
+}
+
+impl Results {
+    /// the fingerprints of the matches, in order (see `fingerprints`)
+    pub fn fingerprints(&self) -> Vec<String> {
+        fingerprints(&self.statements)
+    }
+
+    /// compare the matches of a run with those of a later run
+    pub fn compare(self, later: Results) -> Comparison {
+        let fingerprinted = |results: Results| -> Vec<Fingerprinted> {
+            results.fingerprints().into_iter()
+                .zip(results.statements)
+                .map(|(fingerprint, statement)| Fingerprinted { fingerprint, statement })
+                .collect()
+        };
+        let before = fingerprinted(self);
+        let after = fingerprinted(later);
+        let before_fingerprints: HashSet<&String> = before.iter().map(|m| &m.fingerprint).collect();
+        let after_fingerprints: HashSet<String> = after.iter().map(|m| m.fingerprint.clone()).collect();
+        let (unchanged, added): (Vec<Fingerprinted>, Vec<Fingerprinted>) = after.into_iter()
+            .partition(|m| before_fingerprints.contains(&m.fingerprint));
+        let removed: Vec<Fingerprinted> = before.into_iter()
+            .filter(|m| !after_fingerprints.contains(&m.fingerprint))
+            .collect();
+        Comparison { added, removed, unchanged }
+    }
+}
+
+/// the fingerprints of matches, in order. A fingerprint is a hash of the file,
+/// type, table and text of a match but not of its lines, so that a match keeps
+/// its fingerprint when the lines above it change; the same statement found
+/// twice in a file is told apart by its occurrence.
+pub fn fingerprints(statements: &[Statement]) -> Vec<String> {
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    statements.iter()
+        .map(|s| {
+            let text = s.text.split_whitespace().collect::<Vec<&str>>().join(" ");
+            let key = format!("{}\0{}\0{}\0{}\0{}\0{}", s.file_path.display(), s.statement_type,
+                s.schema.as_deref().unwrap_or_default().to_lowercase(), s.table.to_lowercase(), s.nested, text);
+            let occurrence = occurrences.entry(key.clone()).or_default();
+            *occurrence += 1;
+            let digest = hex::encode(Sha256::digest(format!("{}\0{}", key, occurrence)));
+            digest[..16].to_string()
+        })
+        .collect()
+}
+
+/// the notes of the matches triaged in earlier runs (`--annotate`), a TOML
+/// file of `fingerprint = "note"` lines
+pub fn load_notes(path: &Path) -> Result<BTreeMap<String, String>, Error> {
+    let text = std::fs::read_to_string(path)?;
+    toml::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// give each match the note of its fingerprint, if it has one
+pub fn annotate(statements: &mut [Statement], notes: &BTreeMap<String, String>) {
+    let fingerprints = fingerprints(statements);
+    for (statement, fingerprint) in statements.iter_mut().zip(fingerprints) {
+        statement.note = notes.get(&fingerprint).cloned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ ByteSpan, StatementType };
+
+    fn statement(file_path: &str, statement_type: StatementType) -> Statement {
+        Statement {
+            file_path: PathBuf::from(file_path),
+            statement_type,
+            table:     String::from("t_order"),
+            schema:    None,
+            begin:     0,
+            end:       1,
+            bytes:     ByteSpan::default(),
+            batch:     0,
+            text:      String::new(),
+            keyword_span: None,
+            table_spans:  Vec::new(),
+            nested:    false,
+            partial:   false,
+            dynamic:   false,
+            captures:  Default::default(),
+            object:    None,
+            module:    None,
+            header:    None,
+            queries:   Vec::new(),
+            tables:    Vec::new(),
+            note:      None,
+            commits:   Vec::new(),
+        }
+    }
+
+    #[test]
+    fn merge_names_declared_tables() {
+        let mut shard = Results::new(&[String::from("u:t_order")], vec![statement("b.sql", StatementType::Update)]);
+        let mut other_shard = Results::new(&[String::from("u:t_order")], vec![statement("c.sql", StatementType::Update)]);
+        other_shard.declarations.insert(PathBuf::from("a.sql"), vec![String::from("T_Order")]);
+        shard.statements[0].table = String::from("T_ORDER");
+        let merged = Results::merge(vec![shard, other_shard]).unwrap();
+        let tables: Vec<&str> = merged.statements.iter().map(|s| s.table.as_str()).collect();
+        assert_eq!(tables, vec!["T_Order", "T_Order"]);
+        assert_eq!(merged.declarations.len(), 1);
+    }
+
+    #[test]
+    fn combine_by_file() {
+        let updates = || Results::new(&[String::from("u:t_order")], vec![
+            statement("a.sql", StatementType::Update),
+            statement("b.sql", StatementType::Update),
+        ]);
+        let deletes = || Results::new(&[String::from("d:t_order")], vec![
+            statement("b.sql", StatementType::Delete),
+            statement("c.sql", StatementType::Delete),
+        ]);
+        let files = |r: Results| -> Vec<String> {
+            r.statements.iter().map(|s| s.file_path.display().to_string()).collect()
+        };
+
+        let intersection = Results::combine(vec![updates(), deletes()], SetOperation::Intersect).unwrap();
+        assert_eq!(intersection.queries, vec!["(u:t_order) INTERSECT (d:t_order)"]);
+        assert_eq!(files(intersection), vec!["b.sql", "b.sql"]);
+
+        let union = Results::combine(vec![updates(), deletes()], SetOperation::Union).unwrap();
+        assert_eq!(files(union), vec!["a.sql", "b.sql", "b.sql", "c.sql"]);
+
+        let difference = Results::combine(vec![updates(), deletes()], SetOperation::Subtract).unwrap();
+        assert_eq!(files(difference), vec!["a.sql"]);
+    }
+
+    #[test]
+    fn compare_runs() {
+        let moved = Statement { begin: 10, end: 11, ..statement("a.sql", StatementType::Update) };
+        let before = Results::new(&[String::from("*:t_order")], vec![
+            statement("a.sql", StatementType::Update),
+            statement("b.sql", StatementType::Delete),
+        ]);
+        let after = Results::new(&[String::from("*:t_order")], vec![
+            moved,
+            statement("c.sql", StatementType::Insert),
+        ]);
+        let comparison = before.compare(after);
+        let files = |matches: &[Fingerprinted]| -> Vec<String> {
+            matches.iter().map(|m| m.statement.file_path.display().to_string()).collect()
+        };
+        assert_eq!(files(&comparison.unchanged), vec!["a.sql"]);
+        assert_eq!(comparison.unchanged[0].statement.begin, 10);
+        assert_eq!(files(&comparison.added), vec!["c.sql"]);
+        assert_eq!(files(&comparison.removed), vec!["b.sql"]);
+
+        let mut statements = vec![statement("a.sql", StatementType::Update), statement("a.sql", StatementType::Update)];
+        let fingerprint = fingerprints(&statements)[1].clone();
+        let notes: BTreeMap<String, String> = toml::from_str(&format!("{} = \"approved by DBA 2024-03\"", fingerprint)).unwrap();
+        annotate(&mut statements, &notes);
+        let notes: Vec<Option<&str>> = statements.iter().map(|s| s.note.as_deref()).collect();
+        assert_eq!(notes, vec![None, Some("approved by DBA 2024-03")]);
+    }
+}
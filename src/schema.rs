@@ -0,0 +1,123 @@
+// A schema is the tables (and their columns) declared by a schema script, e.g.
+// the snapshot of a database before and after a deployment. Comparing two
+// snapshots (`gresql schema-diff`) finds what a deployment removes, which the
+// code that still uses it will break on.
+use regex::Regex;
+use std::collections::BTreeMap;
+
+use crate::unbracket;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Schema {
+    // lowercase name => table
+    tables: BTreeMap<String, Table>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub name:    String,
+    pub columns: Vec<String>,
+}
+
+/// what is declared by one schema but not by the other
+#[derive(Debug, Default, PartialEq)]
+pub struct Removed {
+    pub tables:  Vec<String>,
+    /// table => columns
+    pub columns: BTreeMap<String, Vec<String>>,
+}
+
+impl Removed {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty() && self.columns.is_empty()
+    }
+}
+
+// the lines of a table definition that aren't columns
+const CONSTRAINTS: [&str; 7] = ["constraint", "primary", "foreign", "unique", "check", "index", "period"];
+
+impl Schema {
+    pub fn parse(text: &str) -> Schema {
+        let create_re = Regex::new(r"(?i)\bcreate\s+table\s+((\[[^\]]+\]|\w+)\.)*(\[[^\]]+\]|[#\w]+)\s*\(")
+            .expect("regex didn't compile");
+        let mut schema = Schema::default();
+        for captures in create_re.captures_iter(text) {
+            let name = unbracket(&captures[3]);
+            let body = &text[captures.get(0).unwrap().end()..];
+            let columns: Vec<String> = split_definitions(body).into_iter()
+                .filter_map(|definition| definition.split_whitespace().next().map(unbracket))
+                .filter(|column| !CONSTRAINTS.contains(&column.to_lowercase().as_str()))
+                .collect();
+            schema.tables.insert(name.to_lowercase(), Table { name, columns });
+        }
+        schema
+    }
+
+    /// the tables and columns of this schema that aren't in the newer one
+    pub fn removed(&self, newer: &Schema) -> Removed {
+        let mut removed = Removed::default();
+        for (key, table) in &self.tables {
+            let Some(new_table) = newer.tables.get(key) else {
+                removed.tables.push(table.name.clone());
+                continue;
+            };
+            let columns: Vec<String> = table.columns.iter()
+                .filter(|column| !new_table.columns.iter().any(|c| c.eq_ignore_ascii_case(column)))
+                .cloned()
+                .collect();
+            if !columns.is_empty() {
+                removed.columns.insert(table.name.clone(), columns);
+            }
+        }
+        removed
+    }
+}
+
+/// the comma-separated definitions between the parentheses of a CREATE TABLE,
+/// from the text that follows the opening parenthesis
+fn split_definitions(body: &str) -> Vec<&str> {
+    let mut definitions: Vec<&str> = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in body.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                definitions.push(&body[start..i]);
+                break;
+            },
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                definitions.push(&body[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    definitions.into_iter().map(str::trim).filter(|d| !d.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_tables_and_columns() {
+        let old = Schema::parse("\
+CREATE TABLE dbo.t_order (
+    id          INT NOT NULL,
+    [status]    TINYINT,
+    legacy_code VARCHAR(10),
+    amount      DECIMAL(10, 2),
+    CONSTRAINT pk_order PRIMARY KEY (id)
+)
+GO
+CREATE TABLE t_order_archive (id INT)
+");
+        let new = Schema::parse("CREATE TABLE [dbo].[t_order] (id INT NOT NULL, status TINYINT, amount DECIMAL(10, 2))");
+        let removed = old.removed(&new);
+        assert_eq!(removed.tables, vec!["t_order_archive"]);
+        assert_eq!(removed.columns.get("t_order"), Some(&vec![String::from("legacy_code")]));
+        assert!(new.removed(&old).is_empty());
+    }
+}
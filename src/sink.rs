@@ -0,0 +1,228 @@
+// A sink is where the results of a scan are written (`--output`), in one of
+// the output formats, so that a single scan can feed both people (a table on
+// stdout) and tools (NDJSON or SARIF files).
+use clap::ValueEnum;
+use serde_json::{ json, Value };
+use std::fs::File;
+use std::io::{ BufWriter, Error, Write };
+use std::path::PathBuf;
+
+use gresql::{ find_table_mentions, Statement };
+
+use crate::color;
+use crate::OutputFormat;
+
+/// the jump list that `gresql jump` reads, unless another is given
+pub const DEFAULT_JUMPLIST_FILE: &str = ".gresql-jumplist";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sink {
+    pub format: OutputFormat,
+    /// the file to write to, or stdout
+    pub path:   Option<PathBuf>,
+}
+
+impl Sink {
+    pub fn stdout(format: OutputFormat) -> Sink {
+        Sink { format, path: None }
+    }
+
+    pub fn open(&self) -> Result<Box<dyn Write>, Error> {
+        match &self.path {
+            Some(path) => Ok(Box::new(BufWriter::new(File::create(path)?))),
+            None => Ok(Box::new(std::io::stdout().lock())),
+        }
+    }
+}
+
+impl std::fmt::Display for Sink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}", path.display()),
+            None => write!(f, "stdout"),
+        }
+    }
+}
+
+/// parse a sink given as `FORMAT` (to stdout) or `FORMAT=PATH`, where a PATH
+/// of `-` is also stdout
+pub fn parse_sink(s: &str) -> Result<Sink, String> {
+    let (format, path) = match s.split_once('=') {
+        Some((format, "-")) => (format, None),
+        Some((format, path)) => (format, Some(PathBuf::from(path))),
+        None => (s, None),
+    };
+    let format = OutputFormat::from_str(format, true)?;
+    Ok(Sink { format, path })
+}
+
+/// a SARIF 2.1.0 log of the statements, with a result per statement whose
+/// rule is the type of the statement
+pub fn sarif(statements: &[Statement]) -> Value {
+    sarif_log(statements.iter()
+        .map(|s| sarif_result(s, &s.statement_type.to_string(), "note", &format!("{} of {}", s.statement_type, s.table)))
+        .collect())
+}
+
+/// a SARIF result at the lines (and in the object) of a statement
+pub fn sarif_result(s: &Statement, rule_id: &str, level: &str, message: &str) -> Value {
+    json!({
+        "ruleId": rule_id,
+        "level": level,
+        "message": { "text": message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": s.file_path.display().to_string() },
+                "region": { "startLine": s.begin + 1, "endLine": s.end.max(s.begin + 1) },
+            },
+            // the procedure (or other object) of the statement
+            "logicalLocations": s.object.iter().map(|object| json!({ "name": object })).collect::<Vec<Value>>(),
+        }],
+    })
+}
+
+/// a SARIF 2.1.0 log of gresql's results
+pub fn sarif_log(results: Vec<Value>) -> Value {
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": "gresql", "version": env!("CARGO_PKG_VERSION") } },
+            "results": results,
+        }],
+    })
+}
+
+/// a statement as a line of `grep -n` (or ripgrep's `--vimgrep`) output,
+/// `path:line:column: message`, e.g. for Vim's quickfix list. The line and
+/// (byte) column, from 1, are those of the first mention of the table in the
+/// text of the file, or the beginning of the statement if it isn't found.
+/// With `color`, the location is colored and the matches highlighted.
+pub fn vimgrep_line(s: &Statement, file_text: &str, hide_statement: bool, color: bool) -> String {
+    let (line, column) = location(s, file_text);
+    let mut message = summary(s);
+    if let Some(note) = &s.note {
+        message.push_str(&format!(" [{}]", note));
+    }
+    if !s.commits.is_empty() {
+        message.push_str(&format!(" ({})", s.commits.join(" ")));
+    }
+    if !hide_statement {
+        let text = match color {
+            true  => color::highlight(&s.text, s.keyword_span, &s.table_spans),
+            false => s.text.clone(),
+        };
+        message.push_str(&format!(": {}", text.trim()));
+    }
+    match color {
+        true  => format!("{}:{}:{}: {}", color::path(&s.file_path.display().to_string()),
+            color::line(&line.to_string()), color::line(&column.to_string()), message),
+        false => format!("{}:{}:{}: {}", s.file_path.display(), line, column, message),
+    }
+}
+
+/// the line and (byte) column, from 1, of the first mention of the table of a
+/// statement in the text of its file, or of the beginning of the statement if
+/// it isn't found
+fn location(s: &Statement, file_text: &str) -> (usize, usize) {
+    let statement_text = file_text.get(s.bytes.begin..s.bytes.end).unwrap_or_default();
+    match find_table_mentions(statement_text, &s.table, s.partial).first() {
+        Some(span) => {
+            let before = &statement_text[..span.begin];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (s.begin + 1 + before.matches('\n').count(), span.begin - line_start + 1)
+        },
+        None => (s.begin + 1, 1),
+    }
+}
+
+/// what a statement is, e.g. `UPDATE of t_order in usp_order_cancel`
+fn summary(s: &Statement) -> String {
+    let mut summary = format!("{} of {}", s.type_with_flags(), s.table);
+    if let Some(object) = &s.object {
+        summary.push_str(&format!(" in {}", object));
+    }
+    summary
+}
+
+/// a statement as a line of a jump list, `path<TAB>line<TAB>column<TAB>summary`,
+/// at the same location as its `vimgrep` line
+pub fn jumplist_line(s: &Statement, file_text: &str) -> String {
+    let (line, column) = location(s, file_text);
+    format!("{}\t{}\t{}\t{}", s.file_path.display(), line, column, summary(s))
+}
+
+/// the n-th location (from 1) of a jump list, as `path:line:column`
+pub fn jump_location(jumplist: &str, n: usize) -> Option<String> {
+    let entry = jumplist.lines().filter(|line| !line.is_empty()).nth(n.checked_sub(1)?)?;
+    let mut fields = entry.splitn(4, '\t');
+    let (path, line, column) = (fields.next()?, fields.next()?, fields.next()?);
+    Some(format!("{}:{}:{}", path, line, column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gresql::{ ByteSpan, StatementType };
+
+    #[test]
+    fn parse_sinks() {
+        assert_eq!(parse_sink("table"), Ok(Sink::stdout(OutputFormat::Table)));
+        assert_eq!(parse_sink("ndjson=-"), Ok(Sink::stdout(OutputFormat::Ndjson)));
+        assert_eq!(parse_sink("sarif=out/gresql.sarif"),
+            Ok(Sink { format: OutputFormat::Sarif, path: Some(PathBuf::from("out/gresql.sarif")) }));
+        assert!(parse_sink("xml=out.xml").is_err());
+    }
+
+    const FILE_TEXT: &str = "SELECT 1\n\nUPDATE o\n    SET status = 9\n    FROM dbo.t_order o\n";
+
+    fn update_statement() -> Statement {
+        Statement {
+            file_path:      PathBuf::from("src/usp_order.sql"),
+            statement_type: StatementType::Update,
+            table:          String::from("t_order"),
+            schema:         Some(String::from("dbo")),
+            begin:          2,
+            end:            5,
+            bytes:          ByteSpan { begin: 10, end: FILE_TEXT.len() },
+            batch:          0,
+            text:           String::from("UPDATE o SET status = 9 FROM dbo.t_order o "),
+            keyword_span:   None,
+            table_spans:    Vec::new(),
+            nested:         false,
+            partial:        false,
+            dynamic:        false,
+            captures:       Default::default(),
+            object:         Some(String::from("usp_order_cancel")),
+            module:         None,
+            header:         None,
+            queries:        Vec::new(),
+            tables:         Vec::new(),
+            note:           None,
+            commits:        Vec::new(),
+        }
+    }
+
+    #[test]
+    fn write_vimgrep_lines() {
+        let file_text = FILE_TEXT;
+        let statement = update_statement();
+        assert_eq!(vimgrep_line(&statement, file_text, false, false),
+            "src/usp_order.sql:5:14: UPDATE of t_order in usp_order_cancel: UPDATE o SET status = 9 FROM dbo.t_order o");
+        assert_eq!(vimgrep_line(&statement, "", true, false), "src/usp_order.sql:3:1: UPDATE of t_order in usp_order_cancel");
+        let jumplist = jumplist_line(&statement, file_text);
+        assert_eq!(jumplist, "src/usp_order.sql\t5\t14\tUPDATE of t_order in usp_order_cancel");
+        assert_eq!(jump_location(&jumplist, 1), Some(String::from("src/usp_order.sql:5:14")));
+        assert_eq!(jump_location(&jumplist, 2), None);
+        assert_eq!(jump_location(&jumplist, 0), None);
+    }
+
+    #[test]
+    fn write_sarif_results() {
+        let result = sarif_result(&update_statement(), "protected-table", "error", "UPDATE of t_order by usp_order_cancel isn't allowed");
+        assert_eq!(result["ruleId"], "protected-table");
+        assert_eq!(result["locations"][0]["physicalLocation"]["region"], json!({ "startLine": 3, "endLine": 5 }));
+        assert_eq!(result["locations"][0]["logicalLocations"][0]["name"], "usp_order_cancel");
+        assert_eq!(sarif_log(vec![result])["runs"][0]["results"].as_array().map(Vec::len), Some(1));
+    }
+}
@@ -0,0 +1,134 @@
+// The table format (`--format table`) is for people rather than scripts: the
+// columns are aligned and limited in width, and the last column (usually the
+// statement text) is wrapped to the width of the terminal.
+use std::io::{ Error, Write };
+use unicode_width::{ UnicodeWidthChar, UnicodeWidthStr };
+
+const SEPARATOR: &str = "  ";
+// the last column is never narrower than this, even on a narrow terminal
+const MIN_LAST_WIDTH: usize = 20;
+
+pub struct Table {
+    headers: Vec<String>,
+    rows:    Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<String>) -> Table {
+        Table { headers, rows: Vec::new() }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// write the table within `width` columns. Every column but the last is at
+    /// most `max_column_width` wide (longer values are truncated), the last
+    /// column wraps within the rest of the width.
+    pub fn write<W: Write>(&self, mut w: W, width: usize, max_column_width: usize) -> Result<(), Error> {
+        let last = self.headers.len().saturating_sub(1);
+        let mut widths: Vec<usize> = self.headers.iter()
+            .enumerate()
+            .map(|(i, header)| {
+                self.rows.iter()
+                    .map(|row| row[i].width())
+                    .fold(header.width(), usize::max)
+                    .min(max_column_width)
+            })
+            .collect();
+        let used: usize = widths[..last].iter().map(|w| w + SEPARATOR.len()).sum();
+        let last_width = self.rows.iter().map(|row| row[last].width()).fold(self.headers[last].width(), usize::max);
+        widths[last] = width.saturating_sub(used).max(MIN_LAST_WIDTH).min(last_width);
+        // numbers (e.g. line numbers) are right-aligned
+        let numeric: Vec<bool> = (0..self.headers.len())
+            .map(|i| !self.rows.is_empty() && self.rows.iter().all(|row| row[i].parse::<f64>().is_ok()))
+            .collect();
+
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        self.write_row(&mut w, &self.headers, &widths, &[])?;
+        self.write_row(&mut w, &rule, &widths, &[])?;
+        for row in &self.rows {
+            self.write_row(&mut w, row, &widths, &numeric)?;
+        }
+        Ok(())
+    }
+
+    fn write_row<W: Write>(&self, w: &mut W, row: &[String], widths: &[usize], numeric: &[bool]) -> Result<(), Error> {
+        let last = row.len() - 1;
+        let mut line = String::new();
+        for (i, cell) in row[..last].iter().enumerate() {
+            let cell = truncate(cell, widths[i]);
+            let padding = " ".repeat(widths[i] - cell.width());
+            match numeric.get(i) {
+                Some(true) => line.push_str(&format!("{}{}{}", padding, cell, SEPARATOR)),
+                _ => line.push_str(&format!("{}{}{}", cell, padding, SEPARATOR)),
+            }
+        }
+        let indent = " ".repeat(line.width());
+        for (i, text) in wrap(&row[last], widths[last]).iter().enumerate() {
+            match i {
+                0 => writeln!(w, "{}{}", line, text)?,
+                _ => writeln!(w, "{}{}", indent, text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// cut a value down to a display width, marking that it was cut
+fn truncate(s: &str, width: usize) -> String {
+    if s.width() <= width { return s.to_string(); }
+    let mut truncated = String::new();
+    for c in s.chars() {
+        if truncated.width() + c.width().unwrap_or(0) + 1 > width { break; }
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// break text into lines of at most `width` (display) columns at whitespace,
+/// splitting the words that are too long for a line of their own
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.width() + 1 + word.width() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() { line.push(' '); }
+        for c in word.chars() {
+            if line.width() + c.width().unwrap_or(0) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_table() {
+        let mut table = Table::new(vec![String::from("path"), String::from("begin"), String::from("text")]);
+        table.push(vec![String::from("procs/usp_allocate_wave.sql"), String::from("12"),
+            String::from("UPDATE t_order SET status = 1 WHERE id = @id")]);
+        table.push(vec![String::from("a.sql"), String::from("7"), String::from("DELETE FROM t_order")]);
+        let mut out: Vec<u8> = Vec::new();
+        table.write(&mut out, 36, 12).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+path          begin  text
+------------  -----  --------------------
+procs/usp_a…     12  UPDATE t_order SET
+                     status = 1 WHERE id
+                     = @id
+a.sql             7  DELETE FROM t_order
+");
+    }
+}
@@ -0,0 +1,41 @@
+// The tests that need real files write them to a directory of their own, which
+// is removed when it's dropped, even when an assert fails.
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+/// a new directory in the temporary directory, unique to the test (tests run
+/// in parallel, in the same process)
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        static DIRS: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir()
+            .join(format!("gresql-{}-{}-{}", name, std::process::id(), DIRS.fetch_add(1, Ordering::Relaxed)));
+        std::fs::create_dir_all(&path).expect("unable to create the directory of a test");
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// write a file of the directory (and the directories it's in), returning
+    /// its path
+    pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.path.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("unable to create the directory of a test file");
+        }
+        std::fs::write(&path, contents).expect("unable to write a test file");
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
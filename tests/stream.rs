@@ -0,0 +1,27 @@
+use std::process::Command;
+
+// a sorted stream names the tables as they're declared in the files walked
+// before, like a scan of the file list
+#[test]
+fn stream_matches_scan() {
+    let dir = std::env::temp_dir().join(format!("gresql-stream-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.sql"), "CREATE TABLE T_Order (id INT)\nGO\nUPDATE T_ORDER SET id = 0\n").unwrap();
+    std::fs::write(dir.join("b.sql"), "UPDATE t_order SET id = 1\n").unwrap();
+
+    let gresql = |stream: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_gresql"))
+            .args(["-s", "u:t_order", "-f", "vimgrep", "--no-summary"])
+            .args(stream)
+            .arg(&dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let scan = gresql(&[]);
+    let stream = gresql(&["--stream", "--sorted"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(scan.matches("UPDATE of T_Order").count(), 2, "{scan}");
+    assert_eq!(stream, scan);
+}